@@ -0,0 +1,153 @@
+use bevy::prelude::*;
+use bevy_egui::{EguiClipboard, EguiContexts};
+
+use crate::history::{History, ObjectLifetime};
+use crate::outliner::{is_locked, Locked};
+use crate::scene::SceneFile;
+use crate::ui::{SelectMode, Selectable, UiState};
+
+pub struct EditActionsPlugin;
+
+impl Plugin for EditActionsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<EditAction>()
+           .init_resource::<ClipboardState>()
+           .add_systems(Update, (
+               edit_shortcuts,
+               apply_edit_actions.after(edit_shortcuts),
+           ));
+    }
+}
+
+// How far duplicated and pasted objects are moved from the originals
+const COPY_OFFSET: Vec3 = Vec3::new(1.0, 0.0, 1.0);
+
+// Sent by the Edit menu, the Inspector and keyboard shortcuts; all act on the selection
+#[derive(Event, Clone, Copy, PartialEq, Debug)]
+pub enum EditAction {
+    Copy,
+    Paste,
+    Duplicate,
+    Delete,
+}
+
+#[derive(Resource, Default)]
+struct ClipboardState {
+    // Pastes since the last copy, each one lands a little further from the originals
+    pastes: u32,
+}
+
+fn edit_shortcuts(
+    keyboard: Res<Input<KeyCode>>,
+    mut actions: EventWriter<EditAction>,
+    mut contexts: EguiContexts,
+) {
+    // Text fields use the same shortcuts for their own clipboard
+    if contexts.ctx_mut().wants_keyboard_input() {
+        return;
+    }
+
+    let ctrl = keyboard.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    if keyboard.just_pressed(KeyCode::Delete) {
+        actions.send(EditAction::Delete);
+    }
+    if ctrl && keyboard.just_pressed(KeyCode::C) {
+        actions.send(EditAction::Copy);
+    }
+    if ctrl && keyboard.just_pressed(KeyCode::V) {
+        actions.send(EditAction::Paste);
+    }
+    if ctrl && keyboard.just_pressed(KeyCode::D) {
+        actions.send(EditAction::Duplicate);
+    }
+}
+
+// Selected objects that may be edited, primary selection last
+fn editable_selection(world: &World) -> Vec<Entity> {
+    world
+        .resource::<UiState>()
+        .selection
+        .iter()
+        .copied()
+        .filter(|entity| {
+            world.get_entity(*entity).is_some_and(|entity| {
+                entity.contains::<Selectable>() && !is_locked(entity.get::<Locked>())
+            })
+        })
+        .collect()
+}
+
+// Spawns copies of the objects in `scene`, selects them and records them in history
+fn spawn_copies(world: &mut World, scene: &SceneFile, offset: Vec3, label: &str) {
+    if scene.objects.is_empty() {
+        return;
+    }
+    let (objects, _) = scene.spawn_objects_in_world(world, offset);
+
+    let mut ui_state = world.resource_mut::<UiState>();
+    ui_state.clear_selection();
+    for entity in &objects {
+        ui_state.select(*entity, SelectMode::Add);
+    }
+
+    let mut history = world.resource_mut::<History>();
+    history.push(ObjectLifetime::spawned(label, objects));
+    history.seal();
+}
+
+fn apply_edit_actions(world: &mut World) {
+    let actions: Vec<EditAction> = world.resource_mut::<Events<EditAction>>().drain().collect();
+
+    for action in actions {
+        match action {
+            EditAction::Copy => {
+                let selection = editable_selection(world);
+                if selection.is_empty() {
+                    continue;
+                }
+                let (scene, _) = SceneFile::capture_objects(world, &selection);
+                match scene.to_ron() {
+                    Ok(text) => {
+                        world.resource_mut::<EguiClipboard>().set_contents(&text);
+                        world.resource_mut::<ClipboardState>().pastes = 0;
+                    }
+                    Err(err) => warn!("Failed to copy the selection: {}", err),
+                }
+            }
+            EditAction::Paste => {
+                let Some(text) = world.resource::<EguiClipboard>().get_contents() else {
+                    continue;
+                };
+                // The clipboard holds a scene file, so objects can be pasted into other scenes and editors
+                let scene = match SceneFile::from_ron(&text) {
+                    Ok(scene) => scene,
+                    Err(err) => {
+                        warn!("Clipboard does not contain objects: {}", err);
+                        continue;
+                    }
+                };
+                let mut clipboard = world.resource_mut::<ClipboardState>();
+                clipboard.pastes += 1;
+                let offset = COPY_OFFSET * clipboard.pastes as f32;
+                spawn_copies(world, &scene, offset, "Paste");
+            }
+            EditAction::Duplicate => {
+                let selection = editable_selection(world);
+                let (scene, _) = SceneFile::capture_objects(world, &selection);
+                spawn_copies(world, &scene, COPY_OFFSET, "Duplicate");
+            }
+            EditAction::Delete => {
+                let selection = editable_selection(world);
+                if selection.is_empty() {
+                    continue;
+                }
+                let command = ObjectLifetime::delete(world, "Delete", selection.clone());
+                world.resource_mut::<UiState>().selection.retain(|entity| !selection.contains(entity));
+
+                let mut history = world.resource_mut::<History>();
+                history.push(command);
+                history.seal();
+            }
+        }
+    }
+}
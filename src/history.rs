@@ -0,0 +1,455 @@
+use std::any::Any;
+
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use bevy_egui::EguiContexts;
+
+use crate::joints::{spawn_joint_in_world, JointSpec};
+use crate::scene::SceneFile;
+use crate::ui::UiState;
+
+pub struct HistoryPlugin;
+
+impl Plugin for HistoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<History>()
+           .add_systems(Update, (
+               history_shortcuts,
+               apply_history_request.after(history_shortcuts),
+               seal_history,
+           ));
+    }
+}
+
+// Old entity ids and the ids they were respawned under
+pub type EntityRemap = Vec<(Entity, Entity)>;
+
+// A reversible edit. Commands are pushed after the edit has already been applied,
+// so `undo` and `redo` only ever replay recorded state.
+pub trait EditCommand: Send + Sync + 'static {
+    fn label(&self) -> String;
+    fn undo(&mut self, world: &mut World) -> EntityRemap;
+    fn redo(&mut self, world: &mut World) -> EntityRemap;
+    fn remap_entity(&mut self, from: Entity, to: Entity);
+    fn as_any(&self) -> &dyn Any;
+
+    // Absorb a newer command into this one, used to collapse continuous drags
+    fn merge(&mut self, _newer: &dyn EditCommand) -> bool {
+        false
+    }
+}
+
+// A change of one component type, on one entity or on every object of a multi-selection
+pub struct ComponentChange<C: Component + Clone> {
+    changes: Vec<(Entity, C, C)>,
+}
+
+impl<C: Component + Clone> ComponentChange<C> {
+    pub fn new(entity: Entity, before: C, after: C) -> Self {
+        Self { changes: vec![(entity, before, after)] }
+    }
+
+    // Entries are (entity, before, after)
+    pub fn batch(changes: Vec<(Entity, C, C)>) -> Self {
+        Self { changes }
+    }
+}
+
+impl<C: Component + Clone> EditCommand for ComponentChange<C> {
+    fn label(&self) -> String {
+        let name = std::any::type_name::<C>();
+        let name = name.rsplit("::").next().unwrap_or(name);
+        match self.changes.len() {
+            1 => format!("Edit {}", name),
+            count => format!("Edit {} ({} objects)", name, count),
+        }
+    }
+
+    fn undo(&mut self, world: &mut World) -> EntityRemap {
+        for (entity, before, _) in &self.changes {
+            if let Some(mut entity) = world.get_entity_mut(*entity) {
+                entity.insert(before.clone());
+            }
+        }
+        Vec::new()
+    }
+
+    fn redo(&mut self, world: &mut World) -> EntityRemap {
+        for (entity, _, after) in &self.changes {
+            if let Some(mut entity) = world.get_entity_mut(*entity) {
+                entity.insert(after.clone());
+            }
+        }
+        Vec::new()
+    }
+
+    fn remap_entity(&mut self, from: Entity, to: Entity) {
+        for (entity, _, _) in &mut self.changes {
+            if *entity == from {
+                *entity = to;
+            }
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn merge(&mut self, newer: &dyn EditCommand) -> bool {
+        let Some(newer) = newer.as_any().downcast_ref::<Self>() else {
+            return false;
+        };
+        let same_entities = self.changes.len() == newer.changes.len()
+            && self.changes.iter().zip(&newer.changes).all(|(a, b)| a.0 == b.0);
+        if !same_entities {
+            return false;
+        }
+        for (change, newer) in self.changes.iter_mut().zip(&newer.changes) {
+            change.2 = newer.2.clone();
+        }
+        true
+    }
+}
+
+// Several commands recorded as a single history entry
+pub struct CommandGroup {
+    label: String,
+    commands: Vec<Box<dyn EditCommand>>,
+}
+
+impl CommandGroup {
+    pub fn new(label: impl Into<String>) -> Self {
+        Self { label: label.into(), commands: Vec::new() }
+    }
+
+    pub fn add(&mut self, command: impl EditCommand) {
+        self.commands.push(Box::new(command));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    // Entities respawned by one command are passed on to the others in the group
+    fn remap_all(&mut self, remap: &EntityRemap) {
+        for (from, to) in remap {
+            self.remap_entity(*from, *to);
+        }
+    }
+}
+
+impl EditCommand for CommandGroup {
+    fn label(&self) -> String {
+        self.label.clone()
+    }
+
+    fn undo(&mut self, world: &mut World) -> EntityRemap {
+        let mut remap = Vec::new();
+        for index in (0..self.commands.len()).rev() {
+            let respawned = self.commands[index].undo(world);
+            self.remap_all(&respawned);
+            remap.extend(respawned);
+        }
+        remap
+    }
+
+    fn redo(&mut self, world: &mut World) -> EntityRemap {
+        let mut remap = Vec::new();
+        for index in 0..self.commands.len() {
+            let respawned = self.commands[index].redo(world);
+            self.remap_all(&respawned);
+            remap.extend(respawned);
+        }
+        remap
+    }
+
+    fn remap_entity(&mut self, from: Entity, to: Entity) {
+        for command in &mut self.commands {
+            command.remap_entity(from, to);
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+// A joint from a captured object to a body that stays in the world
+struct ExternalJoint {
+    joint: Entity,
+    // Index into the captured objects
+    object: usize,
+    other: Entity,
+    object_is_body1: bool,
+    spec: JointSpec,
+}
+
+// Objects taken out of the world, with every joint they took part in
+struct CapturedObjects {
+    scene: SceneFile,
+    // Entities of `scene.joints`, in the same order
+    joints: Vec<Entity>,
+    external: Vec<ExternalJoint>,
+}
+
+// Spawning (or, with `spawned: false`, deleting) a set of editor objects.
+// The objects are captured whenever they leave the world so they can be rebuilt.
+pub struct ObjectLifetime {
+    label: String,
+    entities: Vec<Entity>,
+    captured: Option<CapturedObjects>,
+    spawned: bool,
+}
+
+impl ObjectLifetime {
+    // For objects that were just spawned
+    pub fn spawned(label: impl Into<String>, entities: Vec<Entity>) -> Self {
+        Self { label: label.into(), entities, captured: None, spawned: true }
+    }
+
+    // Captures and despawns the objects, recording their deletion
+    pub fn delete(world: &mut World, label: impl Into<String>, entities: Vec<Entity>) -> Self {
+        let mut command = Self { label: label.into(), entities, captured: None, spawned: false };
+        command.remove(world);
+        command
+    }
+
+    fn remove(&mut self, world: &mut World) -> EntityRemap {
+        let (scene, joints) = SceneFile::capture_objects(world, &self.entities);
+
+        let external: Vec<ExternalJoint> = world
+            .iter_entities()
+            .filter_map(|entity| {
+                let spec = entity.get::<JointSpec>()?;
+                let body1 = entity.get::<ImpulseJoint>()?.parent;
+                let body2 = entity.get::<Parent>()?.get();
+                let index1 = self.entities.iter().position(|object| *object == body1);
+                let index2 = self.entities.iter().position(|object| *object == body2);
+                let (object, other, object_is_body1) = match (index1, index2) {
+                    (Some(object), None) => (object, body2, true),
+                    (None, Some(object)) => (object, body1, false),
+                    _ => return None,
+                };
+                Some(ExternalJoint { joint: entity.id(), object, other, object_is_body1, spec: *spec })
+            })
+            .collect();
+
+        // Joints are children of their second body, so those on a remaining body need removing by hand
+        for joint in external.iter().filter(|joint| joint.object_is_body1) {
+            if let Some(entity) = world.get_entity_mut(joint.joint) {
+                entity.despawn_recursive();
+            }
+        }
+        for entity in &self.entities {
+            if let Some(entity) = world.get_entity_mut(*entity) {
+                entity.despawn_recursive();
+            }
+        }
+
+        self.captured = Some(CapturedObjects { scene, joints, external });
+        Vec::new()
+    }
+
+    fn restore(&mut self, world: &mut World) -> EntityRemap {
+        let Some(captured) = self.captured.take() else {
+            return Vec::new();
+        };
+        let (objects, joints) = captured.scene.spawn_objects_in_world(world, Vec3::ZERO);
+
+        let mut remap: EntityRemap = self.entities.iter().copied().zip(objects.iter().copied()).collect();
+        remap.extend(captured.joints.into_iter().zip(joints));
+        for joint in captured.external {
+            if world.get_entity(joint.other).is_none() {
+                continue;
+            }
+            let object = objects[joint.object];
+            let (body1, body2) = if joint.object_is_body1 { (object, joint.other) } else { (joint.other, object) };
+            remap.push((joint.joint, spawn_joint_in_world(world, body1, body2, joint.spec)));
+        }
+
+        self.entities = objects;
+        remap
+    }
+}
+
+impl EditCommand for ObjectLifetime {
+    fn label(&self) -> String {
+        match self.entities.len() {
+            1 => self.label.clone(),
+            count => format!("{} ({} objects)", self.label, count),
+        }
+    }
+
+    fn undo(&mut self, world: &mut World) -> EntityRemap {
+        if self.spawned {
+            self.remove(world)
+        } else {
+            self.restore(world)
+        }
+    }
+
+    fn redo(&mut self, world: &mut World) -> EntityRemap {
+        if self.spawned {
+            self.restore(world)
+        } else {
+            self.remove(world)
+        }
+    }
+
+    fn remap_entity(&mut self, from: Entity, to: Entity) {
+        for entity in &mut self.entities {
+            if *entity == from {
+                *entity = to;
+            }
+        }
+        if let Some(captured) = &mut self.captured {
+            for joint in &mut captured.external {
+                if joint.other == from {
+                    joint.other = to;
+                }
+            }
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum HistoryRequest {
+    Undo,
+    Redo,
+}
+
+#[derive(Resource, Default)]
+pub struct History {
+    undo_stack: Vec<Box<dyn EditCommand>>,
+    redo_stack: Vec<Box<dyn EditCommand>>,
+    // While open, a matching command is merged into the top entry instead of pushed
+    open: bool,
+    request: Option<HistoryRequest>,
+}
+
+impl History {
+    pub fn push(&mut self, command: impl EditCommand) {
+        self.redo_stack.clear();
+        if self.open {
+            if let Some(top) = self.undo_stack.last_mut() {
+                if top.merge(&command) {
+                    return;
+                }
+            }
+        }
+        self.undo_stack.push(Box::new(command));
+        self.open = true;
+    }
+
+    // Ends the current interaction so the next edit starts a new entry
+    pub fn seal(&mut self) {
+        self.open = false;
+    }
+
+    pub fn clear(&mut self) {
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+        self.open = false;
+        self.request = None;
+    }
+
+    pub fn request(&mut self, request: HistoryRequest) {
+        self.request = Some(request);
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo_stack.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo_stack.is_empty()
+    }
+
+    pub fn undo_label(&self) -> Option<String> {
+        self.undo_stack.last().map(|command| command.label())
+    }
+
+    pub fn redo_label(&self) -> Option<String> {
+        self.redo_stack.last().map(|command| command.label())
+    }
+
+    fn remap(&mut self, from: Entity, to: Entity) {
+        for command in self.undo_stack.iter_mut().chain(self.redo_stack.iter_mut()) {
+            command.remap_entity(from, to);
+        }
+    }
+}
+
+fn history_shortcuts(
+    keyboard: Res<Input<KeyCode>>,
+    mut history: ResMut<History>,
+    mut contexts: EguiContexts,
+) {
+    // Let text fields keep their own undo behaviour
+    if contexts.ctx_mut().wants_keyboard_input() {
+        return;
+    }
+
+    let ctrl = keyboard.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    let shift = keyboard.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    if ctrl && keyboard.just_pressed(KeyCode::Z) {
+        history.request(if shift { HistoryRequest::Redo } else { HistoryRequest::Undo });
+    }
+}
+
+fn seal_history(
+    mouse_button: Res<Input<MouseButton>>,
+    mut history: ResMut<History>,
+) {
+    // Drags (in the viewport or on egui widgets) hold the left button for their whole duration
+    if history.open && !mouse_button.pressed(MouseButton::Left) {
+        history.seal();
+    }
+}
+
+fn apply_history_request(world: &mut World) {
+    world.resource_scope(|world, mut history: Mut<History>| {
+        let Some(request) = history.request.take() else {
+            return;
+        };
+        history.seal();
+
+        let remap = match request {
+            HistoryRequest::Undo => {
+                let Some(mut command) = history.undo_stack.pop() else {
+                    return;
+                };
+                let remap = command.undo(world);
+                history.redo_stack.push(command);
+                remap
+            }
+            HistoryRequest::Redo => {
+                let Some(mut command) = history.redo_stack.pop() else {
+                    return;
+                };
+                let remap = command.redo(world);
+                history.undo_stack.push(command);
+                remap
+            }
+        };
+
+        for (old, new) in remap {
+            history.remap(old, new);
+            for entity in world.resource_mut::<UiState>().selection.iter_mut() {
+                if *entity == old {
+                    *entity = new;
+                }
+            }
+        }
+
+        // Undoing a spawn can remove selected entities
+        let selection = std::mem::take(&mut world.resource_mut::<UiState>().selection);
+        let selection = selection.into_iter().filter(|entity| world.get_entity(*entity).is_some()).collect();
+        world.resource_mut::<UiState>().selection = selection;
+    });
+}
+
@@ -0,0 +1,378 @@
+use std::any::Any;
+
+use bevy::prelude::*;
+use bevy_egui::egui;
+use bevy_rapier3d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::history::{ComponentChange, EditCommand, EntityRemap, History};
+use crate::ui::UiState;
+
+pub struct JointsPlugin;
+
+impl Plugin for JointsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, (
+            sync_joint_data,
+            draw_joints,
+        ));
+    }
+}
+
+#[derive(Default, PartialEq, Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum JointKind {
+    #[default]
+    Fixed,
+    Revolute,
+    Prismatic,
+    Spherical,
+    Rope,
+}
+
+impl JointKind {
+    pub const ALL: [JointKind; 5] = [
+        JointKind::Fixed,
+        JointKind::Revolute,
+        JointKind::Prismatic,
+        JointKind::Spherical,
+        JointKind::Rope,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            JointKind::Fixed => "Fixed",
+            JointKind::Revolute => "Revolute",
+            JointKind::Prismatic => "Prismatic",
+            JointKind::Spherical => "Spherical",
+            JointKind::Rope => "Rope",
+        }
+    }
+
+    fn has_axis(self) -> bool {
+        matches!(self, JointKind::Revolute | JointKind::Prismatic)
+    }
+
+    fn has_limits(self) -> bool {
+        matches!(self, JointKind::Revolute | JointKind::Prismatic)
+    }
+
+    fn color(self) -> Color {
+        match self {
+            JointKind::Fixed => Color::WHITE,
+            JointKind::Revolute => Color::ORANGE,
+            JointKind::Prismatic => Color::CYAN,
+            JointKind::Spherical => Color::YELLOW,
+            JointKind::Rope => Color::PINK,
+        }
+    }
+}
+
+// Editable description of a joint. It lives on a child entity of the second body next to
+// the rapier `ImpulseJoint` it generates, whose `parent` is the first body.
+#[derive(Component, Clone, Copy, PartialEq, Debug)]
+pub struct JointSpec {
+    pub kind: JointKind,
+    // Anchors are in the local space of each body
+    pub anchor1: Vec3,
+    pub anchor2: Vec3,
+    // Revolute rotation axis or prismatic sliding axis, local to both bodies
+    pub axis: Vec3,
+    // Angle range in radians for revolute joints, distance range for prismatic joints
+    pub limits_enabled: bool,
+    pub limits: [f32; 2],
+    // Maximum distance between the anchors of a rope joint
+    pub rope_length: f32,
+}
+
+impl JointSpec {
+    // A joint of the given kind meeting halfway between two bodies
+    pub fn between(kind: JointKind, transform1: &GlobalTransform, transform2: &GlobalTransform) -> Self {
+        let (_, rotation1, position1) = transform1.to_scale_rotation_translation();
+        let (_, rotation2, position2) = transform2.to_scale_rotation_translation();
+        let midpoint = (position1 + position2) * 0.5;
+        Self {
+            kind,
+            anchor1: rotation1.inverse() * (midpoint - position1),
+            anchor2: rotation2.inverse() * (midpoint - position2),
+            axis: Vec3::Y,
+            limits_enabled: false,
+            limits: match kind {
+                JointKind::Prismatic => [-1.0, 1.0],
+                _ => [-std::f32::consts::FRAC_PI_2, std::f32::consts::FRAC_PI_2],
+            },
+            rope_length: position1.distance(position2),
+        }
+    }
+
+    pub fn to_generic_joint(self) -> GenericJoint {
+        let axis = self.axis.try_normalize().unwrap_or(Vec3::Y);
+        match self.kind {
+            JointKind::Fixed => FixedJointBuilder::new()
+                .local_anchor1(self.anchor1)
+                .local_anchor2(self.anchor2)
+                .into(),
+            JointKind::Revolute => {
+                let mut builder = RevoluteJointBuilder::new(axis)
+                    .local_anchor1(self.anchor1)
+                    .local_anchor2(self.anchor2);
+                if self.limits_enabled {
+                    builder = builder.limits(self.limits);
+                }
+                builder.into()
+            }
+            JointKind::Prismatic => {
+                let mut builder = PrismaticJointBuilder::new(axis)
+                    .local_anchor1(self.anchor1)
+                    .local_anchor2(self.anchor2);
+                if self.limits_enabled {
+                    builder = builder.limits(self.limits);
+                }
+                builder.into()
+            }
+            JointKind::Spherical => SphericalJointBuilder::new()
+                .local_anchor1(self.anchor1)
+                .local_anchor2(self.anchor2)
+                .into(),
+            JointKind::Rope => RopeJointBuilder::new()
+                .local_anchor1(self.anchor1)
+                .local_anchor2(self.anchor2)
+                .limits([0.0, self.rope_length.max(0.0)])
+                .into(),
+        }
+    }
+}
+
+pub fn spawn_joint(commands: &mut Commands, body1: Entity, body2: Entity, spec: JointSpec) -> Entity {
+    let joint = commands
+        .spawn((spec, ImpulseJoint::new(body1, spec.to_generic_joint())))
+        .id();
+    commands.entity(body2).add_child(joint);
+    joint
+}
+
+pub fn spawn_joint_in_world(world: &mut World, body1: Entity, body2: Entity, spec: JointSpec) -> Entity {
+    let joint = world
+        .spawn((spec, ImpulseJoint::new(body1, spec.to_generic_joint())))
+        .id();
+    if let Some(mut body) = world.get_entity_mut(body2) {
+        body.add_child(joint);
+    }
+    joint
+}
+
+// Creating (or, with `created: false`, removing) a joint between two bodies
+pub struct JointLifetime {
+    joint: Entity,
+    body1: Entity,
+    body2: Entity,
+    spec: JointSpec,
+    created: bool,
+}
+
+impl JointLifetime {
+    pub fn created(joint: Entity, body1: Entity, body2: Entity, spec: JointSpec) -> Self {
+        Self { joint, body1, body2, spec, created: true }
+    }
+
+    pub fn removed(joint: Entity, body1: Entity, body2: Entity, spec: JointSpec) -> Self {
+        Self { joint, body1, body2, spec, created: false }
+    }
+
+    fn despawn(&mut self, world: &mut World) -> EntityRemap {
+        if let Some(spec) = world.get::<JointSpec>(self.joint) {
+            self.spec = *spec;
+        }
+        if let Some(joint) = world.get_entity_mut(self.joint) {
+            joint.despawn_recursive();
+        }
+        Vec::new()
+    }
+
+    fn respawn(&mut self, world: &mut World) -> EntityRemap {
+        if world.get_entity(self.body1).is_none() || world.get_entity(self.body2).is_none() {
+            return Vec::new();
+        }
+        let old = self.joint;
+        self.joint = spawn_joint_in_world(world, self.body1, self.body2, self.spec);
+        vec![(old, self.joint)]
+    }
+}
+
+impl EditCommand for JointLifetime {
+    fn label(&self) -> String {
+        let action = if self.created { "Create" } else { "Remove" };
+        format!("{} {} Joint", action, self.spec.kind.label())
+    }
+
+    fn undo(&mut self, world: &mut World) -> EntityRemap {
+        if self.created {
+            self.despawn(world)
+        } else {
+            self.respawn(world)
+        }
+    }
+
+    fn redo(&mut self, world: &mut World) -> EntityRemap {
+        if self.created {
+            self.respawn(world)
+        } else {
+            self.despawn(world)
+        }
+    }
+
+    fn remap_entity(&mut self, from: Entity, to: Entity) {
+        for entity in [&mut self.joint, &mut self.body1, &mut self.body2] {
+            if *entity == from {
+                *entity = to;
+            }
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+// Keeps the rapier joint in step with edits made in the Inspector or through undo/redo
+fn sync_joint_data(
+    mut joints: Query<(&JointSpec, &mut ImpulseJoint), Changed<JointSpec>>,
+) {
+    for (spec, mut joint) in joints.iter_mut() {
+        joint.data = spec.to_generic_joint();
+    }
+}
+
+fn draw_joints(
+    mut gizmos: Gizmos,
+    ui_state: Res<UiState>,
+    joints: Query<(&JointSpec, &ImpulseJoint, &Parent)>,
+    transforms: Query<&GlobalTransform>,
+) {
+    for (spec, joint, parent) in joints.iter() {
+        let (Ok(transform1), Ok(transform2)) = (transforms.get(joint.parent), transforms.get(parent.get())) else {
+            continue;
+        };
+        // Rapier ignores scale, so anchors are placed with rotation and translation only
+        let (_, rotation1, position1) = transform1.to_scale_rotation_translation();
+        let (_, rotation2, position2) = transform2.to_scale_rotation_translation();
+        let anchor1 = position1 + rotation1 * spec.anchor1;
+        let anchor2 = position2 + rotation2 * spec.anchor2;
+
+        let selected = ui_state.is_selected(joint.parent) || ui_state.is_selected(parent.get());
+        let color = if selected { Color::RED } else { spec.kind.color() };
+
+        gizmos.line(position1, anchor1, color);
+        gizmos.line(position2, anchor2, color);
+        gizmos.line(anchor1, anchor2, color);
+        gizmos.sphere(anchor1, Quat::IDENTITY, 0.05, color);
+        gizmos.sphere(anchor2, Quat::IDENTITY, 0.05, color);
+
+        if spec.kind.has_axis() {
+            let axis = rotation1 * spec.axis.try_normalize().unwrap_or(Vec3::Y);
+            gizmos.line(anchor1 - axis * 0.5, anchor1 + axis * 0.5, color);
+        }
+    }
+}
+
+pub fn vec3_editor(ui: &mut egui::Ui, label: &str, value: &mut Vec3) {
+    ui.horizontal(|ui| {
+        ui.label(label);
+        ui.add(egui::DragValue::new(&mut value.x).prefix("X: ").speed(0.05));
+        ui.add(egui::DragValue::new(&mut value.y).prefix("Y: ").speed(0.05));
+        ui.add(egui::DragValue::new(&mut value.z).prefix("Z: ").speed(0.05));
+    });
+}
+
+pub type JointQuery<'w, 's> = Query<'w, 's, (
+    Entity,
+    &'static mut JointSpec,
+    &'static ImpulseJoint,
+    &'static Parent,
+)>;
+
+// "Joints" section of the Inspector, listing every joint the entity takes part in
+pub fn joints_inspector(
+    ui: &mut egui::Ui,
+    selected: Entity,
+    joints: &mut JointQuery,
+    commands: &mut Commands,
+    history: &mut History,
+) {
+    ui.collapsing("Joints", |ui| {
+        let mut any = false;
+        for (joint_entity, mut spec, joint, parent) in joints.iter_mut() {
+            let (body1, body2) = (joint.parent, parent.get());
+            if body1 != selected && body2 != selected {
+                continue;
+            }
+            any = true;
+
+            let other = if body1 == selected { body2 } else { body1 };
+            let before = *spec;
+            let mut edited = before;
+            let mut remove = false;
+
+            ui.group(|ui| {
+                ui.horizontal(|ui| {
+                    egui::ComboBox::from_id_source(("joint_kind", joint_entity))
+                        .selected_text(edited.kind.label())
+                        .show_ui(ui, |ui| {
+                            for kind in JointKind::ALL {
+                                ui.selectable_value(&mut edited.kind, kind, kind.label());
+                            }
+                        });
+                    ui.label(format!("to Entity {:?}", other));
+                });
+
+                vec3_editor(ui, "Anchor 1", &mut edited.anchor1);
+                vec3_editor(ui, "Anchor 2", &mut edited.anchor2);
+
+                if edited.kind.has_axis() {
+                    vec3_editor(ui, "Axis", &mut edited.axis);
+                }
+
+                if edited.kind.has_limits() {
+                    ui.checkbox(&mut edited.limits_enabled, "Limits");
+                    if edited.limits_enabled {
+                        let (speed, suffix) = match edited.kind {
+                            JointKind::Revolute => (0.01, " rad"),
+                            _ => (0.05, " m"),
+                        };
+                        ui.horizontal(|ui| {
+                            ui.add(egui::DragValue::new(&mut edited.limits[0]).prefix("Min: ").suffix(suffix).speed(speed));
+                            ui.add(egui::DragValue::new(&mut edited.limits[1]).prefix("Max: ").suffix(suffix).speed(speed));
+                        });
+                        if edited.limits[0] > edited.limits[1] {
+                            edited.limits.swap(0, 1);
+                        }
+                    }
+                }
+
+                if edited.kind == JointKind::Rope {
+                    ui.add(egui::DragValue::new(&mut edited.rope_length)
+                        .prefix("Max length: ")
+                        .suffix(" m")
+                        .speed(0.05)
+                        .clamp_range(0.0..=f32::MAX));
+                }
+
+                if ui.button("Remove Joint").clicked() {
+                    remove = true;
+                }
+            });
+
+            if remove {
+                commands.entity(joint_entity).despawn_recursive();
+                history.push(JointLifetime::removed(joint_entity, body1, body2, before));
+                history.seal();
+            } else if edited != before {
+                *spec = edited;
+                history.push(ComponentChange::new(joint_entity, before, edited));
+            }
+        }
+
+        if !any {
+            ui.label("No joints. Use the Joint tool to connect two objects.");
+        }
+    });
+}
@@ -1,8 +1,41 @@
 use bevy::prelude::*;
 use bevy_rapier3d::prelude::*;
 use bevy::input::mouse::{MouseMotion, MouseWheel};
+mod edit_actions;
+mod history;
+mod joints;
+mod mass_properties;
+mod mesh_import;
+mod outliner;
+mod physics;
+mod physics_components;
+mod physics_materials;
+mod placement;
+mod primitives;
+mod render_materials;
+mod scene;
+mod simulation;
+mod snapping;
+mod transform_gizmo;
 mod ui;
+mod world_settings;
+use edit_actions::EditActionsPlugin;
+use history::HistoryPlugin;
+use joints::JointsPlugin;
+use mass_properties::MassPropertiesPlugin;
+use mesh_import::MeshImportPlugin;
+use outliner::OutlinerPlugin;
+use physics::PhysicsPlugin;
+use physics_materials::PhysicsMaterialsPlugin;
+use placement::PlacementPlugin;
+use primitives::PrimitivesPlugin;
+use render_materials::RenderMaterialsPlugin;
+use scene::{GroundData, ScenePlugin};
+use simulation::SimulationPlugin;
+use snapping::SnappingPlugin;
+use transform_gizmo::TransformGizmoPlugin;
 use ui::UiPlugin;
+use world_settings::WorldSettingsPlugin;
 
 #[derive(Component)]
 struct OrbitCamera {
@@ -67,7 +100,7 @@ fn orbit_camera(
         // Zoom
         if scroll.abs() > 0.0 {
             let zoom_factor = 1.0 - scroll * camera.zoom_sensitivity;
-            camera.radius = (camera.radius * zoom_factor).max(2.0).min(20.0);
+            camera.radius = (camera.radius * zoom_factor).clamp(2.0, 20.0);
             
             let forward = transform.forward();
             transform.translation = camera.focus - forward * camera.radius;
@@ -81,6 +114,22 @@ fn main() {
         .add_plugins(RapierPhysicsPlugin::<NoUserData>::default())
         .add_plugins(RapierDebugRenderPlugin::default())
         .add_plugins(UiPlugin)
+        .add_plugins(ScenePlugin)
+        .add_plugins(HistoryPlugin)
+        .add_plugins(SimulationPlugin)
+        .add_plugins(PhysicsPlugin)
+        .add_plugins(JointsPlugin)
+        .add_plugins(MassPropertiesPlugin)
+        .add_plugins(MeshImportPlugin)
+        .add_plugins(PrimitivesPlugin)
+        .add_plugins(PlacementPlugin)
+        .add_plugins(PhysicsMaterialsPlugin)
+        .add_plugins(RenderMaterialsPlugin)
+        .add_plugins(WorldSettingsPlugin)
+        .add_plugins(TransformGizmoPlugin)
+        .add_plugins(SnappingPlugin)
+        .add_plugins(OutlinerPlugin)
+        .add_plugins(EditActionsPlugin)
         .add_systems(Startup, setup)
         .add_systems(Update, orbit_camera)
         .run();
@@ -98,28 +147,23 @@ fn setup(
             ..default()
         },
         OrbitCamera::default(),
+        Name::new("Camera"),
     ));
 
     // Light
-    commands.spawn(PointLightBundle {
-        point_light: PointLight {
-            intensity: 1500.0,
-            shadows_enabled: true,
-            ..default()
-        },
-        transform: Transform::from_xyz(4.0, 8.0, 4.0),
-        ..default()
-    });
-
-    // Ground plane
     commands.spawn((
-        PbrBundle {
-            mesh: meshes.add(shape::Plane::from_size(10.0).into()),
-            material: materials.add(Color::rgb(0.3, 0.5, 0.3).into()),
-            transform: Transform::from_xyz(0.0, -0.5, 0.0),
+        PointLightBundle {
+            point_light: PointLight {
+                intensity: 1500.0,
+                shadows_enabled: true,
+                ..default()
+            },
+            transform: Transform::from_xyz(4.0, 8.0, 4.0),
             ..default()
         },
-        RigidBody::Fixed,
-        Collider::cuboid(5.0, 0.1, 5.0),
+        Name::new("Light"),
     ));
+
+    // Ground plane
+    GroundData::default().spawn(&mut commands, &mut meshes, &mut materials);
 }
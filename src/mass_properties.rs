@@ -0,0 +1,172 @@
+use bevy::prelude::*;
+use bevy_egui::egui;
+use bevy_rapier3d::prelude::*;
+use bevy_rapier3d::rapier::geometry::ColliderMassProps;
+
+use crate::joints::vec3_editor;
+use crate::physics::PhysicsWorld;
+use crate::simulation::SimulationControl;
+use crate::ui::UiState;
+
+pub struct MassPropertiesPlugin;
+
+impl Plugin for MassPropertiesPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, draw_center_of_mass);
+    }
+}
+
+// The mass properties rapier derives for a body with this single collider
+pub fn collider_mass_properties(collider: &Collider, mass: &ColliderMassProperties) -> MassProperties {
+    let props = match mass {
+        ColliderMassProperties::Density(density) => ColliderMassProps::Density(*density),
+        ColliderMassProperties::Mass(mass) => ColliderMassProps::Mass(*mass),
+        ColliderMassProperties::MassProperties(props) => {
+            ColliderMassProps::MassProperties(Box::new(props.into_rapier(1.0)))
+        }
+    };
+    MassProperties::from_rapier(props.mass_properties(&*collider.raw), 1.0)
+}
+
+// What the body ends up with. Backends only update their mass properties while they step, so
+// when the simulation is frozen the same values are computed from the collider to show edits at once.
+pub fn resulting_mass_properties(
+    live: Option<MassProperties>,
+    collider: Option<&Collider>,
+    mass: Option<&ColliderMassProperties>,
+    simulating: bool,
+) -> Option<MassProperties> {
+    match (live, collider) {
+        (Some(live), _) if simulating => Some(live),
+        (_, Some(collider)) => Some(collider_mass_properties(collider, &mass.copied().unwrap_or_default())),
+        (live, None) => live,
+    }
+}
+
+type MassBodyQuery<'w, 's> = Query<'w, 's, (
+    &'static GlobalTransform,
+    Option<&'static Collider>,
+    Option<&'static ColliderMassProperties>,
+), With<RigidBody>>;
+
+// Center of mass and principal inertia axes of the selected bodies
+fn draw_center_of_mass(
+    mut gizmos: Gizmos,
+    ui_state: Res<UiState>,
+    physics: Res<PhysicsWorld>,
+    control: Res<SimulationControl>,
+    bodies: MassBodyQuery,
+) {
+    for &entity in &ui_state.selection {
+        let Ok((transform, collider, mass)) = bodies.get(entity) else {
+            continue;
+        };
+        let live = physics.mass_properties(entity);
+        let Some(props) = resulting_mass_properties(live, collider, mass, control.active_timestep().is_some()) else {
+            continue;
+        };
+        // Like joint anchors, the center of mass lives in the body frame, which has no scale
+        let (_, rotation, position) = transform.to_scale_rotation_translation();
+        let center = position + rotation * props.local_center_of_mass;
+        let frame = rotation * props.principal_inertia_local_frame;
+
+        gizmos.sphere(center, frame, 0.06, Color::FUCHSIA);
+        for (axis, color) in [(Vec3::X, Color::RED), (Vec3::Y, Color::GREEN), (Vec3::Z, Color::BLUE)] {
+            let axis = frame * axis * 0.25;
+            gizmos.line(center - axis, center + axis, color);
+        }
+    }
+}
+
+#[derive(PartialEq, Clone, Copy)]
+enum MassMode {
+    Density,
+    Mass,
+    Custom,
+}
+
+impl MassMode {
+    const ALL: [MassMode; 3] = [MassMode::Density, MassMode::Mass, MassMode::Custom];
+
+    fn of(mass: &ColliderMassProperties) -> Self {
+        match mass {
+            ColliderMassProperties::Density(_) => MassMode::Density,
+            ColliderMassProperties::Mass(_) => MassMode::Mass,
+            ColliderMassProperties::MassProperties(_) => MassMode::Custom,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            MassMode::Density => "Density",
+            MassMode::Mass => "Mass",
+            MassMode::Custom => "Custom",
+        }
+    }
+
+    // Switching keeps the body's current mass where the new mode can express it
+    fn convert(self, resulting: MassProperties) -> ColliderMassProperties {
+        match self {
+            MassMode::Density => ColliderMassProperties::Density(1.0),
+            MassMode::Mass => ColliderMassProperties::Mass(resulting.mass.max(0.001)),
+            MassMode::Custom => ColliderMassProperties::MassProperties(resulting),
+        }
+    }
+}
+
+// "Mass" section of the Inspector
+pub fn mass_inspector(ui: &mut egui::Ui, mass: &mut ColliderMassProperties, resulting: Option<MassProperties>) {
+    ui.collapsing("Mass", |ui| {
+        let current = MassMode::of(mass);
+        ui.horizontal(|ui| {
+            for mode in MassMode::ALL {
+                if ui.selectable_label(mode == current, mode.label()).clicked() && mode != current {
+                    *mass = mode.convert(resulting.unwrap_or_default());
+                }
+            }
+        });
+
+        match mass {
+            ColliderMassProperties::Density(density) => {
+                ui.add(egui::DragValue::new(density)
+                    .prefix("Density: ")
+                    .suffix(" kg/m³")
+                    .speed(0.01)
+                    .clamp_range(0.001..=f32::MAX));
+                ui.label("Mass and inertia follow the collider's volume");
+            }
+            ColliderMassProperties::Mass(mass) => {
+                ui.add(egui::DragValue::new(mass)
+                    .prefix("Mass: ")
+                    .suffix(" kg")
+                    .speed(0.05)
+                    .clamp_range(0.001..=f32::MAX));
+                ui.label("Inertia follows the collider's shape");
+            }
+            ColliderMassProperties::MassProperties(props) => {
+                ui.add(egui::DragValue::new(&mut props.mass)
+                    .prefix("Mass: ")
+                    .suffix(" kg")
+                    .speed(0.05)
+                    .clamp_range(0.001..=f32::MAX));
+                vec3_editor(ui, "Center of Mass", &mut props.local_center_of_mass);
+                vec3_editor(ui, "Principal Inertia", &mut props.principal_inertia);
+                props.principal_inertia = props.principal_inertia.max(Vec3::ZERO);
+            }
+        }
+
+        ui.separator();
+        match resulting {
+            Some(props) => {
+                let center = props.local_center_of_mass;
+                let inertia = props.principal_inertia;
+                ui.label(format!("Resulting mass: {:.3} kg", props.mass));
+                ui.label(format!("Center of mass: ({:.2}, {:.2}, {:.2})", center.x, center.y, center.z));
+                ui.label(format!("Principal inertia: ({:.3}, {:.3}, {:.3})", inertia.x, inertia.y, inertia.z));
+            }
+            None => {
+                ui.label("No collider, the body has no mass");
+            }
+        }
+    });
+}
@@ -0,0 +1,542 @@
+use std::fmt;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+use bevy::render::mesh::{Indices, PrimitiveTopology};
+use bevy_egui::{egui, EguiContexts};
+use bevy_rapier3d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::history::{ComponentChange, History, ObjectLifetime};
+use crate::placement::{center_ray, resting_transform};
+use crate::ui::{spawn_body, ShapeType};
+
+pub struct MeshImportPlugin;
+
+impl Plugin for MeshImportPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<OpenMeshImport>()
+           .init_resource::<MeshImportState>()
+           .add_systems(Update, (
+               open_mesh_import,
+               mesh_import_dialog.after(open_mesh_import),
+               sync_mesh_colliders,
+           ));
+    }
+}
+
+// Sent by the "Import Mesh..." entry of the Add Object menu
+#[derive(Event)]
+pub struct OpenMeshImport;
+
+#[derive(Serialize, Deserialize, Default, PartialEq, Clone, Copy, Debug)]
+pub enum MeshColliderKind {
+    #[default]
+    ConvexHull,
+    // Only suitable for fixed bodies, rapier cannot compute good dynamics for hollow triangle soups
+    TriMesh,
+    // Approximate convex decomposition with rapier's VHACD
+    ConvexDecomposition,
+}
+
+impl MeshColliderKind {
+    pub const ALL: [MeshColliderKind; 3] = [
+        MeshColliderKind::ConvexHull,
+        MeshColliderKind::TriMesh,
+        MeshColliderKind::ConvexDecomposition,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            MeshColliderKind::ConvexHull => "Convex Hull",
+            MeshColliderKind::TriMesh => "Triangle Mesh (fixed only)",
+            MeshColliderKind::ConvexDecomposition => "Convex Decomposition (VHACD)",
+        }
+    }
+
+    fn computed_shape(self) -> ComputedColliderShape {
+        match self {
+            MeshColliderKind::ConvexHull => ComputedColliderShape::ConvexHull,
+            MeshColliderKind::TriMesh => ComputedColliderShape::TriMesh,
+            MeshColliderKind::ConvexDecomposition => {
+                ComputedColliderShape::ConvexDecomposition(VHACDParameters::default())
+            }
+        }
+    }
+}
+
+// Where an imported object's geometry came from and how its collider is generated
+#[derive(Component, Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct ImportedMesh {
+    pub path: PathBuf,
+    pub collider: MeshColliderKind,
+}
+
+#[derive(Debug)]
+pub enum MeshImportError {
+    Io(std::io::Error),
+    Obj(tobj::LoadError),
+    Gltf(gltf::Error),
+    UnsupportedFormat(String),
+    NoTriangles,
+    ColliderFailed,
+}
+
+impl fmt::Display for MeshImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MeshImportError::Io(err) => write!(f, "could not read mesh file: {}", err),
+            MeshImportError::Obj(err) => write!(f, "invalid OBJ file: {}", err),
+            MeshImportError::Gltf(err) => write!(f, "invalid glTF file: {}", err),
+            MeshImportError::UnsupportedFormat(extension) => write!(
+                f,
+                "unsupported mesh format '{}' (expected obj, stl, gltf or glb)",
+                extension
+            ),
+            MeshImportError::NoTriangles => write!(f, "file contains no triangles"),
+            MeshImportError::ColliderFailed => write!(f, "could not build a collider for this mesh"),
+        }
+    }
+}
+
+impl std::error::Error for MeshImportError {}
+
+impl From<std::io::Error> for MeshImportError {
+    fn from(err: std::io::Error) -> Self {
+        MeshImportError::Io(err)
+    }
+}
+
+impl From<tobj::LoadError> for MeshImportError {
+    fn from(err: tobj::LoadError) -> Self {
+        MeshImportError::Obj(err)
+    }
+}
+
+impl From<gltf::Error> for MeshImportError {
+    fn from(err: gltf::Error) -> Self {
+        MeshImportError::Gltf(err)
+    }
+}
+
+// Triangle data gathered from any of the supported formats
+#[derive(Default)]
+struct MeshData {
+    positions: Vec<[f32; 3]>,
+    normals: Vec<[f32; 3]>,
+    uvs: Vec<[f32; 2]>,
+    indices: Vec<u32>,
+}
+
+impl MeshData {
+    fn into_mesh(mut self) -> Result<Mesh, MeshImportError> {
+        if self.indices.len() < 3 {
+            return Err(MeshImportError::NoTriangles);
+        }
+        if self.normals.len() != self.positions.len() {
+            self.normals = smooth_normals(&self.positions, &self.indices);
+        }
+        if self.uvs.len() != self.positions.len() {
+            self.uvs = vec![[0.0, 0.0]; self.positions.len()];
+        }
+
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, self.positions);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, self.normals);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, self.uvs);
+        mesh.set_indices(Some(Indices::U32(self.indices)));
+        Ok(mesh)
+    }
+}
+
+// Area-weighted vertex normals for formats that don't store any
+fn smooth_normals(positions: &[[f32; 3]], indices: &[u32]) -> Vec<[f32; 3]> {
+    let mut normals = vec![Vec3::ZERO; positions.len()];
+    for triangle in indices.chunks_exact(3) {
+        let [a, b, c] = [triangle[0], triangle[1], triangle[2]].map(|index| index as usize);
+        let face = (Vec3::from(positions[b]) - Vec3::from(positions[a]))
+            .cross(Vec3::from(positions[c]) - Vec3::from(positions[a]));
+        for index in [a, b, c] {
+            normals[index] += face;
+        }
+    }
+    normals
+        .into_iter()
+        .map(|normal| normal.try_normalize().unwrap_or(Vec3::Y).to_array())
+        .collect()
+}
+
+fn load_obj(path: &Path) -> Result<MeshData, MeshImportError> {
+    let (models, _materials) = tobj::load_obj(path, &tobj::GPU_LOAD_OPTIONS)?;
+    let mut data = MeshData::default();
+    let mut has_normals = true;
+    let mut has_uvs = true;
+
+    for model in models {
+        let mesh = model.mesh;
+        let offset = data.positions.len() as u32;
+        let count = mesh.positions.len() / 3;
+        data.positions.extend(mesh.positions.chunks_exact(3).map(|p| [p[0], p[1], p[2]]));
+        has_normals &= mesh.normals.len() == count * 3;
+        has_uvs &= mesh.texcoords.len() == count * 2;
+        data.normals.extend(mesh.normals.chunks_exact(3).map(|n| [n[0], n[1], n[2]]));
+        // OBJ texture coordinates have their origin at the bottom left
+        data.uvs.extend(mesh.texcoords.chunks_exact(2).map(|t| [t[0], 1.0 - t[1]]));
+        data.indices.extend(mesh.indices.iter().map(|index| index + offset));
+    }
+
+    if !has_normals {
+        data.normals.clear();
+    }
+    if !has_uvs {
+        data.uvs.clear();
+    }
+    Ok(data)
+}
+
+fn load_stl(path: &Path) -> Result<MeshData, MeshImportError> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let stl = stl_io::read_stl(&mut reader)?;
+    let mut data = MeshData::default();
+
+    // Vertices are duplicated per face so every triangle keeps its own flat normal
+    for face in &stl.faces {
+        let corners = face.vertices.map(|index| <[f32; 3]>::from(stl.vertices[index]));
+        let computed = (Vec3::from(corners[1]) - Vec3::from(corners[0]))
+            .cross(Vec3::from(corners[2]) - Vec3::from(corners[0]));
+        let normal = computed
+            .try_normalize()
+            .or_else(|| Vec3::from(<[f32; 3]>::from(face.normal)).try_normalize())
+            .unwrap_or(Vec3::Y)
+            .to_array();
+        for corner in corners {
+            data.indices.push(data.positions.len() as u32);
+            data.positions.push(corner);
+            data.normals.push(normal);
+        }
+    }
+    Ok(data)
+}
+
+fn load_gltf(path: &Path) -> Result<MeshData, MeshImportError> {
+    let (document, buffers, _images) = gltf::import(path)?;
+    let mut data = MeshData::default();
+
+    fn visit(node: gltf::Node, parent: Mat4, buffers: &[gltf::buffer::Data], data: &mut MeshData) {
+        let transform = parent * Mat4::from_cols_array_2d(&node.transform().matrix());
+        let normal_matrix = Mat3::from_mat4(transform).inverse().transpose();
+
+        if let Some(mesh) = node.mesh() {
+            for primitive in mesh.primitives() {
+                if primitive.mode() != gltf::mesh::Mode::Triangles {
+                    continue;
+                }
+                let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+                let Some(positions) = reader.read_positions() else {
+                    continue;
+                };
+
+                let offset = data.positions.len() as u32;
+                let positions: Vec<[f32; 3]> = positions
+                    .map(|position| transform.transform_point3(Vec3::from(position)).to_array())
+                    .collect();
+                let count = positions.len();
+                data.positions.extend(positions);
+
+                let normals: Vec<[f32; 3]> = reader
+                    .read_normals()
+                    .map(|normals| {
+                        normals
+                            .map(|normal| (normal_matrix * Vec3::from(normal)).normalize_or_zero().to_array())
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                // A primitive without normals leaves the counts mismatched, so the whole
+                // mesh falls back to computed normals in `into_mesh`
+                if normals.len() == count {
+                    data.normals.extend(normals);
+                }
+
+                let uvs: Vec<[f32; 2]> = reader
+                    .read_tex_coords(0)
+                    .map(|uvs| uvs.into_f32().collect())
+                    .unwrap_or_default();
+                if uvs.len() == count {
+                    data.uvs.extend(uvs);
+                } else {
+                    data.uvs.extend(std::iter::repeat_n([0.0, 0.0], count));
+                }
+
+                match reader.read_indices() {
+                    Some(indices) => data.indices.extend(indices.into_u32().map(|index| index + offset)),
+                    None => data.indices.extend(offset..offset + count as u32),
+                }
+            }
+        }
+
+        for child in node.children() {
+            visit(child, transform, buffers, data);
+        }
+    }
+
+    match document.default_scene().or_else(|| document.scenes().next()) {
+        Some(scene) => {
+            for node in scene.nodes() {
+                visit(node, Mat4::IDENTITY, &buffers, &mut data);
+            }
+        }
+        None => {
+            for node in document.nodes() {
+                visit(node, Mat4::IDENTITY, &buffers, &mut data);
+            }
+        }
+    }
+    Ok(data)
+}
+
+pub fn load_mesh(path: &Path) -> Result<Mesh, MeshImportError> {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    let data = match extension.as_str() {
+        "obj" => load_obj(path)?,
+        "stl" => load_stl(path)?,
+        "gltf" | "glb" => load_gltf(path)?,
+        _ => return Err(MeshImportError::UnsupportedFormat(extension)),
+    };
+    data.into_mesh()
+}
+
+pub fn mesh_collider(mesh: &Mesh, kind: MeshColliderKind) -> Result<Collider, MeshImportError> {
+    Collider::from_bevy_mesh(mesh, &kind.computed_shape()).ok_or(MeshImportError::ColliderFailed)
+}
+
+// Loads a mesh file and spawns it as a selectable body
+pub fn spawn_imported(
+    commands: &mut Commands,
+    meshes: &mut ResMut<Assets<Mesh>>,
+    materials: &mut ResMut<Assets<StandardMaterial>>,
+    source: &ImportedMesh,
+    position: Vec3,
+) -> Result<Entity, MeshImportError> {
+    let mesh = load_mesh(&source.path)?;
+    let collider = mesh_collider(&mesh, source.collider)?;
+    Ok(spawn_loaded(commands, meshes, materials, source, mesh, collider, position))
+}
+
+// `spawn_imported` once the mesh and its collider are built
+fn spawn_loaded(
+    commands: &mut Commands,
+    meshes: &mut ResMut<Assets<Mesh>>,
+    materials: &mut ResMut<Assets<StandardMaterial>>,
+    source: &ImportedMesh,
+    mesh: Mesh,
+    collider: Collider,
+    position: Vec3,
+) -> Entity {
+    let entity = spawn_body(commands, meshes.add(mesh), materials, collider, ShapeType::Imported, position);
+    commands.entity(entity).insert(source.clone());
+    if let Some(stem) = source.path.file_stem() {
+        commands.entity(entity).insert(Name::new(stem.to_string_lossy().into_owned()));
+    }
+    if source.collider == MeshColliderKind::TriMesh {
+        commands.entity(entity).insert(RigidBody::Fixed);
+    }
+    entity
+}
+
+#[derive(Resource, Default)]
+struct MeshImportState {
+    open: bool,
+    path: String,
+    collider: MeshColliderKind,
+    error: Option<String>,
+}
+
+fn open_mesh_import(
+    mut events: EventReader<OpenMeshImport>,
+    mut state: ResMut<MeshImportState>,
+) {
+    for _ in events.read() {
+        state.open = true;
+        state.error = None;
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn mesh_import_dialog(
+    mut contexts: EguiContexts,
+    mut state: ResMut<MeshImportState>,
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut history: ResMut<History>,
+    rapier_context: Res<RapierContext>,
+    windows: Query<&Window>,
+    cameras: Query<(&Camera, &GlobalTransform)>,
+) {
+    if !state.open {
+        return;
+    }
+
+    let state = &mut *state;
+    let mut import = false;
+    let mut cancel = false;
+
+    egui::Window::new("Import Mesh")
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .show(contexts.ctx_mut(), |ui| {
+            ui.label("File path (.obj, .stl, .gltf, .glb):");
+            ui.text_edit_singleline(&mut state.path);
+
+            ui.label("Collider:");
+            for kind in MeshColliderKind::ALL {
+                ui.radio_value(&mut state.collider, kind, kind.label());
+            }
+
+            if let Some(error) = &state.error {
+                ui.colored_label(egui::Color32::RED, error);
+            }
+
+            ui.horizontal(|ui| {
+                if ui.add_enabled(!state.path.trim().is_empty(), egui::Button::new("Import")).clicked() {
+                    import = true;
+                }
+                if ui.button("Cancel").clicked() {
+                    cancel = true;
+                }
+            });
+        });
+
+    if import {
+        let source = ImportedMesh {
+            path: PathBuf::from(state.path.trim()),
+            collider: state.collider,
+        };
+        let loaded = load_mesh(&source.path)
+            .and_then(|mesh| Ok((mesh_collider(&mesh, source.collider)?, mesh)));
+        match loaded {
+            Ok((collider, mesh)) => {
+                // Rests on whatever is under the middle of the viewport, like new primitives
+                let transform = center_ray(&windows, &cameras)
+                    .map(|ray| resting_transform(&rapier_context, &collider, ray))
+                    .unwrap_or(Transform::from_xyz(0.0, 2.0, 0.0));
+                let entity = spawn_loaded(&mut commands, &mut meshes, &mut materials, &source, mesh, collider, transform.translation);
+                commands.entity(entity).insert(transform);
+                history.push(ObjectLifetime::spawned("Import Mesh", vec![entity]));
+                history.seal();
+                state.open = false;
+                state.error = None;
+            }
+            Err(err) => {
+                error!("Failed to import {}: {}", source.path.display(), err);
+                state.error = Some(err.to_string());
+            }
+        }
+    } else if cancel {
+        state.open = false;
+        state.error = None;
+    }
+}
+
+type ChangedMeshQuery<'w, 's> = Query<'w, 's, (Entity, Ref<'static, ImportedMesh>, &'static Handle<Mesh>), Changed<ImportedMesh>>;
+
+// Rebuilds the collider when the collider kind is changed in the Inspector or by undo/redo.
+// Newly spawned objects already got theirs from `spawn_imported`.
+fn sync_mesh_colliders(
+    mut commands: Commands,
+    meshes: Res<Assets<Mesh>>,
+    imported: ChangedMeshQuery,
+) {
+    for (entity, source, mesh) in imported.iter() {
+        if source.is_added() {
+            continue;
+        }
+        let Some(mesh) = meshes.get(mesh) else {
+            continue;
+        };
+        match mesh_collider(mesh, source.collider) {
+            Ok(collider) => {
+                commands.entity(entity).insert(collider);
+            }
+            Err(err) => warn!("Could not rebuild collider for {}: {}", source.path.display(), err),
+        }
+    }
+}
+
+// "Mesh Collider" section of the Inspector for imported objects
+pub fn mesh_collider_inspector(
+    ui: &mut egui::Ui,
+    entity: Entity,
+    imported: &mut Query<&mut ImportedMesh>,
+    rigid_body: RigidBody,
+    history: &mut History,
+) {
+    let Ok(mut source) = imported.get_mut(entity) else {
+        return;
+    };
+
+    ui.collapsing("Mesh Collider", |ui| {
+        ui.label(format!("Source: {}", source.path.display()));
+        let before = source.clone();
+        let mut kind = before.collider;
+        for option in MeshColliderKind::ALL {
+            ui.radio_value(&mut kind, option, option.label());
+        }
+        if kind == MeshColliderKind::TriMesh && rigid_body != RigidBody::Fixed {
+            ui.colored_label(
+                egui::Color32::YELLOW,
+                "Triangle mesh colliders should only be used on Fixed bodies",
+            );
+        }
+        if kind != before.collider {
+            source.collider = kind;
+            history.push(ComponentChange::new(entity, before, source.clone()));
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy_rapier3d::parry::shape::ShapeType as ParryShapeType;
+
+    // A unit tetrahedron with its corner at the origin
+    fn tetrahedron() -> Mesh {
+        MeshData {
+            positions: vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+            indices: vec![0, 2, 1, 0, 1, 3, 0, 3, 2, 1, 2, 3],
+            ..default()
+        }
+        .into_mesh()
+        .unwrap()
+    }
+
+    #[test]
+    fn every_collider_kind_wraps_the_mesh() {
+        let mesh = tetrahedron();
+        for (kind, shape_type) in [
+            (MeshColliderKind::ConvexHull, ParryShapeType::ConvexPolyhedron),
+            (MeshColliderKind::TriMesh, ParryShapeType::TriMesh),
+            (MeshColliderKind::ConvexDecomposition, ParryShapeType::Compound),
+        ] {
+            let collider = mesh_collider(&mesh, kind).unwrap();
+            assert_eq!(collider.raw.shape_type(), shape_type, "{}", kind.label());
+            let aabb = collider.raw.compute_local_aabb();
+            assert!(Vec3::from(aabb.mins).abs_diff_eq(Vec3::ZERO, 0.05), "{}: {:?}", kind.label(), aabb);
+            assert!(Vec3::from(aabb.maxs).abs_diff_eq(Vec3::ONE, 0.05), "{}: {:?}", kind.label(), aabb);
+        }
+    }
+
+    #[test]
+    fn meshes_without_triangles_are_rejected() {
+        let data = MeshData { positions: vec![[0.0; 3]; 2], indices: vec![0, 1], ..default() };
+        assert!(matches!(data.into_mesh(), Err(MeshImportError::NoTriangles)));
+    }
+}
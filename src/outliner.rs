@@ -0,0 +1,217 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+use crate::history::{ComponentChange, History};
+use crate::scene::Ground;
+use crate::ui::{Selectable, SelectMode, UiState};
+
+pub struct OutlinerPlugin;
+
+impl Plugin for OutlinerPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<OutlinerState>()
+           .add_systems(Update, outliner_system);
+    }
+}
+
+// Locked objects can't be picked in the viewport, moved by the gizmo or edited in the Inspector
+#[derive(Component, Default, PartialEq, Clone, Copy, Debug)]
+pub struct Locked(pub bool);
+
+pub fn is_locked(locked: Option<&Locked>) -> bool {
+    locked.is_some_and(|locked| locked.0)
+}
+
+#[derive(Resource, Default)]
+struct OutlinerState {
+    filter: String,
+    // Entity being renamed and the name typed so far
+    renaming: Option<(Entity, String)>,
+}
+
+type OutlinerObjectQuery<'w, 's> = Query<'w, 's, (
+    Entity,
+    Option<&'static Name>,
+    &'static Visibility,
+    Option<&'static Locked>,
+), With<Selectable>>;
+
+// Ground, lights and the camera spawned by `setup`
+type OutlinerEnvironmentQuery<'w, 's> = Query<'w, 's, (
+    Entity,
+    Option<&'static Name>,
+    Option<&'static Visibility>,
+), (
+    Without<Selectable>,
+    Or<(With<Ground>, With<PointLight>, With<DirectionalLight>, With<Camera3d>)>,
+)>;
+
+fn display_name(entity: Entity, name: Option<&Name>) -> String {
+    match name {
+        Some(name) => name.to_string(),
+        None => format!("Entity {:?}", entity),
+    }
+}
+
+fn toggled_visibility(visibility: Visibility) -> Visibility {
+    if visibility == Visibility::Hidden {
+        Visibility::Inherited
+    } else {
+        Visibility::Hidden
+    }
+}
+
+fn outliner_system(
+    mut contexts: EguiContexts,
+    mut ui_state: ResMut<UiState>,
+    mut history: ResMut<History>,
+    mut state: ResMut<OutlinerState>,
+    mut commands: Commands,
+    objects: OutlinerObjectQuery,
+    environment: OutlinerEnvironmentQuery,
+) {
+    let ctx = contexts.ctx_mut();
+    let default_x = ctx.screen_rect().right() - 260.0;
+
+    // Edits are collected while drawing and applied afterwards, all through history
+    let mut renamed = None;
+    let mut hidden = None;
+    let mut locked = None;
+
+    egui::Window::new("Outliner")
+        .default_pos([default_x, 80.0])
+        .default_width(240.0)
+        .default_height(400.0)
+        .resizable(true)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("🔍");
+                ui.text_edit_singleline(&mut state.filter);
+            });
+            ui.separator();
+
+            let filter = state.filter.to_lowercase();
+            let matches = |name: &str| filter.is_empty() || name.to_lowercase().contains(&filter);
+
+            let mut rows: Vec<_> = objects.iter().collect();
+            rows.sort_by_key(|(entity, ..)| *entity);
+            let mut environment_rows: Vec<_> = environment.iter().collect();
+            environment_rows.sort_by_key(|(entity, ..)| *entity);
+
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                ui.label("Objects");
+                for (entity, name, visibility, lock) in rows {
+                    let label = display_name(entity, name);
+                    if !matches(&label) {
+                        continue;
+                    }
+                    ui.horizontal(|ui| {
+                        let visible = *visibility != Visibility::Hidden;
+                        if ui.selectable_label(visible, "👁").on_hover_text("Show / hide").clicked() {
+                            hidden = Some((entity, *visibility));
+                        }
+                        let is_locked = is_locked(lock);
+                        if ui.selectable_label(is_locked, "🔒").on_hover_text("Lock / unlock").clicked() {
+                            locked = Some((entity, is_locked));
+                        }
+                        let selected = ui_state.is_selected(entity);
+                        let response = name_label(ui, &mut state.renaming, &mut renamed, entity, name, label, selected);
+                        // Locked objects stay out of the selection, like in the viewport
+                        if response.is_some_and(|response| response.clicked()) && !is_locked {
+                            let modifiers = ui.input(|input| input.modifiers);
+                            let mode = if modifiers.shift {
+                                SelectMode::Add
+                            } else if modifiers.command {
+                                SelectMode::Toggle
+                            } else {
+                                SelectMode::Replace
+                            };
+                            ui_state.select(entity, mode);
+                        }
+                    });
+                }
+
+                ui.separator();
+                ui.label("Environment");
+                for (entity, name, visibility) in environment_rows {
+                    let label = display_name(entity, name);
+                    if !matches(&label) {
+                        continue;
+                    }
+                    ui.horizontal(|ui| {
+                        match visibility {
+                            Some(visibility) => {
+                                let visible = *visibility != Visibility::Hidden;
+                                if ui.selectable_label(visible, "👁").on_hover_text("Show / hide").clicked() {
+                                    hidden = Some((entity, *visibility));
+                                }
+                            }
+                            None => {
+                                ui.add_enabled(false, egui::SelectableLabel::new(true, "👁"));
+                            }
+                        }
+                        name_label(ui, &mut state.renaming, &mut renamed, entity, name, label, false);
+                    });
+                }
+            });
+        });
+
+    if let Some((entity, before, after)) = renamed {
+        commands.entity(entity).insert(after.clone());
+        history.push(ComponentChange::new(entity, before, after));
+        history.seal();
+    }
+    if let Some((entity, before)) = hidden {
+        let after = toggled_visibility(before);
+        commands.entity(entity).insert(after);
+        history.push(ComponentChange::new(entity, before, after));
+        history.seal();
+    }
+    if let Some((entity, was_locked)) = locked {
+        commands.entity(entity).insert(Locked(!was_locked));
+        history.push(ComponentChange::new(entity, Locked(was_locked), Locked(!was_locked)));
+        history.seal();
+        // Locked objects leave the selection so they can't be edited by accident
+        if !was_locked {
+            ui_state.selection.retain(|selected| *selected != entity);
+        }
+    }
+}
+
+// The name of an outliner row: a selectable label, or a text field while renaming (double click).
+// Returns the label's response when it is shown.
+fn name_label(
+    ui: &mut egui::Ui,
+    renaming: &mut Option<(Entity, String)>,
+    renamed: &mut Option<(Entity, Name, Name)>,
+    entity: Entity,
+    name: Option<&Name>,
+    label: String,
+    selected: bool,
+) -> Option<egui::Response> {
+    match renaming {
+        Some((renaming_entity, text)) if *renaming_entity == entity => {
+            let response = ui.text_edit_singleline(text);
+            if !response.has_focus() && !response.lost_focus() {
+                response.request_focus();
+            }
+            if response.lost_focus() {
+                let text = text.trim().to_string();
+                if !text.is_empty() && Some(text.as_str()) != name.map(|name| name.as_str()) {
+                    let before = name.cloned().unwrap_or_else(|| Name::new(label));
+                    *renamed = Some((entity, before, Name::new(text)));
+                }
+                *renaming = None;
+            }
+            None
+        }
+        _ => {
+            let response = ui.selectable_label(selected, &label)
+                .on_hover_text("Double click to rename");
+            if response.double_clicked() {
+                *renaming = Some((entity, label));
+            }
+            Some(response)
+        }
+    }
+}
@@ -1,44 +1,539 @@
 use bevy::prelude::*;
+use bevy::utils::HashMap;
 use bevy_rapier3d::prelude::*;
 
+use crate::simulation::{EditorMode, SimulationControl};
+use crate::world_settings::WorldSettings;
+
+pub mod broadphase;
+mod integrators;
+mod native;
+mod narrowphase;
+#[cfg(test)]
+mod random;
+mod rapier_backend;
+
+use broadphase::{Aabb, DebugBox};
+pub use broadphase::BroadPhaseKind;
+pub use integrators::Integrator;
+use native::NativeBackend;
+use rapier_backend::RapierBackend;
+
+// Steps the editor's bodies with the backend picked on the command line (`--physics native`).
+// bevy_rapier stays around for scene queries and debug rendering but never steps itself.
 pub struct PhysicsPlugin;
 
 impl Plugin for PhysicsPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, (
-            apply_gravity,
-            handle_collisions,
-        ));
+        let kind = PhysicsBackendKind::from_args();
+        info!("Physics backend: {}", kind.label());
+        app.insert_resource(PhysicsWorld::new(kind))
+           .init_resource::<AabbOverlay>()
+           .init_resource::<SleepingOverlay>()
+           .add_systems(Startup, disable_rapier_stepping)
+           .add_systems(PostUpdate, (
+               apply_broad_phase,
+               sync_bodies,
+               sync_joints,
+               step_physics,
+               write_back,
+           ).chain().before(PhysicsSet::SyncBackend))
+           .add_systems(Update, (draw_aabb_overlay, draw_sleeping_overlay));
+    }
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum PhysicsBackendKind {
+    Rapier,
+    Native,
+}
+
+impl PhysicsBackendKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            PhysicsBackendKind::Rapier => "Rapier",
+            PhysicsBackendKind::Native => "Native",
+        }
+    }
+
+    // `--physics native` or `--physics=native`, rapier otherwise
+    fn from_args() -> Self {
+        let args: Vec<String> = std::env::args().collect();
+        let value = args.iter().enumerate().find_map(|(i, arg)| match arg.strip_prefix("--physics") {
+            Some("") => args.get(i + 1).map(String::as_str),
+            Some(value) => value.strip_prefix('='),
+            None => None,
+        });
+        match value {
+            Some("native") => PhysicsBackendKind::Native,
+            Some("rapier") | None => PhysicsBackendKind::Rapier,
+            Some(other) => {
+                warn!("Unknown physics backend '{other}', using rapier");
+                PhysicsBackendKind::Rapier
+            }
+        }
+    }
+}
+
+// Pose and velocity of a body. Velocities are those of the center of mass, like `Velocity`.
+#[derive(PartialEq, Clone, Copy, Debug, Default)]
+pub struct BodyState {
+    pub position: Vec3,
+    pub rotation: Quat,
+    pub linvel: Vec3,
+    pub angvel: Vec3,
+}
+
+// Everything a backend needs to build one body and its collider
+#[derive(Clone)]
+pub struct BodyDesc {
+    pub body_type: RigidBody,
+    pub state: BodyState,
+    pub collider: Option<ColliderDesc>,
+    pub damping: Damping,
+    pub force: ExternalForce,
+    pub ccd: bool,
+}
+
+#[derive(Clone)]
+pub struct ColliderDesc {
+    // Already scaled by the entity's transform
+    pub shape: Collider,
+    pub mass: ColliderMassProperties,
+    pub friction: Friction,
+    pub restitution: Restitution,
+    pub sensor: bool,
+}
+
+// A joint from `body1` to `body2`, with anchors in their local frames
+#[derive(Clone, Copy)]
+pub struct JointDesc {
+    pub body1: Entity,
+    pub body2: Entity,
+    pub joint: GenericJoint,
+}
+
+// One step of the simulation
+#[derive(Clone, Copy, Debug)]
+pub struct StepParams {
+    pub dt: f32,
+    pub gravity: Vec3,
+    pub velocity_iterations: usize,
+    pub friction_iterations: usize,
+    pub stabilization_iterations: usize,
+    pub max_ccd_substeps: usize,
+    // Rapier always integrates semi-implicitly
+    pub integrator: Integrator,
+}
+
+// A physics engine the editor can drive. Bodies and joints are keyed by the entity they belong
+// to, the editor's components stay the source of truth and are pushed in whenever they change.
+pub trait PhysicsBackend: Send + Sync {
+    // Creates the body, or updates it in place when it already exists
+    fn set_body(&mut self, entity: Entity, desc: &BodyDesc);
+    // Teleports the body or changes its velocity, as edits and Stop do
+    fn set_state(&mut self, entity: Entity, state: &BodyState);
+    fn remove_body(&mut self, entity: Entity);
+    fn set_joint(&mut self, entity: Entity, desc: &JointDesc);
+    fn remove_joint(&mut self, entity: Entity);
+    // Brings scene queries up to date after bodies were added, moved or removed
+    fn update_queries(&mut self);
+    fn step(&mut self, params: &StepParams);
+    // Thresholds and state from rapier's `Sleeping`. A sleeping body stays put until something
+    // touches it, it's edited or it's woken here.
+    fn set_sleeping(&mut self, entity: Entity, sleeping: &Sleeping);
+    fn is_sleeping(&self, entity: Entity) -> bool;
+    // Mass, center of mass and inertia the backend derived for the body, updated as it steps
+    fn mass_properties(&self, entity: Entity) -> Option<MassProperties>;
+    fn body_state(&self, entity: Entity) -> Option<BodyState>;
+    // Closest collider hit by the ray, as an entity and the distance along the ray
+    fn cast_ray(&self, ray: Ray, max_distance: f32, filter: &dyn Fn(Entity) -> bool) -> Option<(Entity, f32)>;
+    // Backends with a broad phase of their own ignore the choice
+    fn set_broad_phase(&mut self, _kind: BroadPhaseKind) {}
+    // The boxes the broad phase works with, for the overlay
+    fn debug_boxes(&self, visit: &mut dyn FnMut(&Aabb, DebugBox));
+}
+
+#[derive(Resource)]
+pub struct PhysicsWorld {
+    kind: PhysicsBackendKind,
+    backend: Box<dyn PhysicsBackend>,
+    // Real time not yet simulated by an interpolated timestep
+    accumulator: f32,
+    // Body states before the last step, which an interpolated timestep blends from
+    previous: HashMap<Entity, BodyState>,
+    // What `write_back` last put on each entity, so it isn't pushed back as an edit
+    written: HashMap<Entity, BodyState>,
+}
+
+impl PhysicsWorld {
+    pub fn new(kind: PhysicsBackendKind) -> Self {
+        let backend: Box<dyn PhysicsBackend> = match kind {
+            PhysicsBackendKind::Rapier => Box::<RapierBackend>::default(),
+            PhysicsBackendKind::Native => Box::<NativeBackend>::default(),
+        };
+        Self { kind, backend, accumulator: 0.0, previous: HashMap::default(), written: HashMap::default() }
+    }
+
+    pub fn kind(&self) -> PhysicsBackendKind {
+        self.kind
+    }
+
+    pub fn cast_ray(&self, ray: Ray, max_distance: f32, filter: &dyn Fn(Entity) -> bool) -> Option<(Entity, f32)> {
+        self.backend.cast_ray(ray, max_distance, filter)
+    }
+
+    pub fn is_sleeping(&self, entity: Entity) -> bool {
+        self.backend.is_sleeping(entity)
+    }
+
+    pub fn mass_properties(&self, entity: Entity) -> Option<MassProperties> {
+        self.backend.mass_properties(entity)
+    }
+}
+
+// Which broad phase boxes the viewport draws, toggled in the View menu
+#[derive(Resource, Default)]
+pub struct AabbOverlay {
+    pub show: bool,
+    // Also the inner nodes of a tree, coloured by depth
+    pub tree_nodes: bool,
+}
+
+fn draw_aabb_overlay(
+    mut gizmos: Gizmos,
+    overlay: Res<AabbOverlay>,
+    physics: Res<PhysicsWorld>,
+) {
+    if !overlay.show {
+        return;
+    }
+    physics.backend.debug_boxes(&mut |aabb, kind| {
+        let color = match kind {
+            DebugBox::Proxy { paired: true } => Color::ORANGE,
+            DebugBox::Proxy { paired: false } => Color::GREEN,
+            DebugBox::Node { depth } if overlay.tree_nodes => Color::hsla(200.0 + 25.0 * depth as f32, 0.8, 0.6, 0.5),
+            DebugBox::Node { .. } => return,
+        };
+        gizmos.cuboid(Transform::from_translation(aabb.center()).with_scale(aabb.size()), color);
+    });
+}
+
+// Whether the viewport tints sleeping bodies, toggled in the View menu
+#[derive(Resource, Default)]
+pub struct SleepingOverlay {
+    pub show: bool,
+}
+
+fn draw_sleeping_overlay(
+    mut gizmos: Gizmos,
+    overlay: Res<SleepingOverlay>,
+    physics: Res<PhysicsWorld>,
+    bodies: Query<(Entity, &GlobalTransform, &bevy::render::primitives::Aabb), With<RigidBody>>,
+) {
+    if !overlay.show {
+        return;
+    }
+    for (entity, transform, aabb) in bodies.iter() {
+        if physics.is_sleeping(entity) {
+            let local = Transform::from_translation(aabb.center.into()).with_scale(Vec3::from(aabb.half_extents) * 2.0);
+            gizmos.cuboid(transform.mul_transform(local), Color::rgb(0.4, 0.5, 1.0));
+        }
+    }
+}
+
+fn apply_broad_phase(mut physics: ResMut<PhysicsWorld>, settings: Res<WorldSettings>) {
+    if settings.is_changed() {
+        physics.backend.set_broad_phase(settings.broad_phase);
+    }
+}
+
+fn disable_rapier_stepping(mut rapier_config: ResMut<RapierConfiguration>) {
+    rapier_config.physics_pipeline_active = false;
+}
+
+fn body_state(transform: &Transform, velocity: Option<&Velocity>) -> BodyState {
+    let velocity = velocity.copied().unwrap_or_default();
+    BodyState {
+        position: transform.translation,
+        rotation: transform.rotation,
+        linvel: velocity.linvel,
+        angvel: velocity.angvel,
     }
 }
 
-#[derive(Component)]
-pub struct PhysicsObject {
-    pub mass: f32,
+type BodyQuery<'w, 's> = Query<'w, 's, (
+    Entity,
+    &'static Transform,
+    Option<&'static RigidBody>,
+    Option<&'static Collider>,
+    Option<&'static Velocity>,
+    Option<&'static ColliderMassProperties>,
+    Option<&'static Friction>,
+    Option<&'static Restitution>,
+    Option<&'static Damping>,
+    Option<&'static ExternalForce>,
+    Option<&'static Sensor>,
+    Option<&'static Ccd>,
+), Or<(With<RigidBody>, With<Collider>)>>;
+
+type ChangedBodyFilter = Or<(
+    Added<Transform>,
+    Changed<RigidBody>,
+    Changed<Collider>,
+    Changed<ColliderMassProperties>,
+    Changed<Friction>,
+    Changed<Restitution>,
+    Changed<Damping>,
+    Changed<ExternalForce>,
+    Changed<Sensor>,
+    Changed<Ccd>,
+)>;
+
+type ChangedSleepingQuery<'w, 's> = Query<'w, 's, (Entity, &'static Sleeping), Changed<Sleeping>>;
+
+type MovedBodyFilter = (Or<(Changed<Transform>, Changed<Velocity>)>, Or<(With<RigidBody>, With<Collider>)>);
+
+// Pushes new, edited and removed bodies into the backend
+#[allow(clippy::too_many_arguments)]
+fn sync_bodies(
+    mut physics: ResMut<PhysicsWorld>,
+    bodies: BodyQuery,
+    changed: Query<Entity, ChangedBodyFilter>,
+    moved: Query<(Entity, &Transform, Option<&Velocity>), MovedBodyFilter>,
+    sleeping: ChangedSleepingQuery,
+    mut removed_bodies: RemovedComponents<RigidBody>,
+    mut removed_colliders: RemovedComponents<Collider>,
+    mut removed_sensors: RemovedComponents<Sensor>,
+    mut removed_transforms: RemovedComponents<Transform>,
+) {
+    let removed: Vec<Entity> = removed_bodies.read()
+        .chain(removed_colliders.read())
+        .chain(removed_sensors.read())
+        .chain(removed_transforms.read())
+        .collect();
+    for &entity in &removed {
+        if !bodies.contains(entity) {
+            physics.backend.remove_body(entity);
+            physics.previous.remove(&entity);
+            physics.written.remove(&entity);
+        }
+    }
+
+    let rebuilt: Vec<Entity> = changed.iter().chain(removed.iter().copied()).collect();
+    for &entity in &rebuilt {
+        let Ok((_, transform, rigid_body, collider, velocity, mass, friction, restitution, damping, force, sensor, ccd)) = bodies.get(entity) else {
+            continue;
+        };
+        let desc = BodyDesc {
+            // A collider without a body is static, as in rapier
+            body_type: rigid_body.copied().unwrap_or(RigidBody::Fixed),
+            state: body_state(transform, velocity),
+            collider: collider.map(|collider| ColliderDesc {
+                shape: collider.clone(),
+                mass: mass.copied().unwrap_or_default(),
+                friction: friction.copied().unwrap_or_default(),
+                restitution: restitution.copied().unwrap_or_default(),
+                sensor: sensor.is_some(),
+            }),
+            damping: damping.copied().unwrap_or_default(),
+            force: force.copied().unwrap_or_default(),
+            ccd: ccd.is_some_and(|ccd| ccd.enabled),
+        };
+        physics.backend.set_body(entity, &desc);
+    }
+
+    let mut synced = !removed.is_empty() || !rebuilt.is_empty();
+    for (entity, transform, velocity) in moved.iter() {
+        let state = body_state(transform, velocity);
+        if !rebuilt.contains(&entity) && physics.written.get(&entity) != Some(&state) {
+            physics.backend.set_state(entity, &state);
+            // Teleported, there's nothing to interpolate from
+            physics.previous.remove(&entity);
+            synced = true;
+        }
+    }
+    // `write_back` mirrors the backend's state here, which comes back unchanged
+    for (entity, sleeping) in sleeping.iter() {
+        physics.backend.set_sleeping(entity, sleeping);
+    }
+    if synced {
+        physics.backend.update_queries();
+    }
+}
+
+type ChangedJointQuery<'w, 's> = Query<'w, 's, (Entity, &'static ImpulseJoint, &'static Parent), Or<(Changed<ImpulseJoint>, Changed<Parent>)>>;
+
+// Joint entities are children of their second body, see `joints.rs`
+fn sync_joints(
+    mut physics: ResMut<PhysicsWorld>,
+    joints: ChangedJointQuery,
+    mut removed: RemovedComponents<ImpulseJoint>,
+) {
+    for entity in removed.read() {
+        physics.backend.remove_joint(entity);
+    }
+    for (entity, joint, parent) in joints.iter() {
+        physics.backend.set_joint(entity, &JointDesc {
+            body1: joint.parent,
+            body2: parent.get(),
+            joint: joint.data,
+        });
+    }
 }
 
-fn apply_gravity(
-    mut query: Query<(&PhysicsObject, &mut Transform)>,
+// Interpolated steps one frame may catch up on, time beyond that is dropped so a slow frame
+// doesn't make the next one slower still
+const MAX_STEPS_PER_FRAME: u32 = 8;
+
+// How many steps this frame takes and how long each one is, following the timestep mode
+fn frame_steps(mode: TimestepMode, delta: f32, accumulator: &mut f32) -> (u32, f32, usize) {
+    match mode {
+        TimestepMode::Variable { max_dt, time_scale, substeps } => (1, (delta * time_scale).min(max_dt), substeps),
+        TimestepMode::Fixed { dt, substeps } => (1, dt, substeps),
+        TimestepMode::Interpolated { dt, time_scale, substeps } => {
+            if !dt.is_finite() || dt <= 0.0 {
+                *accumulator = 0.0;
+                return (0, 0.0, substeps);
+            }
+            *accumulator += delta;
+            let mut steps = 0;
+            while *accumulator > 0.0 && steps < MAX_STEPS_PER_FRAME {
+                steps += 1;
+                *accumulator -= dt;
+            }
+            *accumulator = accumulator.min(0.0);
+            (steps, dt * time_scale, substeps)
+        }
+    }
+}
+
+fn step_physics(
+    mut physics: ResMut<PhysicsWorld>,
+    mut control: ResMut<SimulationControl>,
+    settings: Res<WorldSettings>,
+    mode: Res<State<EditorMode>>,
     time: Res<Time>,
+    bodies: Query<Entity, With<RigidBody>>,
 ) {
-    const GRAVITY: f32 = -9.81;
-    
-    for (_physics_object, mut transform) in query.iter_mut() {
-        transform.translation.y += GRAVITY * time.delta_seconds();
+    let Some(timestep) = control.active_timestep() else {
+        return;
+    };
+
+    let delta = time.delta_seconds();
+    let physics = &mut *physics;
+    let (steps, dt, substeps) = frame_steps(timestep, delta, &mut physics.accumulator);
+    let substeps = substeps.max(1);
+    let params = StepParams {
+        dt: dt / substeps as f32,
+        gravity: Vec3::from_array(settings.gravity),
+        velocity_iterations: settings.velocity_iterations,
+        friction_iterations: settings.friction_iterations,
+        stabilization_iterations: settings.stabilization_iterations,
+        max_ccd_substeps: settings.max_ccd_substeps,
+        integrator: settings.integrator,
+    };
+    let interpolated = matches!(timestep, TimestepMode::Interpolated { .. });
+    if !interpolated {
+        physics.previous.clear();
+    }
+    for step in 0..steps {
+        if interpolated && step + 1 == steps {
+            let backend = &physics.backend;
+            physics.previous = bodies.iter().filter_map(|entity| Some((entity, backend.body_state(entity)?))).collect();
+        }
+        for _ in 0..substeps {
+            physics.backend.step(&params);
+        }
     }
+
+    control.record_steps(steps as u64, steps as f32 * dt, delta, *mode.get() == EditorMode::Playing);
 }
 
-fn handle_collisions(
-    mut collision_events: EventReader<CollisionEvent>,
+type WriteBackQuery<'w, 's> = Query<'w, 's, (
+    Entity,
+    &'static RigidBody,
+    &'static mut Transform,
+    Option<&'static mut Velocity>,
+    Option<&'static mut Sleeping>,
+)>;
+
+// Copies simulated poses, velocities and sleep states back onto the entities of moving bodies
+fn write_back(
+    mut physics: ResMut<PhysicsWorld>,
+    control: Res<SimulationControl>,
+    mut bodies: WriteBackQuery,
 ) {
-    for collision_event in collision_events.iter() {
-        match collision_event {
-            CollisionEvent::Started(entity1, entity2, _) => {
-                println!("Collision started between entities: {:?} and {:?}", entity1, entity2);
+    let Some(timestep) = control.active_timestep() else {
+        // Edits made while stopped or paused are always pushed
+        if !physics.written.is_empty() {
+            physics.written.clear();
+        }
+        return;
+    };
+    // An interpolated timestep runs up to one step ahead of real time, the poses shown are
+    // blended back from the last step by the time left over
+    let blend = match timestep {
+        TimestepMode::Interpolated { dt, .. } if dt > 0.0 => Some((1.0 + physics.accumulator / dt).clamp(0.0, 1.0)),
+        _ => None,
+    };
+    let physics = &mut *physics;
+    for (entity, rigid_body, mut transform, mut velocity, sleeping) in bodies.iter_mut() {
+        if *rigid_body == RigidBody::Fixed {
+            continue;
+        }
+        let Some(mut state) = physics.backend.body_state(entity) else {
+            continue;
+        };
+        if let (Some(blend), Some(previous)) = (blend, physics.previous.get(&entity)) {
+            state.position = previous.position.lerp(state.position, blend);
+            state.rotation = previous.rotation.slerp(state.rotation, blend);
+        }
+        // Only touched when something moved, so resting bodies don't look changed
+        if transform.translation != state.position || transform.rotation != state.rotation {
+            transform.translation = state.position;
+            transform.rotation = state.rotation;
+        }
+        if let Some(velocity) = velocity.as_deref_mut() {
+            if velocity.linvel != state.linvel || velocity.angvel != state.angvel {
+                velocity.linvel = state.linvel;
+                velocity.angvel = state.angvel;
             }
-            CollisionEvent::Stopped(entity1, entity2, _) => {
-                println!("Collision stopped between entities: {:?} and {:?}", entity1, entity2);
+        }
+        physics.written.insert(entity, body_state(&transform, velocity.as_deref()));
+        if let Some(mut sleeping) = sleeping {
+            let asleep = physics.backend.is_sleeping(entity);
+            if sleeping.sleeping != asleep {
+                sleeping.sleeping = asleep;
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interpolated_steps_are_capped() {
+        let mode = TimestepMode::Interpolated { dt: 0.01, time_scale: 1.0, substeps: 1 };
+        let mut accumulator = 0.0;
+        assert_eq!(frame_steps(mode, 0.025, &mut accumulator).0, 3);
+        assert!((accumulator + 0.005).abs() < 1e-6);
+
+        // A long hitch only catches up on the capped number of steps and forgets the rest
+        assert_eq!(frame_steps(mode, 1.0, &mut accumulator).0, MAX_STEPS_PER_FRAME);
+        assert_eq!(accumulator, 0.0);
+        assert_eq!(frame_steps(mode, 0.005, &mut accumulator).0, 1);
+    }
+
+    #[test]
+    fn zero_length_steps_are_skipped() {
+        let mut accumulator = 0.0;
+        for dt in [0.0, -0.01, f32::NAN] {
+            let mode = TimestepMode::Interpolated { dt, time_scale: 1.0, substeps: 1 };
+            assert_eq!(frame_steps(mode, 0.016, &mut accumulator).0, 0);
+            assert_eq!(accumulator, 0.0);
+        }
+    }
+}
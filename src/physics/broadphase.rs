@@ -0,0 +1,748 @@
+use std::hash::Hash;
+
+use bevy::prelude::*;
+use bevy::utils::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+
+// Finds which boxes overlap without testing every pair. Keyed by anything small and ordered, so
+// gameplay code can use it on its own with entities or ids, not only the native backend.
+
+// Axis aligned bounding box
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    pub fn new(min: Vec3, max: Vec3) -> Self {
+        Self { min, max }
+    }
+
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn size(&self) -> Vec3 {
+        self.max - self.min
+    }
+
+    pub fn intersects(&self, other: &Aabb) -> bool {
+        self.min.cmple(other.max).all() && other.min.cmple(self.max).all()
+    }
+
+    pub fn contains(&self, other: &Aabb) -> bool {
+        self.min.cmple(other.min).all() && other.max.cmple(self.max).all()
+    }
+
+    pub fn merged(&self, other: &Aabb) -> Aabb {
+        Aabb::new(self.min.min(other.min), self.max.max(other.max))
+    }
+
+    pub fn loosened(&self, margin: f32) -> Aabb {
+        Aabb::new(self.min - Vec3::splat(margin), self.max + Vec3::splat(margin))
+    }
+
+    // Half the surface area, which is what the tree tries to keep small
+    fn half_area(&self) -> f32 {
+        let size = self.size();
+        size.x * size.y + size.y * size.z + size.z * size.x
+    }
+
+    // Distance along the ray at which it enters the box, zero when it starts inside
+    pub fn cast_ray(&self, origin: Vec3, direction: Vec3, max_distance: f32) -> Option<f32> {
+        let inverse = direction.recip();
+        let t1 = (self.min - origin) * inverse;
+        let t2 = (self.max - origin) * inverse;
+        // NaN from a zero direction component on the box boundary is ignored by min/max
+        let enter = t1.min(t2).max_element().max(0.0);
+        let exit = t1.max(t2).min_element().min(max_distance);
+        (enter <= exit).then_some(enter)
+    }
+}
+
+pub trait ProxyKey: Copy + Eq + Hash + Ord + Send + Sync {}
+
+impl<K: Copy + Eq + Hash + Ord + Send + Sync> ProxyKey for K {}
+
+// The pair with its keys in order, so (a, b) and (b, a) are the same
+fn pair<K: ProxyKey>(a: K, b: K) -> (K, K) {
+    if a < b { (a, b) } else { (b, a) }
+}
+
+// Pairs that started or stopped overlapping since the last update
+#[derive(Debug)]
+pub struct PairEvents<K> {
+    pub added: Vec<(K, K)>,
+    pub removed: Vec<(K, K)>,
+}
+
+impl<K> Default for PairEvents<K> {
+    fn default() -> Self {
+        Self { added: Vec::new(), removed: Vec::new() }
+    }
+}
+
+// What a box drawn by the overlay stands for
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum DebugBox {
+    // The box of one proxy, and whether it overlaps another
+    Proxy { paired: bool },
+    // An inner node of a tree, the root at depth 0
+    Node { depth: usize },
+}
+
+fn paired_keys<K: ProxyKey>(pairs: &HashSet<(K, K)>) -> HashSet<K> {
+    pairs.iter().flat_map(|&(a, b)| [a, b]).collect()
+}
+
+pub trait BroadPhase<K: ProxyKey>: Send + Sync {
+    // Adds the proxy, or moves it when it already exists
+    fn set(&mut self, key: K, aabb: Aabb);
+    fn remove(&mut self, key: K);
+    // Brings the overlapping pairs up to date with the boxes set since the last update
+    fn update(&mut self) -> PairEvents<K>;
+    // Proxies whose box overlaps `aabb`
+    fn query_aabb(&self, aabb: &Aabb, visit: &mut dyn FnMut(K));
+    // Proxies whose box the ray enters within `max_distance`, with the distance it enters at
+    fn query_ray(&self, origin: Vec3, direction: Vec3, max_distance: f32, visit: &mut dyn FnMut(K, f32));
+    fn debug_boxes(&self, visit: &mut dyn FnMut(&Aabb, DebugBox));
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub enum BroadPhaseKind {
+    // Boxes sorted along one axis, cheap to update when everything moves a little
+    #[default]
+    SweepAndPrune,
+    // A balanced tree of boxes, only reshaped around proxies that leave their fattened box.
+    // Slower when everything moves, but faster for scenes that mostly rest and for ray queries.
+    DynamicTree,
+}
+
+impl BroadPhaseKind {
+    pub const ALL: [BroadPhaseKind; 2] = [BroadPhaseKind::SweepAndPrune, BroadPhaseKind::DynamicTree];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            BroadPhaseKind::SweepAndPrune => "Sweep and prune",
+            BroadPhaseKind::DynamicTree => "Dynamic AABB tree",
+        }
+    }
+
+    pub fn create<K: ProxyKey + 'static>(self) -> Box<dyn BroadPhase<K>> {
+        match self {
+            BroadPhaseKind::SweepAndPrune => Box::<SweepAndPrune<K>>::default(),
+            BroadPhaseKind::DynamicTree => Box::<DynamicTree<K>>::default(),
+        }
+    }
+}
+
+// Keeps the proxies sorted by the low end of their boxes along the axis they are most spread
+// out on. Sweeping that list only has to look ahead while boxes still overlap on that axis.
+pub struct SweepAndPrune<K> {
+    proxies: Vec<(K, Aabb)>,
+    slots: HashMap<K, usize>,
+    axis: usize,
+    // Whether the proxies are still in order, nothing was set since the last update
+    sorted: bool,
+    pairs: HashSet<(K, K)>,
+}
+
+impl<K> Default for SweepAndPrune<K> {
+    fn default() -> Self {
+        Self { proxies: Vec::new(), slots: HashMap::default(), axis: 0, sorted: true, pairs: HashSet::default() }
+    }
+}
+
+impl<K: ProxyKey> SweepAndPrune<K> {
+    // The axis along which the box centers vary most, so the fewest boxes overlap on it
+    fn sweep_axis(&self) -> usize {
+        let count = self.proxies.len().max(1) as f32;
+        let (sum, sum_squared) = self.proxies.iter().fold((Vec3::ZERO, Vec3::ZERO), |(sum, squared), (_, aabb)| {
+            let center = aabb.center();
+            (sum + center, squared + center * center)
+        });
+        let mean = sum / count;
+        let variance = sum_squared / count - mean * mean;
+        if variance.x >= variance.y && variance.x >= variance.z {
+            0
+        } else if variance.y >= variance.z {
+            1
+        } else {
+            2
+        }
+    }
+}
+
+impl<K: ProxyKey> BroadPhase<K> for SweepAndPrune<K> {
+    fn set(&mut self, key: K, aabb: Aabb) {
+        self.sorted = false;
+        match self.slots.get(&key) {
+            Some(&slot) => self.proxies[slot].1 = aabb,
+            None => {
+                self.slots.insert(key, self.proxies.len());
+                self.proxies.push((key, aabb));
+            }
+        }
+    }
+
+    fn remove(&mut self, key: K) {
+        let Some(slot) = self.slots.remove(&key) else {
+            return;
+        };
+        self.proxies.swap_remove(slot);
+        self.sorted = false;
+        if let Some(&(moved, _)) = self.proxies.get(slot) {
+            self.slots.insert(moved, slot);
+        }
+    }
+
+    fn update(&mut self) -> PairEvents<K> {
+        self.axis = self.sweep_axis();
+        let axis = self.axis;
+        // The standard sort is adaptive and quick on last update's nearly sorted order
+        self.proxies.sort_by(|a, b| a.1.min[axis].total_cmp(&b.1.min[axis]));
+        for (slot, (key, _)) in self.proxies.iter().enumerate() {
+            self.slots.insert(*key, slot);
+        }
+        self.sorted = true;
+
+        let mut pairs = HashSet::default();
+        for (i, (key1, aabb1)) in self.proxies.iter().enumerate() {
+            for (key2, aabb2) in &self.proxies[i + 1..] {
+                if aabb2.min[axis] > aabb1.max[axis] {
+                    break;
+                }
+                if aabb1.intersects(aabb2) {
+                    pairs.insert(pair(*key1, *key2));
+                }
+            }
+        }
+
+        let events = PairEvents {
+            added: pairs.difference(&self.pairs).copied().collect(),
+            removed: self.pairs.difference(&pairs).copied().collect(),
+        };
+        self.pairs = pairs;
+        events
+    }
+
+    fn query_aabb(&self, aabb: &Aabb, visit: &mut dyn FnMut(K)) {
+        // Only the proxies starting before the end of `aabb` can overlap it, as long as nothing
+        // was set since the last update put them in order
+        let candidates = if self.sorted {
+            let end = self.proxies.partition_point(|(_, proxy)| proxy.min[self.axis] <= aabb.max[self.axis]);
+            &self.proxies[..end]
+        } else {
+            &self.proxies[..]
+        };
+        for (key, proxy) in candidates {
+            if proxy.intersects(aabb) {
+                visit(*key);
+            }
+        }
+    }
+
+    // A sorted list doesn't help rays much, every box is tested
+    fn query_ray(&self, origin: Vec3, direction: Vec3, max_distance: f32, visit: &mut dyn FnMut(K, f32)) {
+        for (key, proxy) in &self.proxies {
+            if let Some(distance) = proxy.cast_ray(origin, direction, max_distance) {
+                visit(*key, distance);
+            }
+        }
+    }
+
+    fn debug_boxes(&self, visit: &mut dyn FnMut(&Aabb, DebugBox)) {
+        let paired = paired_keys(&self.pairs);
+        for (key, proxy) in &self.proxies {
+            visit(proxy, DebugBox::Proxy { paired: paired.contains(key) });
+        }
+    }
+}
+
+const NULL: usize = usize::MAX;
+
+struct Node<K> {
+    // Fattened for leaves, so small moves don't change the tree
+    aabb: Aabb,
+    // The proxy's own box, on leaves only
+    tight: Aabb,
+    key: Option<K>,
+    parent: usize,
+    children: [usize; 2],
+    // Leaves are at height 0
+    height: i32,
+}
+
+impl<K> Node<K> {
+    fn is_leaf(&self) -> bool {
+        self.children[0] == NULL
+    }
+}
+
+// A bounding volume hierarchy that changes incrementally, in the manner of Box2D's dynamic tree.
+// Leaves hold a box fattened by `margin` and are only reinserted once their proxy leaves it.
+// Insertion picks the sibling that grows the tree's surface area least, and rotations keep it
+// balanced on the way back up.
+pub struct DynamicTree<K> {
+    nodes: Vec<Node<K>>,
+    free: Vec<usize>,
+    root: usize,
+    leaves: HashMap<K, usize>,
+    margin: f32,
+    // Proxies set or removed since the last update, whose pairs have to be checked again
+    dirty: HashSet<K>,
+    pairs: HashSet<(K, K)>,
+}
+
+impl<K> Default for DynamicTree<K> {
+    fn default() -> Self {
+        Self::with_margin(0.1)
+    }
+}
+
+impl<K> DynamicTree<K> {
+    pub fn with_margin(margin: f32) -> Self {
+        Self {
+            nodes: Vec::new(),
+            free: Vec::new(),
+            root: NULL,
+            leaves: HashMap::default(),
+            margin,
+            dirty: HashSet::default(),
+            pairs: HashSet::default(),
+        }
+    }
+
+    fn allocate(&mut self, node: Node<K>) -> usize {
+        match self.free.pop() {
+            Some(index) => {
+                self.nodes[index] = node;
+                index
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        }
+    }
+
+    // Bounds and height of an inner node from its children
+    fn refit(&mut self, index: usize) {
+        let [left, right] = self.nodes[index].children;
+        self.nodes[index].aabb = self.nodes[left].aabb.merged(&self.nodes[right].aabb);
+        self.nodes[index].height = 1 + self.nodes[left].height.max(self.nodes[right].height);
+    }
+
+    fn replace_child(&mut self, parent: usize, old: usize, new: usize) {
+        if parent == NULL {
+            self.root = new;
+        } else {
+            let children = &mut self.nodes[parent].children;
+            let side = if children[0] == old { 0 } else { 1 };
+            children[side] = new;
+        }
+    }
+
+    // Rotates the taller child up when one side is more than one level deeper than the other,
+    // returning the node now in `index`'s place
+    fn balance(&mut self, index: usize) -> usize {
+        let node = &self.nodes[index];
+        if node.is_leaf() || node.height < 2 {
+            return index;
+        }
+        let [left, right] = node.children;
+        let side = match self.nodes[right].height - self.nodes[left].height {
+            difference if difference > 1 => 1,
+            difference if difference < -1 => 0,
+            _ => return index,
+        };
+
+        let heavy = self.nodes[index].children[side];
+        let [first, second] = self.nodes[heavy].children;
+        let (taller, shorter) = if self.nodes[first].height > self.nodes[second].height {
+            (first, second)
+        } else {
+            (second, first)
+        };
+
+        let parent = self.nodes[index].parent;
+        self.replace_child(parent, index, heavy);
+        self.nodes[heavy].parent = parent;
+        self.nodes[heavy].children = [index, taller];
+        self.nodes[index].parent = heavy;
+        self.nodes[index].children[side] = shorter;
+        self.nodes[shorter].parent = index;
+        self.refit(index);
+        self.refit(heavy);
+        heavy
+    }
+
+    // Balances and refits every ancestor from `index` up to the root
+    fn fix_upwards(&mut self, mut index: usize) {
+        while index != NULL {
+            index = self.balance(index);
+            self.refit(index);
+            index = self.nodes[index].parent;
+        }
+    }
+
+    fn insert_leaf(&mut self, leaf: usize) {
+        if self.root == NULL {
+            self.root = leaf;
+            self.nodes[leaf].parent = NULL;
+            return;
+        }
+
+        // Walk down to the sibling that makes the tree grow least
+        let aabb = self.nodes[leaf].aabb;
+        let mut index = self.root;
+        while !self.nodes[index].is_leaf() {
+            let node = &self.nodes[index];
+            let combined = node.aabb.merged(&aabb).half_area();
+            // Pairing with this node directly creates a parent over it
+            let cost = 2.0 * combined;
+            // Going further down grows this node and every ancestor by the same amount
+            let inherited = 2.0 * (combined - node.aabb.half_area());
+            let child_cost = |child: usize| {
+                let child = &self.nodes[child];
+                let grown = child.aabb.merged(&aabb).half_area();
+                if child.is_leaf() { grown + inherited } else { grown - child.aabb.half_area() + inherited }
+            };
+            let [left, right] = node.children;
+            let (left_cost, right_cost) = (child_cost(left), child_cost(right));
+            if cost < left_cost && cost < right_cost {
+                break;
+            }
+            index = if left_cost < right_cost { left } else { right };
+        }
+
+        let sibling = index;
+        let parent = self.nodes[sibling].parent;
+        let new_parent = self.allocate(Node {
+            aabb: aabb.merged(&self.nodes[sibling].aabb),
+            tight: aabb,
+            key: None,
+            parent,
+            children: [sibling, leaf],
+            height: self.nodes[sibling].height + 1,
+        });
+        self.replace_child(parent, sibling, new_parent);
+        self.nodes[sibling].parent = new_parent;
+        self.nodes[leaf].parent = new_parent;
+        self.fix_upwards(new_parent);
+    }
+
+    fn remove_leaf(&mut self, leaf: usize) {
+        if leaf == self.root {
+            self.root = NULL;
+            return;
+        }
+        // The sibling takes the parent's place
+        let parent = self.nodes[leaf].parent;
+        let grandparent = self.nodes[parent].parent;
+        let [left, right] = self.nodes[parent].children;
+        let sibling = if left == leaf { right } else { left };
+        self.replace_child(grandparent, parent, sibling);
+        self.nodes[sibling].parent = grandparent;
+        self.free.push(parent);
+        if grandparent != NULL {
+            self.fix_upwards(grandparent);
+        }
+    }
+}
+
+impl<K: ProxyKey> BroadPhase<K> for DynamicTree<K> {
+    fn set(&mut self, key: K, aabb: Aabb) {
+        let leaf = match self.leaves.get(&key) {
+            Some(&leaf) => {
+                if self.nodes[leaf].tight == aabb {
+                    return;
+                }
+                self.nodes[leaf].tight = aabb;
+                self.dirty.insert(key);
+                if self.nodes[leaf].aabb.contains(&aabb) {
+                    return;
+                }
+                self.remove_leaf(leaf);
+                leaf
+            }
+            None => {
+                let leaf = self.allocate(Node { aabb, tight: aabb, key: Some(key), parent: NULL, children: [NULL; 2], height: 0 });
+                self.leaves.insert(key, leaf);
+                self.dirty.insert(key);
+                leaf
+            }
+        };
+        self.nodes[leaf].aabb = aabb.loosened(self.margin);
+        self.insert_leaf(leaf);
+    }
+
+    fn remove(&mut self, key: K) {
+        let Some(leaf) = self.leaves.remove(&key) else {
+            return;
+        };
+        self.remove_leaf(leaf);
+        self.free.push(leaf);
+        self.dirty.insert(key);
+    }
+
+    // Only the pairs of proxies that were set or removed can have changed
+    fn update(&mut self) -> PairEvents<K> {
+        let mut events = PairEvents::default();
+        let dirty = std::mem::take(&mut self.dirty);
+        let tight = |tree: &Self, key: K| tree.leaves.get(&key).map(|&leaf| tree.nodes[leaf].tight);
+
+        let mut pairs = std::mem::take(&mut self.pairs);
+        pairs.retain(|&(a, b)| {
+            if !dirty.contains(&a) && !dirty.contains(&b) {
+                return true;
+            }
+            let overlapping = matches!((tight(self, a), tight(self, b)), (Some(a), Some(b)) if a.intersects(&b));
+            if !overlapping {
+                events.removed.push((a, b));
+            }
+            overlapping
+        });
+
+        let mut overlaps = Vec::new();
+        for &key in &dirty {
+            let Some(aabb) = tight(self, key) else {
+                continue;
+            };
+            self.query_aabb(&aabb, &mut |other| {
+                if other != key {
+                    overlaps.push(pair(key, other));
+                }
+            });
+        }
+        for overlap in overlaps {
+            if pairs.insert(overlap) {
+                events.added.push(overlap);
+            }
+        }
+        self.pairs = pairs;
+        events
+    }
+
+    fn query_aabb(&self, aabb: &Aabb, visit: &mut dyn FnMut(K)) {
+        if self.root == NULL {
+            return;
+        }
+        let mut stack = vec![self.root];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if !node.aabb.intersects(aabb) {
+                continue;
+            }
+            match node.key {
+                Some(key) => {
+                    if node.tight.intersects(aabb) {
+                        visit(key);
+                    }
+                }
+                None => stack.extend(node.children),
+            }
+        }
+    }
+
+    fn query_ray(&self, origin: Vec3, direction: Vec3, max_distance: f32, visit: &mut dyn FnMut(K, f32)) {
+        if self.root == NULL {
+            return;
+        }
+        let mut stack = vec![self.root];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if node.aabb.cast_ray(origin, direction, max_distance).is_none() {
+                continue;
+            }
+            match node.key {
+                Some(key) => {
+                    if let Some(distance) = node.tight.cast_ray(origin, direction, max_distance) {
+                        visit(key, distance);
+                    }
+                }
+                None => stack.extend(node.children),
+            }
+        }
+    }
+
+    fn debug_boxes(&self, visit: &mut dyn FnMut(&Aabb, DebugBox)) {
+        if self.root == NULL {
+            return;
+        }
+        let paired = paired_keys(&self.pairs);
+        let mut stack = vec![(self.root, 0)];
+        while let Some((index, depth)) = stack.pop() {
+            let node = &self.nodes[index];
+            match node.key {
+                Some(key) => visit(&node.tight, DebugBox::Proxy { paired: paired.contains(&key) }),
+                None => {
+                    visit(&node.aabb, DebugBox::Node { depth });
+                    stack.extend(node.children.map(|child| (child, depth + 1)));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    use crate::physics::random::Random;
+
+    fn random_box(random: &mut Random, extent: f32, size: f32) -> Aabb {
+        let min = random.vec3(extent);
+        Aabb::new(min, min + random.vec3(size) + Vec3::splat(0.1))
+    }
+
+    // Side of a cube that keeps the density of boxes of up to `size` the same for any count
+    fn extent(count: usize, size: f32) -> f32 {
+        (count as f32).cbrt() * size * 2.0
+    }
+
+    fn scatter(random: &mut Random, count: usize, size: f32) -> Vec<Aabb> {
+        (0..count).map(|_| random_box(random, extent(count, size), size)).collect()
+    }
+
+    fn jitter(random: &mut Random, aabb: &Aabb, amount: f32) -> Aabb {
+        let offset = random.vec3(amount * 2.0) - Vec3::splat(amount);
+        Aabb::new(aabb.min + offset, aabb.max + offset)
+    }
+
+    fn brute_force(boxes: &[Option<Aabb>]) -> BTreeSet<(usize, usize)> {
+        let mut pairs = BTreeSet::new();
+        for (i, a) in boxes.iter().enumerate() {
+            for (j, b) in boxes.iter().enumerate().skip(i + 1) {
+                if let (Some(a), Some(b)) = (a, b) {
+                    if a.intersects(b) {
+                        pairs.insert((i, j));
+                    }
+                }
+            }
+        }
+        pairs
+    }
+
+    // The pairs the events add up to, checked against brute force as boxes move, appear and go
+    fn check_against_brute_force(kind: BroadPhaseKind) {
+        let mut random = Random(0x2545_f491_4f6c_dd1d);
+        let mut boxes: Vec<Option<Aabb>> = scatter(&mut random, 300, 1.0).into_iter().map(Some).collect();
+        let mut broad_phase = kind.create::<usize>();
+        let mut pairs = BTreeSet::new();
+
+        for step in 0..30 {
+            for (key, aabb) in boxes.iter_mut().enumerate() {
+                match aabb {
+                    // Every seventh box is removed and added back on alternate steps
+                    Some(_) if key % 7 == step % 7 && step % 2 == 1 => {
+                        *aabb = None;
+                        broad_phase.remove(key);
+                    }
+                    Some(current) => {
+                        *current = jitter(&mut random, current, 0.2);
+                        broad_phase.set(key, *current);
+                    }
+                    None => {
+                        let restored = random_box(&mut random, extent(300, 1.0), 1.0);
+                        *aabb = Some(restored);
+                        broad_phase.set(key, restored);
+                    }
+                }
+            }
+
+            let events = broad_phase.update();
+            for removed in &events.removed {
+                assert!(pairs.remove(removed), "{}: step {step} removed {removed:?}, which wasn't a pair", kind.label());
+            }
+            for added in &events.added {
+                assert!(pairs.insert(*added), "{}: step {step} added {added:?} twice", kind.label());
+            }
+            assert_eq!(pairs, brute_force(&boxes), "{}: step {step}", kind.label());
+            let mut proxies = 0;
+            broad_phase.debug_boxes(&mut |_, kind| proxies += matches!(kind, DebugBox::Proxy { .. }) as usize);
+            assert_eq!(proxies, boxes.iter().flatten().count(), "{}: step {step}", kind.label());
+        }
+    }
+
+    #[test]
+    fn sweep_and_prune_matches_brute_force() {
+        check_against_brute_force(BroadPhaseKind::SweepAndPrune);
+    }
+
+    #[test]
+    fn dynamic_tree_matches_brute_force() {
+        check_against_brute_force(BroadPhaseKind::DynamicTree);
+    }
+
+    #[test]
+    fn queries_match_brute_force() {
+        let mut random = Random(0x9e37_79b9_7f4a_7c15);
+        let boxes = scatter(&mut random, 500, 1.0);
+        for kind in BroadPhaseKind::ALL {
+            let mut broad_phase = kind.create::<usize>();
+            for (key, aabb) in boxes.iter().enumerate() {
+                broad_phase.set(key, *aabb);
+            }
+            broad_phase.update();
+
+            for _ in 0..20 {
+                let query = boxes[0].merged(&jitter(&mut random, &boxes[0], 3.0));
+                let mut found = BTreeSet::new();
+                broad_phase.query_aabb(&query, &mut |key| {
+                    found.insert(key);
+                });
+                let expected: BTreeSet<usize> = (0..boxes.len()).filter(|&key| boxes[key].intersects(&query)).collect();
+                assert_eq!(found, expected, "{}", kind.label());
+
+                let origin = random.vec3(10.0) - Vec3::splat(5.0);
+                let direction = (random.vec3(2.0) - Vec3::ONE).normalize();
+                let mut hits = BTreeSet::new();
+                broad_phase.query_ray(origin, direction, 20.0, &mut |key, distance| {
+                    assert_eq!(boxes[key].cast_ray(origin, direction, 20.0), Some(distance));
+                    hits.insert(key);
+                });
+                let expected: BTreeSet<usize> = (0..boxes.len())
+                    .filter(|&key| boxes[key].cast_ray(origin, direction, 20.0).is_some())
+                    .collect();
+                assert_eq!(hits, expected, "{}", kind.label());
+            }
+        }
+    }
+
+    #[test]
+    fn dynamic_tree_stays_balanced() {
+        let mut random = Random(0xdead_beef_cafe_f00d);
+        let mut tree = DynamicTree::<usize>::default();
+        // Sorted insertion is the worst case for an unbalanced tree
+        for key in 0..1024 {
+            let min = Vec3::X * key as f32;
+            tree.set(key, Aabb::new(min, min + Vec3::ONE * 0.5));
+        }
+        for key in (0..1024).step_by(3) {
+            tree.remove(key);
+        }
+        for key in 0..1024 {
+            let min = random.vec3(100.0);
+            tree.set(key + 2000, Aabb::new(min, min + Vec3::ONE));
+        }
+        let leaves = tree.leaves.len() as f32;
+        let height = tree.nodes[tree.root].height;
+        assert!(height as f32 <= 2.0 * leaves.log2(), "height {height} for {leaves} leaves");
+
+        // Every node is inside its parent, and every parent knows its children
+        for (key, &leaf) in &tree.leaves {
+            assert_eq!(tree.nodes[leaf].key, Some(*key));
+            let mut index = leaf;
+            while tree.nodes[index].parent != NULL {
+                let parent = tree.nodes[index].parent;
+                assert!(tree.nodes[parent].children.contains(&index));
+                assert!(tree.nodes[parent].aabb.contains(&tree.nodes[index].aabb));
+                index = parent;
+            }
+            assert_eq!(index, tree.root);
+        }
+    }
+}
@@ -0,0 +1,260 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// How the native backend advances free motion over a step. Contacts are solved on top of it.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub enum Integrator {
+    // Position from the old velocity, then velocity: simple but gains energy
+    ExplicitEuler,
+    // Velocity first, then position from the new velocity: symplectic, what rapier uses
+    #[default]
+    SemiImplicitEuler,
+    // Second order and symplectic, exact for constant acceleration
+    VelocityVerlet,
+    // Classic fourth order Runge-Kutta: very accurate per step but slowly loses energy
+    Rk4,
+}
+
+// Position and velocity of a point mass, or of a body's center of mass
+#[derive(PartialEq, Clone, Copy, Debug, Default)]
+pub struct Kinematics {
+    pub position: Vec3,
+    pub velocity: Vec3,
+}
+
+impl Integrator {
+    pub const ALL: [Integrator; 4] = [
+        Integrator::ExplicitEuler,
+        Integrator::SemiImplicitEuler,
+        Integrator::VelocityVerlet,
+        Integrator::Rk4,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Integrator::ExplicitEuler => "Explicit Euler",
+            Integrator::SemiImplicitEuler => "Semi-implicit Euler",
+            Integrator::VelocityVerlet => "Velocity Verlet",
+            Integrator::Rk4 => "RK4",
+        }
+    }
+
+    // How far a constant acceleration `a` moves a body at rest over one step, as a multiple of
+    // a·dt². Contact impulses are spread over the step this way, so a body resting on the ground
+    // isn't pushed up by the impulse cancelling gravity.
+    pub fn displacement_weight(self) -> f32 {
+        match self {
+            Integrator::ExplicitEuler => 0.0,
+            Integrator::SemiImplicitEuler => 1.0,
+            Integrator::VelocityVerlet | Integrator::Rk4 => 0.5,
+        }
+    }
+
+    // Advances `state` by `dt` under `acceleration(position, velocity)`
+    pub fn step(self, state: Kinematics, dt: f32, acceleration: impl Fn(Vec3, Vec3) -> Vec3) -> Kinematics {
+        let Kinematics { position, velocity } = state;
+        match self {
+            Integrator::ExplicitEuler => Kinematics {
+                position: position + velocity * dt,
+                velocity: velocity + acceleration(position, velocity) * dt,
+            },
+            Integrator::SemiImplicitEuler => {
+                let velocity = velocity + acceleration(position, velocity) * dt;
+                Kinematics { position: position + velocity * dt, velocity }
+            }
+            Integrator::VelocityVerlet => {
+                let start = acceleration(position, velocity);
+                let position = position + velocity * dt + start * (0.5 * dt * dt);
+                // Velocity dependent forces (damping) see a first order guess of the new velocity
+                let end = acceleration(position, velocity + start * dt);
+                Kinematics { position, velocity: velocity + (start + end) * (0.5 * dt) }
+            }
+            Integrator::Rk4 => {
+                let k1 = (velocity, acceleration(position, velocity));
+                let p2 = (position + k1.0 * (0.5 * dt), velocity + k1.1 * (0.5 * dt));
+                let k2 = (p2.1, acceleration(p2.0, p2.1));
+                let p3 = (position + k2.0 * (0.5 * dt), velocity + k2.1 * (0.5 * dt));
+                let k3 = (p3.1, acceleration(p3.0, p3.1));
+                let p4 = (position + k3.0 * dt, velocity + k3.1 * dt);
+                let k4 = (p4.1, acceleration(p4.0, p4.1));
+                Kinematics {
+                    position: position + (k1.0 + (k2.0 + k3.0) * 2.0 + k4.0) * (dt / 6.0),
+                    velocity: velocity + (k1.1 + (k2.1 + k3.1) * 2.0 + k4.1) * (dt / 6.0),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    const DT: f32 = 1.0 / 60.0;
+
+    fn simulate(
+        integrator: Integrator,
+        start: Kinematics,
+        steps: usize,
+        acceleration: impl Fn(Vec3, Vec3) -> Vec3,
+    ) -> Vec<Kinematics> {
+        let mut states = vec![start];
+        for _ in 0..steps {
+            let next = integrator.step(*states.last().unwrap(), DT, &acceleration);
+            states.push(next);
+        }
+        states
+    }
+
+    // Largest relative change of the energy over the run, printed for `cargo test -- --nocapture`
+    fn energy_drift(integrator: Integrator, case: &str, states: &[Kinematics], energy: impl Fn(&Kinematics) -> f32) -> f32 {
+        let initial = energy(&states[0]);
+        let drift = states
+            .iter()
+            .map(|state| ((energy(state) - initial) / initial).abs())
+            .fold(0.0, f32::max);
+        let last = (energy(states.last().unwrap()) - initial) / initial;
+        println!("{case:>10} {:>20}: max energy drift {:>9.3}%, final {:>+9.3}%", integrator.label(), drift * 100.0, last * 100.0);
+        drift
+    }
+
+    #[test]
+    fn projectile_matches_analytic_solution() {
+        let gravity = Vec3::new(0.0, -9.81, 0.0);
+        let start = Kinematics { position: Vec3::ZERO, velocity: Vec3::new(3.0, 10.0, -1.0) };
+        let steps = 120;
+        let t = steps as f32 * DT;
+        let expected = start.position + start.velocity * t + gravity * (0.5 * t * t);
+
+        for integrator in Integrator::ALL {
+            let states = simulate(integrator, start, steps, |_, _| gravity);
+            let error = states.last().unwrap().position.distance(expected);
+            // Mechanical energy, measured from the lowest point reached so it stays positive
+            energy_drift(integrator, "projectile", &states, |state| {
+                0.5 * state.velocity.length_squared() - gravity.y * (state.position.y + 20.0)
+            });
+            match integrator {
+                // First order: off by half a step of gravity per step, g·t·dt/2 overall
+                Integrator::ExplicitEuler | Integrator::SemiImplicitEuler => {
+                    let bound = 9.81 * t * DT * 0.5;
+                    assert!((error - bound).abs() < 1e-3, "{}: error {error}, expected {bound}", integrator.label());
+                }
+                // Exact for constant acceleration, up to rounding
+                Integrator::VelocityVerlet | Integrator::Rk4 => {
+                    assert!(error < 1e-4, "{}: error {error}", integrator.label());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn displacement_weight_matches_step() {
+        for integrator in Integrator::ALL {
+            let end = integrator.step(Kinematics::default(), 1.0, |_, _| Vec3::X);
+            assert_eq!(end.position.x, integrator.displacement_weight(), "{}", integrator.label());
+        }
+    }
+
+    #[test]
+    fn harmonic_oscillator_energy() {
+        let omega = 2.0 * PI;
+        let amplitude = 1.0;
+        let start = Kinematics { position: Vec3::X * amplitude, velocity: Vec3::ZERO };
+        // Ten periods
+        let steps = 600;
+        let spring = |position: Vec3, _| -omega * omega * position;
+        let energy = |state: &Kinematics| 0.5 * state.velocity.length_squared() + 0.5 * omega * omega * state.position.length_squared();
+
+        for integrator in Integrator::ALL {
+            let states = simulate(integrator, start, steps, spring);
+            let drift = energy_drift(integrator, "oscillator", &states, energy);
+            let t = steps as f32 * DT;
+            let expected = amplitude * (omega * t).cos();
+            let error = (states.last().unwrap().position.x - expected).abs();
+            match integrator {
+                // Energy grows by a factor (1 + ω²dt²) every step
+                Integrator::ExplicitEuler => {
+                    let growth = (1.0 + (omega * DT).powi(2)).powi(steps as i32) - 1.0;
+                    assert!((drift - growth).abs() < growth * 0.01, "drift {drift}, expected {growth}");
+                }
+                // Symplectic: the energy oscillates within O(ω·dt) but never runs away
+                Integrator::SemiImplicitEuler => {
+                    assert!(drift < omega * DT, "drift {drift}");
+                    assert!(error < 0.2, "error {error}");
+                }
+                Integrator::VelocityVerlet => {
+                    assert!(drift < 0.01, "drift {drift}");
+                    assert!(error < 0.02, "error {error}");
+                }
+                // Fifth order local error: tiny, and only ever decaying
+                Integrator::Rk4 => {
+                    assert!(drift < 1e-4, "drift {drift}");
+                    assert!(error < 1e-3, "error {error}");
+                }
+            }
+        }
+    }
+
+    // Complete elliptic integral of the first kind, through the arithmetic-geometric mean
+    fn elliptic_k(k: f64) -> f64 {
+        let (mut a, mut b) = (1.0, (1.0 - k * k).sqrt());
+        while (a - b).abs() > 1e-15 {
+            (a, b) = ((a + b) / 2.0, (a * b).sqrt());
+        }
+        std::f64::consts::PI / (2.0 * a)
+    }
+
+    // Angle in `position.x`, angular velocity in `velocity.x`
+    fn pendulum(gravity: f32, length: f32) -> impl Fn(Vec3, Vec3) -> Vec3 {
+        move |position: Vec3, _| Vec3::X * (-gravity / length * position.x.sin())
+    }
+
+    // Time of the first upward zero crossing after starting at rest at a positive angle,
+    // which is three quarters of a period
+    fn three_quarter_period(states: &[Kinematics]) -> f32 {
+        let crossings: Vec<f32> = states
+            .windows(2)
+            .enumerate()
+            .filter(|(_, pair)| pair[0].position.x < 0.0 && pair[1].position.x >= 0.0)
+            .map(|(i, pair)| {
+                let fraction = -pair[0].position.x / (pair[1].position.x - pair[0].position.x);
+                (i as f32 + fraction) * DT
+            })
+            .collect();
+        crossings[0]
+    }
+
+    #[test]
+    fn pendulum_period_and_energy() {
+        let (gravity, length) = (9.81, 1.0);
+        let energy = |state: &Kinematics| {
+            0.5 * length * length * state.velocity.x.powi(2) + gravity * length * (1.0 - state.position.x.cos())
+        };
+
+        for (case, angle) in [("pendulum", 0.1), ("pendulum+", 2.0)] {
+            // Exact period for any amplitude, which the small angle formula 2π·√(L/g) underestimates
+            let exact = 4.0 * (length as f64 / gravity as f64).sqrt() * elliptic_k((angle as f64 / 2.0).sin());
+            let start = Kinematics { position: Vec3::X * angle, velocity: Vec3::ZERO };
+            let steps = (exact * 5.0 / DT as f64) as usize;
+
+            for integrator in Integrator::ALL {
+                let states = simulate(integrator, start, steps, pendulum(gravity, length));
+                let drift = energy_drift(integrator, case, &states, energy);
+                let period = three_quarter_period(&states) / 0.75;
+                let error = (period as f64 - exact).abs() / exact;
+                match integrator {
+                    Integrator::ExplicitEuler => assert!(drift > 0.1, "{case}: explicit Euler should gain energy, drift {drift}"),
+                    Integrator::SemiImplicitEuler => {
+                        assert!(drift < 0.2, "{case}: drift {drift}");
+                        assert!(error < 0.01, "{case}: period error {error}");
+                    }
+                    Integrator::VelocityVerlet | Integrator::Rk4 => {
+                        assert!(drift < 1e-3, "{case}: drift {drift}");
+                        assert!(error < 1e-3, "{case}: period error {error}");
+                    }
+                }
+            }
+        }
+    }
+}
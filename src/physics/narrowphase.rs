@@ -0,0 +1,1323 @@
+use std::ops::Mul;
+use std::sync::Arc;
+
+use bevy::prelude::*;
+use bevy_rapier3d::parry::shape::{ConvexPolyhedron, Shape as ParryShape, SharedShape, TypedShape};
+
+use super::broadphase::{Aabb, BroadPhase, DynamicTree};
+
+// Exact contacts between the pairs the broad phase found. GJK finds the distance between the
+// cores of two convex shapes (rounded shapes are a core plus a radius), EPA the penetration when
+// the cores overlap, and the features of both shapes facing each other are clipped against each
+// other for a manifold of up to four points.
+
+// A face or edge whose normal is within this angle (as a sine) of the contact normal counts as
+// lying flat on the contact, so a box resting on the ground gets all four corners
+const FEATURE_TOLERANCE: f32 = 0.05;
+// Closer cores count as touching and go to EPA, which can tell which way they should separate
+const GJK_EPSILON: f32 = 1e-5;
+// GJK stops once a step brings the distance down by less than this share
+const GJK_RELATIVE_TOLERANCE: f32 = 1e-5;
+const GJK_MAX_ITERATIONS: usize = 64;
+const EPA_TOLERANCE: f32 = 1e-4;
+const EPA_MAX_ITERATIONS: usize = 64;
+// Cylinder caps and cone bases are clipped as polygons with this many corners
+const CAP_SEGMENTS: u32 = 16;
+const MAX_MANIFOLD_POINTS: usize = 4;
+
+// A rigid transform, the rotation applied first
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct Pose {
+    pub translation: Vec3,
+    pub rotation: Quat,
+}
+
+impl Pose {
+    pub const IDENTITY: Pose = Pose { translation: Vec3::ZERO, rotation: Quat::IDENTITY };
+
+    pub fn new(translation: Vec3, rotation: Quat) -> Self {
+        Self { translation, rotation }
+    }
+
+    pub fn transform_point(&self, point: Vec3) -> Vec3 {
+        self.translation + self.rotation * point
+    }
+
+    pub fn inverse(&self) -> Pose {
+        let rotation = self.rotation.inverse();
+        Pose { translation: rotation * -self.translation, rotation }
+    }
+
+    // `other` seen from this pose's frame
+    pub fn inv_mul(&self, other: &Pose) -> Pose {
+        self.inverse() * *other
+    }
+}
+
+impl Mul for Pose {
+    type Output = Pose;
+
+    fn mul(self, other: Pose) -> Pose {
+        Pose { translation: self.transform_point(other.translation), rotation: self.rotation * other.rotation }
+    }
+}
+
+// Which vertex, edge or face of a shape a contact point comes from, so the same contact is
+// recognised from one step to the next
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub enum FeatureId {
+    Vertex(u32),
+    Edge(u32),
+    Face(u32),
+}
+
+impl FeatureId {
+    fn code(self) -> u32 {
+        match self {
+            FeatureId::Vertex(id) => id.wrapping_mul(3),
+            FeatureId::Edge(id) => id.wrapping_mul(3).wrapping_add(1),
+            FeatureId::Face(id) => id.wrapping_mul(3).wrapping_add(2),
+        }
+    }
+
+    // The edge between two features, the same whichever order they come in
+    fn edge(a: FeatureId, b: FeatureId) -> FeatureId {
+        if a == b {
+            return a;
+        }
+        let (low, high) = if a.code() < b.code() { (a.code(), b.code()) } else { (b.code(), a.code()) };
+        FeatureId::Edge(low.wrapping_mul(0x9e37_79b1) ^ high.rotate_left(16))
+    }
+}
+
+// The part of a convex shape a radius is added around
+#[derive(Clone, Debug)]
+pub enum Convex {
+    Point,
+    Segment(Vec3, Vec3),
+    Cuboid(Vec3),
+    // Along Y, like rapier's
+    Cylinder { half_height: f32, radius: f32 },
+    // Apex at `half_height` on Y, base at `-half_height`
+    Cone { half_height: f32, radius: f32 },
+    Polyhedron(Arc<Polytope>),
+    Triangle([Vec3; 3]),
+}
+
+// A convex hull with its faces, so a flat contact keeps every corner of the face
+#[derive(Debug)]
+pub struct Polytope {
+    points: Vec<Vec3>,
+    // Outward normal and the vertices around each face
+    faces: Vec<(Vec3, Vec<u32>)>,
+}
+
+// The vertices of a shape furthest along a direction: a vertex, an edge or a face
+struct Feature {
+    vertices: Vec<(Vec3, FeatureId)>,
+    id: FeatureId,
+}
+
+impl Feature {
+    // The face turned towards `direction` if it's within the tolerance angle, else an edge of the
+    // furthest vertex lying nearly across it, else that vertex
+    fn of_polytope(points: &[Vec3], faces: &[(Vec3, Vec<u32>)], direction: Vec3) -> Feature {
+        let numbered = |indices: &[u32]| indices.iter().map(|&i| (points[i as usize], FeatureId::Vertex(i))).collect();
+        let facing = faces.iter().enumerate().max_by(|(_, (a, _)), (_, (b, _))| direction.dot(*a).total_cmp(&direction.dot(*b)));
+        if let Some((index, (normal, vertices))) = facing {
+            if direction.dot(*normal) >= aligned() {
+                return Feature { vertices: numbered(vertices), id: FeatureId::Face(index as u32) };
+            }
+        }
+        let furthest = (0..points.len() as u32)
+            .max_by(|a, b| direction.dot(points[*a as usize]).total_cmp(&direction.dot(points[*b as usize])))
+            .unwrap_or_default();
+        let tilt = |other: u32| {
+            let edge = points[other as usize] - points[furthest as usize];
+            direction.dot(edge).abs() / edge.length()
+        };
+        let neighbour = faces
+            .iter()
+            .flat_map(|(_, ring)| {
+                let count = ring.len();
+                (0..count).filter(move |&k| ring[k] == furthest).flat_map(move |k| [ring[(k + 1) % count], ring[(k + count - 1) % count]])
+            })
+            .filter(|&other| other != furthest && tilt(other) <= FEATURE_TOLERANCE)
+            .min_by(|a, b| tilt(*a).total_cmp(&tilt(*b)));
+        match neighbour {
+            Some(other) => Feature {
+                vertices: numbered(&[furthest, other]),
+                id: FeatureId::edge(FeatureId::Vertex(furthest), FeatureId::Vertex(other)),
+            },
+            None => Feature::vertex(points.get(furthest as usize).copied().unwrap_or_default(), furthest),
+        }
+    }
+
+    fn vertex(point: Vec3, id: u32) -> Feature {
+        Feature { vertices: vec![(point, FeatureId::Vertex(id))], id: FeatureId::Vertex(id) }
+    }
+
+    // A circle on a plane of constant `y`, as a polygon
+    fn cap(y: f32, radius: f32, first_vertex: u32, face: u32) -> Feature {
+        let vertices = (0..CAP_SEGMENTS)
+            .map(|k| {
+                let angle = k as f32 / CAP_SEGMENTS as f32 * std::f32::consts::TAU;
+                (Vec3::new(radius * angle.cos(), y, radius * angle.sin()), FeatureId::Vertex(first_vertex + k))
+            })
+            .collect();
+        Feature { vertices, id: FeatureId::Face(face) }
+    }
+
+    fn segment(a: Vec3, b: Vec3, first_vertex: u32, face: u32) -> Feature {
+        Feature {
+            vertices: vec![(a, FeatureId::Vertex(first_vertex)), (b, FeatureId::Vertex(first_vertex + 1))],
+            id: FeatureId::Face(face),
+        }
+    }
+}
+
+// Cosine of the feature tolerance angle
+fn aligned() -> f32 {
+    (1.0 - FEATURE_TOLERANCE * FEATURE_TOLERANCE).sqrt()
+}
+
+impl Convex {
+    fn support(&self, direction: Vec3) -> Vec3 {
+        let furthest = |points: &[Vec3]| {
+            points.iter().copied().max_by(|a, b| direction.dot(*a).total_cmp(&direction.dot(*b))).unwrap_or_default()
+        };
+        match self {
+            Convex::Point => Vec3::ZERO,
+            Convex::Segment(a, b) => furthest(&[*a, *b]),
+            Convex::Cuboid(half_extents) => Vec3::select(direction.cmpge(Vec3::ZERO), *half_extents, -*half_extents),
+            Convex::Cylinder { half_height, radius } => {
+                let rim = Vec3::new(direction.x, 0.0, direction.z).normalize_or_zero() * *radius;
+                rim + Vec3::Y * half_height.copysign(direction.y)
+            }
+            Convex::Cone { half_height, radius } => {
+                let rim = Vec3::new(direction.x, 0.0, direction.z).normalize_or_zero() * *radius;
+                furthest(&[Vec3::Y * *half_height, rim - Vec3::Y * *half_height])
+            }
+            Convex::Polyhedron(polytope) => furthest(&polytope.points),
+            Convex::Triangle(points) => furthest(points),
+        }
+    }
+
+    fn feature(&self, direction: Vec3) -> Feature {
+        let direction = direction.normalize_or_zero();
+        match self {
+            Convex::Point => Feature::vertex(Vec3::ZERO, 0),
+            Convex::Segment(a, b) => {
+                let along = direction.dot(*b - *a);
+                if *a != *b && along.abs() <= FEATURE_TOLERANCE * a.distance(*b) {
+                    Feature::segment(*a, *b, 0, 0)
+                } else if along > 0.0 {
+                    Feature::vertex(*b, 1)
+                } else {
+                    Feature::vertex(*a, 0)
+                }
+            }
+            // Axes the direction runs nearly across span the feature, the others pick a side. Corners
+            // are numbered by the signs of their coordinates.
+            Convex::Cuboid(half_extents) => {
+                let spanned = direction.abs().cmple(Vec3::splat(FEATURE_TOLERANCE)).bitmask();
+                let sides = direction.cmpge(Vec3::ZERO).bitmask() & !spanned;
+                let vertices: Vec<(Vec3, FeatureId)> = (0..8u32)
+                    .filter(|bits| bits & !spanned == sides)
+                    .map(|bits| {
+                        let sign = Vec3::select(BVec3::new(bits & 1 != 0, bits & 2 != 0, bits & 4 != 0), Vec3::ONE, -Vec3::ONE);
+                        (*half_extents * sign, FeatureId::Vertex(bits))
+                    })
+                    .collect();
+                let id = match vertices.as_slice() {
+                    [(_, id)] => *id,
+                    [(_, a), (_, b)] => FeatureId::edge(*a, *b),
+                    _ => FeatureId::Face(spanned | sides << 3),
+                };
+                Feature { vertices, id }
+            }
+            Convex::Polyhedron(polytope) => Feature::of_polytope(&polytope.points, &polytope.faces, direction),
+            Convex::Triangle(points) => {
+                let normal = (points[1] - points[0]).cross(points[2] - points[0]).normalize_or_zero();
+                Feature::of_polytope(points, &[(normal, vec![0, 1, 2]), (-normal, vec![0, 2, 1])], direction)
+            }
+            // Vertices 0..2n are the two caps, then the ends of the side line and the rim point
+            Convex::Cylinder { half_height, radius } => {
+                let radial = Vec3::new(direction.x, 0.0, direction.z);
+                let top = direction.y >= 0.0;
+                let y = half_height.copysign(direction.y);
+                let rim = radial.normalize_or_zero() * *radius;
+                if direction.y.abs() >= aligned() {
+                    Feature::cap(y, *radius, if top { CAP_SEGMENTS } else { 0 }, top as u32)
+                } else if direction.y.abs() <= FEATURE_TOLERANCE {
+                    Feature::segment(rim - Vec3::Y * *half_height, rim + Vec3::Y * *half_height, 2 * CAP_SEGMENTS, 2)
+                } else {
+                    Feature::vertex(rim + Vec3::Y * y, 2 * CAP_SEGMENTS + 2 + top as u32)
+                }
+            }
+            // Vertices 0..n are the base, then the apex and the rim point below the side line
+            Convex::Cone { half_height, radius } => {
+                let radial = Vec3::new(direction.x, 0.0, direction.z).normalize_or_zero();
+                let apex = Vec3::Y * *half_height;
+                let rim = radial * *radius - Vec3::Y * *half_height;
+                let side_normal = (radial * 2.0 * *half_height + Vec3::Y * *radius).normalize_or_zero();
+                if -direction.y >= aligned() {
+                    Feature::cap(-*half_height, *radius, 0, 0)
+                } else if radial != Vec3::ZERO && direction.dot(side_normal) >= aligned() {
+                    Feature::segment(apex, rim, CAP_SEGMENTS, 1)
+                } else if direction.dot(apex) >= direction.dot(rim) {
+                    Feature::vertex(apex, CAP_SEGMENTS)
+                } else {
+                    Feature::vertex(rim, CAP_SEGMENTS + 1)
+                }
+            }
+        }
+    }
+}
+
+// A convex core and the radius around it
+#[derive(Clone, Debug)]
+pub struct ConvexShape {
+    pub core: Convex,
+    pub radius: f32,
+}
+
+impl ConvexShape {
+    fn from_parry(shape: &dyn ParryShape) -> Option<Self> {
+        let polyhedron = |shape: &ConvexPolyhedron| {
+            let faces = shape
+                .faces()
+                .iter()
+                .map(|face| {
+                    let first = face.first_vertex_or_edge as usize;
+                    let ring = &shape.vertices_adj_to_face()[first..first + face.num_vertices_or_edges as usize];
+                    (Vec3::from(*face.normal), ring.to_vec())
+                })
+                .collect();
+            Convex::Polyhedron(Arc::new(Polytope { points: shape.points().iter().map(|point| Vec3::from(*point)).collect(), faces }))
+        };
+        let cylinder = |half_height, radius| Convex::Cylinder { half_height, radius };
+        let cone = |half_height, radius| Convex::Cone { half_height, radius };
+        let (core, radius) = match shape.as_typed_shape() {
+            TypedShape::Ball(ball) => (Convex::Point, ball.radius),
+            TypedShape::Cuboid(cuboid) => (Convex::Cuboid(cuboid.half_extents.into()), 0.0),
+            TypedShape::Capsule(capsule) => (Convex::Segment(capsule.segment.a.into(), capsule.segment.b.into()), capsule.radius),
+            TypedShape::Cylinder(shape) => (cylinder(shape.half_height, shape.radius), 0.0),
+            TypedShape::Cone(shape) => (cone(shape.half_height, shape.radius), 0.0),
+            TypedShape::ConvexPolyhedron(shape) => (polyhedron(shape), 0.0),
+            TypedShape::Triangle(shape) => (Convex::Triangle([shape.a.into(), shape.b.into(), shape.c.into()]), 0.0),
+            TypedShape::RoundCuboid(shape) => (Convex::Cuboid(shape.inner_shape.half_extents.into()), shape.border_radius),
+            TypedShape::RoundCylinder(shape) => {
+                (cylinder(shape.inner_shape.half_height, shape.inner_shape.radius), shape.border_radius)
+            }
+            TypedShape::RoundCone(shape) => (cone(shape.inner_shape.half_height, shape.inner_shape.radius), shape.border_radius),
+            TypedShape::RoundConvexPolyhedron(shape) => (polyhedron(&shape.inner_shape), shape.border_radius),
+            _ => return None,
+        };
+        Some(ConvexShape { core, radius })
+    }
+
+    fn support(&self, direction: Vec3) -> Vec3 {
+        self.core.support(direction) + direction.normalize_or_zero() * self.radius
+    }
+
+    fn aabb(&self, pose: &Pose) -> Aabb {
+        let inverse = pose.rotation.inverse();
+        let extreme = |axis: Vec3| pose.transform_point(self.support(inverse * axis)).dot(axis);
+        Aabb::new(
+            Vec3::new(-extreme(-Vec3::X), -extreme(-Vec3::Y), -extreme(-Vec3::Z)),
+            Vec3::new(extreme(Vec3::X), extreme(Vec3::Y), extreme(Vec3::Z)),
+        )
+    }
+}
+
+pub struct TriangleMesh {
+    triangles: Vec<[Vec3; 3]>,
+    // Triangles found by their bounds, so only the few near the other shape are tested
+    tree: DynamicTree<u32>,
+    aabb: Aabb,
+}
+
+// A collider as the narrow phase sees it
+pub enum Shape {
+    Convex(ConvexShape),
+    // Parts with their pose in the shape's frame, like a compound collider's
+    Compound(Vec<(Pose, ConvexShape)>),
+    TriMesh(TriangleMesh),
+}
+
+// Bounds of `aabb` once moved by `pose`
+fn transformed(aabb: &Aabb, pose: &Pose) -> Aabb {
+    let corners = (0..8).map(|bits| {
+        let corner = Vec3::select(
+            BVec3::new(bits & 1 != 0, bits & 2 != 0, bits & 4 != 0),
+            aabb.max,
+            aabb.min,
+        );
+        pose.transform_point(corner)
+    });
+    corners.fold(Aabb::new(Vec3::INFINITY, Vec3::NEG_INFINITY), |bounds, corner| {
+        Aabb::new(bounds.min.min(corner), bounds.max.max(corner))
+    })
+}
+
+impl Shape {
+    // None for shapes the editor doesn't create, like height fields
+    pub fn from_collider(shape: &SharedShape) -> Option<Shape> {
+        match shape.as_typed_shape() {
+            TypedShape::Compound(compound) => compound
+                .shapes()
+                .iter()
+                .map(|(pose, part)| {
+                    let pose = Pose::new(pose.translation.vector.into(), pose.rotation.into());
+                    Some((pose, ConvexShape::from_parry(&**part)?))
+                })
+                .collect::<Option<Vec<_>>>()
+                .map(Shape::Compound),
+            TypedShape::TriMesh(mesh) => {
+                let triangles: Vec<[Vec3; 3]> = mesh.triangles().map(|t| [t.a.into(), t.b.into(), t.c.into()]).collect();
+                let mut tree = DynamicTree::with_margin(0.0);
+                let mut aabb = Aabb::new(Vec3::INFINITY, Vec3::NEG_INFINITY);
+                for (index, triangle) in triangles.iter().enumerate() {
+                    let bounds = Aabb::new(triangle[0].min(triangle[1]).min(triangle[2]), triangle[0].max(triangle[1]).max(triangle[2]));
+                    tree.set(index as u32, bounds);
+                    aabb = aabb.merged(&bounds);
+                }
+                Some(Shape::TriMesh(TriangleMesh { triangles, tree, aabb }))
+            }
+            _ => ConvexShape::from_parry(&**shape).map(Shape::Convex),
+        }
+    }
+
+    fn aabb(&self, pose: &Pose) -> Aabb {
+        match self {
+            Shape::Convex(convex) => convex.aabb(pose),
+            Shape::Compound(parts) => parts
+                .iter()
+                .map(|(part, convex)| convex.aabb(&(*pose * *part)))
+                .reduce(|a, b| a.merged(&b))
+                .unwrap_or(Aabb::new(pose.translation, pose.translation)),
+            Shape::TriMesh(mesh) => transformed(&mesh.aabb, pose),
+        }
+    }
+
+    // The convex parts overlapping `near`, which is in this shape's frame, with their index
+    fn parts_near(&self, near: &Aabb, visit: &mut dyn FnMut(u32, &Pose, &ConvexShape)) {
+        match self {
+            Shape::Convex(convex) => visit(0, &Pose::IDENTITY, convex),
+            Shape::Compound(parts) => {
+                for (index, (pose, convex)) in parts.iter().enumerate() {
+                    if convex.aabb(pose).intersects(near) {
+                        visit(index as u32, pose, convex);
+                    }
+                }
+            }
+            Shape::TriMesh(mesh) => mesh.tree.query_aabb(near, &mut |index| {
+                let triangle = ConvexShape { core: Convex::Triangle(mesh.triangles[index as usize]), radius: 0.0 };
+                visit(index, &Pose::IDENTITY, &triangle);
+            }),
+        }
+    }
+}
+
+// A point of the Minkowski difference of two shapes, with the points on each it came from
+#[derive(Clone, Copy, Debug)]
+struct SupportPoint {
+    w: Vec3,
+    a: Vec3,
+    b: Vec3,
+}
+
+impl SupportPoint {
+    fn new(a: Vec3, b: Vec3) -> Self {
+        Self { w: a - b, a, b }
+    }
+}
+
+// Closest point to the origin on a simplex, as weights of its vertices. Vertices that don't
+// contribute get a weight of zero.
+fn closest_on_simplex(simplex: &[SupportPoint]) -> Vec<f32> {
+    match simplex {
+        [_] => vec![1.0],
+        [a, b] => {
+            let ab = b.w - a.w;
+            let t = (-a.w.dot(ab) / ab.length_squared().max(f32::MIN_POSITIVE)).clamp(0.0, 1.0);
+            vec![1.0 - t, t]
+        }
+        [a, b, c] => closest_on_triangle(a.w, b.w, c.w).to_vec(),
+        [a, b, c, d] => {
+            // The closest point on the faces the origin is in front of, unless it's inside
+            let faces = [([0, 1, 2], 3), ([0, 1, 3], 2), ([0, 2, 3], 1), ([1, 2, 3], 0)];
+            let points = [a.w, b.w, c.w, d.w];
+            let mut best: Option<(f32, Vec<f32>)> = None;
+            for (face, opposite) in faces {
+                let [i, j, k] = face.map(|index| points[index]);
+                let normal = (j - i).cross(k - i);
+                let origin_side = normal.dot(-i);
+                let opposite_side = normal.dot(points[opposite] - i);
+                let flat = opposite_side.abs() <= f32::EPSILON * normal.length() * (points[opposite] - i).length();
+                if origin_side * opposite_side < 0.0 || flat {
+                    let weights = closest_on_triangle(i, j, k);
+                    let point = i * weights[0] + j * weights[1] + k * weights[2];
+                    if best.as_ref().is_none_or(|(distance, _)| point.length_squared() < *distance) {
+                        let mut all = vec![0.0; 4];
+                        for (index, weight) in face.iter().zip(weights) {
+                            all[*index] = weight;
+                        }
+                        best = Some((point.length_squared(), all));
+                    }
+                }
+            }
+            best.map_or(vec![0.25; 4], |(_, weights)| weights)
+        }
+        _ => unreachable!("a simplex has one to four vertices"),
+    }
+}
+
+// Ericson, Real-Time Collision Detection, 5.1.5, for the point at the origin
+fn closest_on_triangle(a: Vec3, b: Vec3, c: Vec3) -> [f32; 3] {
+    let (ab, ac) = (b - a, c - a);
+    let (d1, d2) = (ab.dot(-a), ac.dot(-a));
+    if d1 <= 0.0 && d2 <= 0.0 {
+        return [1.0, 0.0, 0.0];
+    }
+    let (d3, d4) = (ab.dot(-b), ac.dot(-b));
+    if d3 >= 0.0 && d4 <= d3 {
+        return [0.0, 1.0, 0.0];
+    }
+    let vc = d1 * d4 - d3 * d2;
+    if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+        let v = d1 / (d1 - d3);
+        return [1.0 - v, v, 0.0];
+    }
+    let (d5, d6) = (ab.dot(-c), ac.dot(-c));
+    if d6 >= 0.0 && d5 <= d6 {
+        return [0.0, 0.0, 1.0];
+    }
+    let vb = d5 * d2 - d1 * d6;
+    if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+        let w = d2 / (d2 - d6);
+        return [1.0 - w, 0.0, w];
+    }
+    let va = d3 * d6 - d5 * d4;
+    if va <= 0.0 && d4 - d3 >= 0.0 && d5 - d6 >= 0.0 {
+        let w = (d4 - d3) / ((d4 - d3) + (d5 - d6));
+        return [0.0, 1.0 - w, w];
+    }
+    let denominator = 1.0 / (va + vb + vc);
+    let (v, w) = (vb * denominator, vc * denominator);
+    [1.0 - v - w, v, w]
+}
+
+struct Gjk {
+    // The last simplex, which holds the origin when the shapes overlap
+    simplex: Vec<SupportPoint>,
+    distance: f32,
+    // Closest points on the two shapes
+    a: Vec3,
+    b: Vec3,
+}
+
+// Distance between two convex shapes given the support function of their Minkowski difference
+fn gjk(support: &dyn Fn(Vec3) -> SupportPoint, initial_direction: Vec3) -> Gjk {
+    let direction = if initial_direction.length_squared() > f32::EPSILON { initial_direction } else { Vec3::X };
+    let first = support(direction);
+    let mut simplex = vec![first];
+    let mut weights = vec![1.0];
+    let mut closest = first.w;
+
+    for _ in 0..GJK_MAX_ITERATIONS {
+        let distance_squared = closest.length_squared();
+        if distance_squared <= GJK_EPSILON * GJK_EPSILON {
+            break;
+        }
+        let point = support(-closest);
+        // No vertex gets any closer: `closest` is the closest point of the difference
+        if distance_squared - closest.dot(point.w) <= GJK_RELATIVE_TOLERANCE * distance_squared
+            || simplex.iter().any(|vertex| vertex.w == point.w)
+        {
+            break;
+        }
+        let mut grown = simplex.clone();
+        grown.push(point);
+        let grown_weights = closest_on_simplex(&grown);
+        let kept: Vec<(SupportPoint, f32)> =
+            grown.into_iter().zip(grown_weights).filter(|(_, weight)| *weight > 0.0).collect();
+        if kept.len() == 4 {
+            simplex = kept.iter().map(|(vertex, _)| *vertex).collect();
+            closest = Vec3::ZERO;
+            break;
+        }
+        let next: Vec3 = kept.iter().map(|(vertex, weight)| vertex.w * *weight).sum();
+        // Rounding on a nearly flat simplex can only move away from the origin, stop before it does
+        if next.length_squared() >= distance_squared {
+            break;
+        }
+        simplex = kept.iter().map(|(vertex, _)| *vertex).collect();
+        weights = kept.iter().map(|(_, weight)| *weight).collect();
+        closest = next;
+    }
+
+    let a = simplex.iter().zip(&weights).map(|(vertex, weight)| vertex.a * *weight).sum();
+    let b = simplex.iter().zip(&weights).map(|(vertex, weight)| vertex.b * *weight).sum();
+    Gjk { simplex, distance: closest.length(), a, b }
+}
+
+struct Penetration {
+    // From the first shape into the second
+    normal: Vec3,
+    a: Vec3,
+    b: Vec3,
+}
+
+// Grows a simplex around the origin into a tetrahedron, or gives up when the difference is flat
+fn blow_up(mut simplex: Vec<SupportPoint>, support: &dyn Fn(Vec3) -> SupportPoint) -> Option<Vec<SupportPoint>> {
+    let far_from = |simplex: &[SupportPoint], point: &SupportPoint| match simplex {
+        [a] => a.w.distance(point.w) > GJK_EPSILON,
+        [a, b] => (b.w - a.w).normalize_or_zero().cross(point.w - a.w).length() > GJK_EPSILON,
+        [a, b, c] => (b.w - a.w).cross(c.w - a.w).normalize_or_zero().dot(point.w - a.w).abs() > GJK_EPSILON,
+        _ => false,
+    };
+    while simplex.len() < 4 {
+        let directions: Vec<Vec3> = match simplex.as_slice() {
+            [_] => vec![Vec3::X, -Vec3::X, Vec3::Y, -Vec3::Y, Vec3::Z, -Vec3::Z],
+            [a, b] => {
+                let axis = (b.w - a.w).normalize_or_zero();
+                let perpendicular = axis.any_orthonormal_vector();
+                (0..6).map(|k| Quat::from_axis_angle(axis, k as f32 * std::f32::consts::FRAC_PI_3) * perpendicular).collect()
+            }
+            [a, b, c] => {
+                let normal = (b.w - a.w).cross(c.w - a.w);
+                vec![normal, -normal]
+            }
+            _ => unreachable!(),
+        };
+        let point = directions.into_iter().map(support).find(|point| far_from(&simplex, point))?;
+        simplex.push(point);
+    }
+    Some(simplex)
+}
+
+#[derive(Clone, Copy)]
+struct EpaFace {
+    vertices: [usize; 3],
+    normal: Vec3,
+    distance: f32,
+}
+
+fn epa_face(points: &[SupportPoint], vertices: [usize; 3]) -> Option<EpaFace> {
+    let [a, b, c] = vertices.map(|index| points[index].w);
+    let normal = (b - a).cross(c - a).try_normalize()?;
+    Some(EpaFace { vertices, normal, distance: normal.dot(a) })
+}
+
+// Penetration of two overlapping shapes: expands a polytope inside their Minkowski difference
+// until the face closest to the origin is on its boundary
+fn epa(simplex: Vec<SupportPoint>, support: &dyn Fn(Vec3) -> SupportPoint) -> Option<Penetration> {
+    let mut points = blow_up(simplex, support)?;
+    // Faces wound so their normals point away from the opposite vertex
+    let mut faces = Vec::new();
+    for (face, opposite) in [([0, 1, 2], 3), ([0, 3, 1], 2), ([0, 2, 3], 1), ([1, 3, 2], 0)] {
+        let mut face = epa_face(&points, face)?;
+        if face.normal.dot(points[opposite].w) > face.distance {
+            face = epa_face(&points, [face.vertices[0], face.vertices[2], face.vertices[1]])?;
+        }
+        faces.push(face);
+    }
+
+    let mut closest;
+    let mut iterations = 0;
+    loop {
+        let (index, face) = faces.iter().enumerate().min_by(|a, b| a.1.distance.total_cmp(&b.1.distance))?;
+        closest = *face;
+        let point = support(closest.normal);
+        iterations += 1;
+        if point.w.dot(closest.normal) - closest.distance <= EPA_TOLERANCE * closest.distance.abs().max(1.0)
+            || iterations > EPA_MAX_ITERATIONS
+        {
+            break;
+        }
+
+        // Faces the new point sees are replaced by a fan from it to the edges around them. They
+        // are collected from the closest face outwards, so rounding can't punch a hole elsewhere.
+        let visible = |face: &EpaFace| face.normal.dot(point.w) > face.distance;
+        let edges = |face: &EpaFace| {
+            let [a, b, c] = face.vertices;
+            [(a, b), (b, c), (c, a)]
+        };
+        let mut removed = vec![false; faces.len()];
+        removed[index] = true;
+        let mut stack = vec![index];
+        while let Some(current) = stack.pop() {
+            for (other, face) in faces.iter().enumerate() {
+                let adjacent = edges(&faces[current]).iter().any(|&(from, to)| edges(face).contains(&(to, from)));
+                if !removed[other] && adjacent && visible(face) {
+                    removed[other] = true;
+                    stack.push(other);
+                }
+            }
+        }
+        let horizon: Vec<(usize, usize)> = (0..faces.len())
+            .filter(|&face| removed[face])
+            .flat_map(|face| edges(&faces[face]))
+            .filter(|&(from, to)| !(0..faces.len()).any(|face| removed[face] && edges(&faces[face]).contains(&(to, from))))
+            .collect();
+
+        points.push(point);
+        let new = points.len() - 1;
+        // A point in line with an edge of the horizon would leave a hole, this is as close as it gets
+        let Some(fan) = horizon.into_iter().map(|(from, to)| epa_face(&points, [from, to, new])).collect::<Option<Vec<_>>>() else {
+            break;
+        };
+        let mut kept = removed.iter();
+        faces.retain(|_| !kept.next().unwrap());
+        faces.extend(fan);
+    }
+
+    // Faces in the same plane as the closest one split a face of the difference into triangles.
+    // The one the origin projects into holds the point closest to it.
+    let on_triangle = |face: &EpaFace| {
+        let [a, b, c] = face.vertices.map(|index| points[index]);
+        let weights = closest_on_triangle(a.w, b.w, c.w);
+        (a.w * weights[0] + b.w * weights[1] + c.w * weights[2], weights)
+    };
+    let coplanar = |face: &&EpaFace| {
+        face.normal.dot(closest.normal) >= 1.0 - EPA_TOLERANCE
+            && (face.distance - closest.distance).abs() <= EPA_TOLERANCE * closest.distance.abs().max(1.0)
+    };
+    let face = faces
+        .iter()
+        .filter(coplanar)
+        .min_by(|a, b| on_triangle(a).0.length_squared().total_cmp(&on_triangle(b).0.length_squared()))
+        .unwrap_or(&closest);
+    let [a, b, c] = face.vertices.map(|index| points[index]);
+    let weights = on_triangle(face).1;
+    Some(Penetration {
+        normal: closest.normal,
+        a: a.a * weights[0] + b.a * weights[1] + c.a * weights[2],
+        b: a.b * weights[0] + b.b * weights[1] + c.b * weights[2],
+    })
+}
+
+// One contact point between two convex shapes, everything in the frame of the first
+struct RawContact {
+    point1: Vec3,
+    point2: Vec3,
+    dist: f32,
+    fid1: FeatureId,
+    fid2: FeatureId,
+}
+
+// A feature laid flat on the contact plane: 2D positions with their height along the normal
+struct FlatFeature {
+    points: Vec<(Vec2, f32, FeatureId)>,
+    id: FeatureId,
+}
+
+impl FlatFeature {
+    fn new(feature: &Feature, tangents: (Vec3, Vec3), normal: Vec3) -> Self {
+        let points = feature
+            .vertices
+            .iter()
+            .map(|(vertex, id)| (Vec2::new(vertex.dot(tangents.0), vertex.dot(tangents.1)), vertex.dot(normal), *id))
+            .collect();
+        FlatFeature { points: convex_hull_2d(points), id: feature.id }
+    }
+
+    // The point of the feature above `point` on the contact plane, and its height. Segments only
+    // run nearly parallel to each other, so the nearest point on the segment stands in.
+    fn surface(&self, point: Vec2) -> (Vec2, f32) {
+        match self.points.as_slice() {
+            [(position, height, _)] => (*position, *height),
+            [(a, height_a, _), (b, height_b, _)] => {
+                let ab = *b - *a;
+                let t = ((point - *a).dot(ab) / ab.length_squared().max(f32::MIN_POSITIVE)).clamp(0.0, 1.0);
+                (a.lerp(*b, t), height_a + (height_b - height_a) * t)
+            }
+            points => {
+                // Newell's method gives the plane through a polygon even when it isn't quite flat
+                let mut plane = Vec3::ZERO;
+                let mut center = Vec3::ZERO;
+                for (i, (position, height, _)) in points.iter().enumerate() {
+                    let current = position.extend(*height);
+                    let (next, next_height, _) = points[(i + 1) % points.len()];
+                    let next = next.extend(next_height);
+                    plane += Vec3::new(
+                        (current.y - next.y) * (current.z + next.z),
+                        (current.z - next.z) * (current.x + next.x),
+                        (current.x - next.x) * (current.y + next.y),
+                    );
+                    center += current;
+                }
+                center /= points.len() as f32;
+                if plane.z.abs() <= f32::EPSILON * plane.length() {
+                    return (point, center.z);
+                }
+                (point, center.z - (plane.x * (point.x - center.x) + plane.y * (point.y - center.y)) / plane.z)
+            }
+        }
+    }
+}
+
+// Andrew's monotone chain, counter-clockwise and without duplicate or collinear points
+fn convex_hull_2d(mut points: Vec<(Vec2, f32, FeatureId)>) -> Vec<(Vec2, f32, FeatureId)> {
+    points.sort_by(|a, b| a.0.x.total_cmp(&b.0.x).then(a.0.y.total_cmp(&b.0.y)));
+    let scale = points.iter().fold(0.0f32, |scale, (point, _, _)| scale.max(point.abs().max_element())).max(1.0);
+    points.dedup_by(|a, b| a.0.distance(b.0) <= 1e-6 * scale);
+    if points.len() < 3 {
+        return points;
+    }
+    // Nearly straight corners are dropped too, a sliver has no plane to measure heights on
+    let turns_left = |o: Vec2, a: Vec2, b: Vec2| (a - o).perp_dot(b - o) > 1e-4 * (a - o).length() * (b - o).length();
+    let mut hull: Vec<(Vec2, f32, FeatureId)> = Vec::new();
+    for pass in [&points[..], &points[..]] {
+        let start = hull.len();
+        let iterator: Box<dyn Iterator<Item = &(Vec2, f32, FeatureId)>> =
+            if start == 0 { Box::new(pass.iter()) } else { Box::new(pass.iter().rev()) };
+        for point in iterator {
+            while hull.len() >= start + 2 && !turns_left(hull[hull.len() - 2].0, hull[hull.len() - 1].0, point.0) {
+                hull.pop();
+            }
+            hull.push(*point);
+        }
+        hull.pop();
+    }
+    if hull.len() < 2 {
+        // Everything was on one line, keep its two ends
+        return vec![points[0], points[points.len() - 1]];
+    }
+    hull
+}
+
+// A point of the clipped contact polygon, with the features of each side it lies on
+#[derive(Clone, Copy)]
+struct ClipPoint {
+    position: Vec2,
+    subject: FeatureId,
+    clip: FeatureId,
+}
+
+// Sutherland-Hodgman: what's left of the subject polygon inside the counter-clockwise `clip` one
+fn clip_polygon(subject: &FlatFeature, clip: &FlatFeature) -> Vec<ClipPoint> {
+    let mut points: Vec<ClipPoint> = subject
+        .points
+        .iter()
+        .map(|(position, _, id)| ClipPoint { position: *position, subject: *id, clip: clip.id })
+        .collect();
+    for (i, (a, _, id_a)) in clip.points.iter().enumerate() {
+        let (b, _, id_b) = clip.points[(i + 1) % clip.points.len()];
+        let edge = FeatureId::edge(*id_a, id_b);
+        let side = |point: &ClipPoint| (b - *a).perp_dot(point.position - *a);
+        let input = std::mem::take(&mut points);
+        for (j, current) in input.iter().enumerate() {
+            let previous = &input[(j + input.len() - 1) % input.len()];
+            let (current_side, previous_side) = (side(current), side(previous));
+            if (current_side >= 0.0) != (previous_side >= 0.0) {
+                let t = previous_side / (previous_side - current_side);
+                points.push(ClipPoint {
+                    position: previous.position.lerp(current.position, t),
+                    subject: FeatureId::edge(previous.subject, current.subject),
+                    clip: edge,
+                });
+            }
+            if current_side >= 0.0 {
+                points.push(*current);
+            }
+        }
+        if points.is_empty() {
+            break;
+        }
+    }
+    points
+}
+
+// Clips the subject segment to the stretch it shares with the parallel clip segment
+fn clip_segment(subject: &FlatFeature, clip: &FlatFeature) -> Vec<ClipPoint> {
+    let (a, b) = (clip.points[0], clip.points[1]);
+    let axis = (b.0 - a.0).normalize_or_zero();
+    let (low, high) = (a.0.dot(axis), b.0.dot(axis));
+    let (start, end) = (subject.points[0], subject.points[1]);
+    let (start_at, end_at) = (start.0.dot(axis), end.0.dot(axis));
+    let mut points = Vec::new();
+    for (point, at, other_at) in [(start, start_at, end_at), (end, end_at, start_at)] {
+        if (low..=high).contains(&at) {
+            points.push(ClipPoint { position: point.0, subject: point.2, clip: clip.id });
+        } else {
+            // Moved along the subject onto the nearer end of the clip segment
+            let (bound, id) = if at < low { (low, a.2) } else { (high, b.2) };
+            if (other_at - bound) * (at - bound) < 0.0 {
+                let t = (bound - at) / (other_at - at);
+                let other = if at == start_at { end.0 } else { start.0 };
+                points.push(ClipPoint { position: point.0.lerp(other, t), subject: subject.id, clip: id });
+            }
+        }
+    }
+    points
+}
+
+// Keeps the deepest point and the three that span the largest area with it
+fn reduce_manifold(points: &mut Vec<RawContact>) {
+    if points.len() <= MAX_MANIFOLD_POINTS {
+        return;
+    }
+    let deepest = (0..points.len()).min_by(|&i, &j| points[i].dist.total_cmp(&points[j].dist)).unwrap();
+    let first = points[deepest].point1;
+    let furthest = |score: &dyn Fn(Vec3) -> f32| {
+        (0..points.len()).max_by(|&i, &j| score(points[i].point1).total_cmp(&score(points[j].point1))).unwrap()
+    };
+    let second = points[furthest(&|point| point.distance_squared(first))].point1;
+    let third = points[furthest(&|point| (point - first).cross(point - second).length_squared())].point1;
+    let area = |a: Vec3, b: Vec3, c: Vec3| (b - a).cross(c - a).length();
+    let fourth = furthest(&|point| area(first, second, point) + area(second, third, point) + area(third, first, point));
+
+    let keep: Vec<usize> = [
+        deepest,
+        points.iter().position(|point| point.point1 == second).unwrap(),
+        points.iter().position(|point| point.point1 == third).unwrap(),
+        fourth,
+    ]
+    .into_iter()
+    .fold(Vec::new(), |mut keep, index| {
+        if !keep.contains(&index) {
+            keep.push(index);
+        }
+        keep
+    });
+    let mut index = 0;
+    points.retain(|_| {
+        index += 1;
+        keep.contains(&(index - 1))
+    });
+}
+
+// Contact normal and points between two convex shapes, the second at `pos12` in the frame of
+// the first. None when they are further apart than `prediction`.
+fn convex_contacts(shape1: &ConvexShape, shape2: &ConvexShape, pos12: &Pose, prediction: f32) -> Option<(Vec3, Vec<RawContact>)> {
+    let rotation21 = pos12.rotation.inverse();
+    let core_support = |direction: Vec3| {
+        SupportPoint::new(shape1.core.support(direction), pos12.transform_point(shape2.core.support(rotation21 * -direction)))
+    };
+    let radii = shape1.radius + shape2.radius;
+
+    let cores = gjk(&core_support, -pos12.translation);
+    let (normal, witness1, witness2) = if cores.distance > GJK_EPSILON {
+        if cores.distance > radii + prediction {
+            return None;
+        }
+        let normal = (cores.b - cores.a) / cores.distance;
+        (normal, cores.a + normal * shape1.radius, cores.b - normal * shape2.radius)
+    } else {
+        // The cores touch: the full shapes, radius included, tell how deep they are
+        let support = |direction: Vec3| {
+            SupportPoint::new(shape1.support(direction), pos12.transform_point(shape2.support(rotation21 * -direction)))
+        };
+        let penetration = epa(cores.simplex, &support)?;
+        (penetration.normal, penetration.a, penetration.b)
+    };
+    let witness_dist = (witness2 - witness1).dot(normal);
+
+    // The features facing each other, flattened onto the contact plane
+    let tangents = normal.any_orthonormal_pair();
+    let feature1 = shape1.core.feature(normal);
+    let mut feature2 = shape2.core.feature(rotation21 * -normal);
+    for (vertex, _) in &mut feature2.vertices {
+        *vertex = pos12.transform_point(*vertex);
+    }
+    let flat1 = FlatFeature::new(&feature1, tangents, normal);
+    let flat2 = FlatFeature::new(&feature2, tangents, normal);
+
+    // The first shape's feature clips the second's, unless the second is the bigger one
+    let clipped = match (flat1.points.len(), flat2.points.len()) {
+        (1, _) | (_, 1) => Vec::new(),
+        (n1, n2) if n1 >= 3 && n2 >= n1.min(3) => clip_polygon(&flat2, &flat1),
+        (_, n2) if n2 >= 3 => swap(clip_polygon(&flat1, &flat2)),
+        (n1, _) if n1 >= 3 => clip_polygon(&flat2, &flat1),
+        _ => {
+            let direction1 = flat1.points[1].0 - flat1.points[0].0;
+            let direction2 = flat2.points[1].0 - flat2.points[0].0;
+            if direction1.perp_dot(direction2).abs() <= FEATURE_TOLERANCE * direction1.length() * direction2.length() {
+                clip_segment(&flat2, &flat1)
+            } else {
+                Vec::new()
+            }
+        }
+    };
+
+    let mut points: Vec<RawContact> = clipped
+        .iter()
+        .filter_map(|point| {
+            let ((position1, height1), (position2, height2)) = (flat1.surface(point.position), flat2.surface(point.position));
+            let dist = height2 - height1 - radii;
+            if dist > prediction {
+                return None;
+            }
+            let base = |position: Vec2| tangents.0 * position.x + tangents.1 * position.y;
+            Some(RawContact {
+                point1: base(position1) + normal * (height1 + shape1.radius),
+                point2: base(position2) + normal * (height2 - shape2.radius),
+                dist,
+                fid1: point.clip,
+                fid2: point.subject,
+            })
+        })
+        .collect();
+    // Dropping the duplicates clipping leaves at corners
+    points.dedup_by(|a, b| a.point1.distance_squared(b.point1) <= 1e-10);
+
+    // Features tilted against the normal can miss the deepest point, which the witnesses have
+    let deepest = points.iter().map(|point| point.dist).fold(f32::INFINITY, f32::min);
+    if deepest > witness_dist + EPA_TOLERANCE.max(GJK_EPSILON) * 10.0 {
+        if witness_dist > prediction {
+            return None;
+        }
+        points.push(RawContact { point1: witness1, point2: witness2, dist: witness_dist, fid1: feature1.id, fid2: feature2.id });
+    }
+    reduce_manifold(&mut points);
+    Some((normal, points))
+}
+
+// Clip results with the roles of the two features swapped back
+fn swap(points: Vec<ClipPoint>) -> Vec<ClipPoint> {
+    points.into_iter().map(|point| ClipPoint { subject: point.clip, clip: point.subject, ..point }).collect()
+}
+
+#[derive(Clone, Debug)]
+pub struct ContactPoint {
+    // On the surface of each shape, in that shape's frame
+    pub local1: Vec3,
+    pub local2: Vec3,
+    // Negative when the shapes overlap
+    pub dist: f32,
+    pub fid1: FeatureId,
+    pub fid2: FeatureId,
+    // Left by the solver, and kept for as long as the same features stay in contact
+    pub normal_impulse: f32,
+    pub tangent_impulse: [f32; 2],
+}
+
+// The contact between one part of each shape
+#[derive(Clone, Debug)]
+pub struct ContactManifold {
+    pub subshape1: u32,
+    pub subshape2: u32,
+    // From the first shape towards the second, in the first shape's frame
+    pub local_normal1: Vec3,
+    pub points: Vec<ContactPoint>,
+}
+
+// Replaces `manifolds` with the contacts between two shapes, the second at `pos12` in the frame
+// of the first. Points on the same features as before keep their impulses.
+pub fn update_contact_manifolds(pos12: &Pose, shape1: &Shape, shape2: &Shape, prediction: f32, manifolds: &mut Vec<ContactManifold>) {
+    let previous = std::mem::take(manifolds);
+    let pos21 = pos12.inverse();
+    let near1 = shape2.aabb(pos12).loosened(prediction);
+
+    shape1.parts_near(&near1, &mut |subshape1, part1, convex1| {
+        let near2 = convex1.aabb(&(pos21 * *part1)).loosened(prediction);
+        shape2.parts_near(&near2, &mut |subshape2, part2, convex2| {
+            // Both triangle soups and pairs of them have no inside, and are never dynamic
+            let part12 = part1.inv_mul(&(*pos12 * *part2));
+            let Some((normal, contacts)) = convex_contacts(convex1, convex2, &part12, prediction) else {
+                return;
+            };
+            let old = previous.iter().find(|old| old.subshape1 == subshape1 && old.subshape2 == subshape2);
+            let part21 = part12.inverse();
+            let points = contacts
+                .into_iter()
+                .map(|contact| {
+                    let (normal_impulse, tangent_impulse) = old
+                        .and_then(|old| old.points.iter().find(|old| old.fid1 == contact.fid1 && old.fid2 == contact.fid2))
+                        .map_or((0.0, [0.0; 2]), |old| (old.normal_impulse, old.tangent_impulse));
+                    ContactPoint {
+                        local1: part1.transform_point(contact.point1),
+                        local2: part2.transform_point(part21.transform_point(contact.point2)),
+                        dist: contact.dist,
+                        fid1: contact.fid1,
+                        fid2: contact.fid2,
+                        normal_impulse,
+                        tangent_impulse,
+                    }
+                })
+                .collect();
+            manifolds.push(ContactManifold { subshape1, subshape2, local_normal1: part1.rotation * normal, points });
+        });
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy_rapier3d::parry::query;
+    use bevy_rapier3d::rapier::prelude::Isometry;
+
+    use crate::mesh_import::{mesh_collider, MeshColliderKind};
+    use crate::physics::random::Random;
+    use crate::primitives::Primitive;
+    use crate::ui::ShapeType;
+
+    // Every shape the editor creates: the primitives at their default size and a mesh as each
+    // kind of imported collider
+    fn colliders() -> Vec<(String, SharedShape)> {
+        let mut colliders: Vec<(String, SharedShape)> = ShapeType::PRIMITIVES
+            .into_iter()
+            .map(|shape_type| {
+                let primitive = Primitive::unit(shape_type).expect("primitives have a default size");
+                (shape_type.label().to_string(), primitive.collider().raw)
+            })
+            .collect();
+        let mesh = Primitive::unit(ShapeType::Wedge).unwrap().mesh();
+        for (name, kind) in [
+            ("Convex hull", MeshColliderKind::ConvexHull),
+            ("Trimesh", MeshColliderKind::TriMesh),
+            ("Convex decomposition", MeshColliderKind::ConvexDecomposition),
+        ] {
+            colliders.push((name.to_string(), mesh_collider(&mesh, kind).expect("the wedge mesh is valid").raw));
+        }
+        colliders
+    }
+
+    fn isometry(pose: &Pose) -> Isometry<f32> {
+        Isometry::from_parts(pose.translation.into(), pose.rotation.into())
+    }
+
+    // How far a point is outside one part of a collider, and how far inside behind the part's
+    // supporting plane along `direction`
+    fn off_surface(collider: &SharedShape, subshape: u32, point: Vec3, direction: Vec3) -> (f32, f32) {
+        let triangle;
+        let (pose, part): (Isometry<f32>, &dyn ParryShape) = match collider.as_typed_shape() {
+            TypedShape::Compound(compound) => {
+                let (pose, part) = &compound.shapes()[subshape as usize];
+                (*pose, &**part)
+            }
+            TypedShape::TriMesh(mesh) => {
+                triangle = mesh.triangle(subshape);
+                (Isometry::identity(), &triangle)
+            }
+            _ => (Isometry::identity(), &**collider),
+        };
+        let local = pose.inverse_transform_point(&point.into());
+        let local_direction = pose.inverse_transform_vector(&direction.into());
+        // Rapier's projection onto convex hulls is unreliable for points on their edges, the
+        // planes of the faces are exact
+        let outside = match part.as_convex_polyhedron() {
+            Some(hull) => hull
+                .faces()
+                .iter()
+                .map(|plane| {
+                    let vertex = hull.points()[hull.vertices_adj_to_face()[plane.first_vertex_or_edge as usize] as usize];
+                    plane.normal.dot(&(local - vertex))
+                })
+                .fold(0.0, f32::max),
+            None => part.distance_to_local_point(&local, true),
+        };
+        let support = part.as_support_map().expect("every part is convex").local_support_point(&local_direction);
+        let behind = Vec3::from(support - local).dot(Vec3::from(local_direction).normalize());
+        (outside, behind)
+    }
+
+    // The parts of a collider with their pose, one triangle at a time for meshes
+    fn parts(collider: &SharedShape) -> Vec<(Isometry<f32>, Box<dyn ParryShape>)> {
+        match collider.as_typed_shape() {
+            TypedShape::Compound(compound) => compound.shapes().iter().map(|(pose, part)| (*pose, part.clone_box())).collect(),
+            TypedShape::TriMesh(mesh) => {
+                (0..mesh.num_triangles() as u32).map(|i| (Isometry::identity(), mesh.triangle(i).clone_box())).collect()
+            }
+            _ => vec![(Isometry::identity(), collider.clone_box())],
+        }
+    }
+
+    struct RapierContact {
+        dist: f32,
+        normal: Vec3,
+    }
+
+    // Rapier's deepest contact between any two parts. Its own query on compound shapes stops
+    // at the first part pair in contact rather than the deepest.
+    fn rapier_contact(pose1: &Pose, collider1: &SharedShape, pose2: &Pose, collider2: &SharedShape, prediction: f32) -> Option<RapierContact> {
+        let mut deepest: Option<RapierContact> = None;
+        for (part1, shape1) in parts(collider1) {
+            for (part2, shape2) in parts(collider2) {
+                let contact = query::contact(&(isometry(pose1) * part1), &*shape1, &(isometry(pose2) * part2), &*shape2, prediction)
+                    .expect("rapier supports every pair of parts");
+                if let Some(contact) = contact.filter(|contact| deepest.as_ref().is_none_or(|deepest| contact.dist < deepest.dist)) {
+                    let normal = Vec3::from(contact.normal1.into_inner());
+                    deepest = Some(RapierContact { dist: contact.dist, normal });
+                }
+            }
+        }
+        deepest
+    }
+
+    fn manifolds(pos12: &Pose, shape1: &Shape, shape2: &Shape, prediction: f32) -> Vec<ContactManifold> {
+        let mut manifolds = Vec::new();
+        update_contact_manifolds(pos12, shape1, shape2, prediction, &mut manifolds);
+        manifolds
+    }
+
+    fn deepest(manifolds: &[ContactManifold]) -> Option<(&ContactManifold, &ContactPoint)> {
+        manifolds
+            .iter()
+            .flat_map(|manifold| manifold.points.iter().map(move |point| (manifold, point)))
+            .min_by(|a, b| a.1.dist.total_cmp(&b.1.dist))
+    }
+
+    #[test]
+    fn spheres_distance_is_exact() {
+        let ball = Shape::from_collider(&SharedShape::ball(0.5)).unwrap();
+        for (offset, expected) in [(Vec3::new(2.0, 0.0, 0.0), 1.0), (Vec3::new(0.3, 0.4, 0.0), -0.5), (Vec3::new(0.0, 0.0, 1.0), 0.0)] {
+            let pose = Pose::new(offset, Quat::IDENTITY);
+            let found = manifolds(&pose, &ball, &ball, 2.0);
+            let (manifold, point) = deepest(&found).expect("the balls are within prediction");
+            assert!((point.dist - expected).abs() < 1e-5, "dist {}, expected {expected}", point.dist);
+            assert!(manifold.local_normal1.abs_diff_eq(offset.normalize(), 1e-5));
+        }
+    }
+
+    // Deep overlaps, where the depth and normal are known exactly
+    #[test]
+    fn deep_overlaps_have_analytic_depths() {
+        let ball = Shape::from_collider(&SharedShape::ball(0.5)).unwrap();
+        let cube = Shape::from_collider(&SharedShape::cuboid(0.5, 0.5, 0.5)).unwrap();
+        let turned = Quat::from_rotation_y(0.6);
+        for (shape, pose, expected, normal) in [
+            // Spheres: the distance between centers less both radii, along the line between them
+            (&ball, Pose::new(Vec3::new(0.1, 0.05, 0.0), Quat::IDENTITY), 0.0125f32.sqrt() - 1.0, Vec3::new(2.0, 1.0, 0.0).normalize()),
+            (&ball, Pose::new(Vec3::new(0.0, -0.3, 0.4), turned), -0.5, Vec3::new(0.0, -0.6, 0.8)),
+            // Cubes: the least overlap of the face axes, out of the face it's along
+            (&cube, Pose::new(Vec3::new(0.3, 0.1, 0.05), Quat::IDENTITY), -0.7, Vec3::X),
+            (&cube, Pose::new(Vec3::new(0.05, -0.4, 0.1), turned), -0.6, -Vec3::Y),
+            (&cube, Pose::new(Vec3::new(0.0, 0.0, -0.8), Quat::IDENTITY), -0.2, -Vec3::Z),
+        ] {
+            let found = manifolds(&pose, shape, shape, 0.02);
+            let (manifold, point) = deepest(&found).expect("the shapes overlap");
+            assert!((point.dist - expected).abs() < 1e-4, "{pose:?}: dist {}, expected {expected}", point.dist);
+            assert!(manifold.local_normal1.dot(normal) > 0.9999, "{pose:?}: normal {}, expected {normal}", manifold.local_normal1);
+        }
+    }
+
+    // Random poses of every pair of shapes, close enough to touch or overlap. There are contacts
+    // exactly when rapier has one, the deepest point agrees with it in depth and normal, and
+    // every point lies on both surfaces.
+    #[test]
+    fn contacts_match_rapier() {
+        let colliders = colliders();
+        let shapes: Vec<Shape> = colliders
+            .iter()
+            .map(|(name, collider)| Shape::from_collider(collider).unwrap_or_else(|| panic!("{name} has no narrow phase shape")))
+            .collect();
+        let mut random = Random(0x9e37_79b9_7f4a_7c15);
+        let prediction = 0.1;
+        let (mut checked, mut worst_depth, mut worst_normal) = (0, 0.0f32, 1.0f32);
+
+        for (i, (name1, collider1)) in colliders.iter().enumerate() {
+            for (j, (name2, collider2)) in colliders.iter().enumerate() {
+                // Rapier has no contacts between two triangle meshes, and nor do fixed bodies
+                if collider1.as_trimesh().is_some() && collider2.as_trimesh().is_some() {
+                    continue;
+                }
+                for _ in 0..24 {
+                    let pose1 = Pose::new(random.signed(), random.rotation());
+                    let pose2 = Pose::new(pose1.translation + random.signed() * 0.8, random.rotation());
+                    let expected = rapier_contact(&pose1, collider1, &pose2, collider2, prediction);
+                    let pos12 = pose1.inv_mul(&pose2);
+                    let found = manifolds(&pos12, &shapes[i], &shapes[j], prediction);
+
+                    let Some(expected) = expected else {
+                        assert!(deepest(&found).is_none(), "{name1} and {name2}: contact where rapier has none");
+                        continue;
+                    };
+                    let (manifold, point) = deepest(&found)
+                        .unwrap_or_else(|| panic!("{name1} and {name2}: no contact, rapier has {}", expected.dist));
+                    let error = (point.dist - expected.dist).abs();
+                    assert!(error < 0.005, "{name1} and {name2}: dist {}, rapier {}", point.dist, expected.dist);
+                    let normal = pose1.rotation * manifold.local_normal1;
+                    let agreement = normal.dot(expected.normal);
+                    assert!(agreement > 0.99, "{name1} and {name2}: normal {normal}, rapier {}", expected.normal);
+                    worst_depth = worst_depth.max(error);
+                    worst_normal = worst_normal.min(agreement);
+                    checked += 1;
+
+                    for manifold in &found {
+                        for point in &manifold.points {
+                            let normal2 = pos12.rotation.inverse() * -manifold.local_normal1;
+                            let off1 = off_surface(collider1, manifold.subshape1, point.local1, manifold.local_normal1);
+                            let off2 = off_surface(collider2, manifold.subshape2, point.local2, normal2);
+                            // Faces within the feature tolerance of the normal are tilted by up to that much
+                            for (outside, behind) in [off1, off2] {
+                                assert!(outside < 0.02, "{name1} and {name2}: point {outside} outside the surface");
+                                assert!(behind < FEATURE_TOLERANCE * 1.8, "{name1} and {name2}: point {behind} inside the surface");
+                            }
+                            // The gap between the two points is along the normal, give or take
+                            // nearly parallel edges not quite meeting
+                            let gap = pos12.transform_point(point.local2) - point.local1;
+                            let along = gap.dot(manifold.local_normal1);
+                            assert!((along - point.dist).abs() < 1e-4, "{name1} and {name2}: gap {along}, dist {}", point.dist);
+                            assert!((gap - manifold.local_normal1 * along).length() < 0.1, "{name1} and {name2}: gap {gap}");
+                        }
+                    }
+                }
+            }
+        }
+        println!("{checked} contacts checked, largest depth error {worst_depth}, smallest normal agreement {worst_normal}");
+    }
+
+    #[test]
+    fn resting_shapes_get_full_manifolds() {
+        let ground = Shape::from_collider(&SharedShape::cuboid(5.0, 0.5, 5.0)).unwrap();
+        let lying = Quat::from_rotation_z(std::f32::consts::FRAC_PI_2);
+        for (collider, rotation, height, count) in [
+            (SharedShape::cuboid(0.5, 0.5, 0.5), Quat::from_rotation_y(0.3), 0.5, 4),
+            (SharedShape::round_cuboid(0.4, 0.4, 0.4, 0.1), Quat::IDENTITY, 0.5, 4),
+            (SharedShape::cylinder(0.5, 0.5), Quat::IDENTITY, 0.5, 4),
+            (SharedShape::cylinder(0.5, 0.5), lying, 0.5, 2),
+            (SharedShape::capsule_x(0.5, 0.25), Quat::IDENTITY, 0.25, 2),
+            (SharedShape::cone(0.5, 0.5), Quat::IDENTITY, 0.5, 4),
+            (SharedShape::ball(0.5), Quat::IDENTITY, 0.5, 1),
+        ] {
+            let shape = Shape::from_collider(&collider).unwrap();
+            // Sunk in by a millimetre
+            let pose = Pose::new(Vec3::Y * (0.5 + height - 0.001), rotation);
+            let found = manifolds(&pose, &ground, &shape, 0.02);
+            let points: Vec<&ContactPoint> = found.iter().flat_map(|manifold| &manifold.points).collect();
+            assert_eq!(points.len(), count, "{:?}", collider.shape_type());
+            for point in points {
+                assert!((point.dist + 0.001).abs() < 1e-4, "{:?}: dist {}", collider.shape_type(), point.dist);
+            }
+            assert!(found[0].local_normal1.abs_diff_eq(Vec3::Y, 1e-4));
+        }
+    }
+
+    #[test]
+    fn impulses_persist_on_the_same_features() {
+        let ground = Shape::from_collider(&SharedShape::cuboid(5.0, 0.5, 5.0)).unwrap();
+        let cube = Shape::from_collider(&SharedShape::cuboid(0.5, 0.5, 0.5)).unwrap();
+        let mut pose = Pose::new(Vec3::Y * 0.999, Quat::IDENTITY);
+        let mut found = Vec::new();
+        update_contact_manifolds(&pose, &ground, &cube, 0.02, &mut found);
+        for (i, point) in found[0].points.iter_mut().enumerate() {
+            point.normal_impulse = i as f32 + 1.0;
+            point.tangent_impulse = [i as f32, -(i as f32)];
+        }
+        let before: Vec<(FeatureId, FeatureId, f32)> =
+            found[0].points.iter().map(|point| (point.fid1, point.fid2, point.normal_impulse)).collect();
+
+        // A small slide keeps the same corners on the same face
+        pose.translation += Vec3::new(0.01, 0.0005, -0.02);
+        update_contact_manifolds(&pose, &ground, &cube, 0.02, &mut found);
+        assert_eq!(found[0].points.len(), before.len());
+        for point in &found[0].points {
+            let old = before.iter().find(|old| old.0 == point.fid1 && old.1 == point.fid2).expect("the same corner");
+            assert_eq!(point.normal_impulse, old.2);
+        }
+
+        // Tipped onto an edge, only the corners still touching keep theirs
+        pose.rotation = Quat::from_rotation_z(0.3);
+        pose.translation.y = 0.5 + 0.5 * (0.3f32.cos() + 0.3f32.sin()) - 0.001;
+        update_contact_manifolds(&pose, &ground, &cube, 0.02, &mut found);
+        assert_eq!(found[0].points.len(), 2);
+        for point in &found[0].points {
+            let old = before.iter().find(|old| old.0 == point.fid1 && old.1 == point.fid2);
+            assert_eq!(point.normal_impulse, old.map_or(0.0, |old| old.2));
+        }
+    }
+}
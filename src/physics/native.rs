@@ -0,0 +1,1612 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use bevy_rapier3d::parry::bounding_volume::BoundingVolume;
+use bevy_rapier3d::parry::query::Ray as RawRay;
+use bevy_rapier3d::parry::shape::SharedShape;
+use bevy_rapier3d::prelude::*;
+use bevy_rapier3d::rapier::prelude::Isometry;
+
+use crate::mass_properties::collider_mass_properties;
+
+use super::broadphase::{Aabb, BroadPhase, BroadPhaseKind, DebugBox};
+use super::integrators::Kinematics;
+use super::narrowphase::{update_contact_manifolds, ContactManifold, Pose, Shape};
+use super::{BodyDesc, BodyState, JointDesc, PhysicsBackend, StepParams};
+
+// Contacts are created this far before the shapes touch, so fast bodies slow down in time
+const PREDICTION: f32 = 0.02;
+// Penetration left alone, so resting contacts don't jitter
+const ALLOWED_PENETRATION: f32 = 0.005;
+// Share of the remaining penetration removed per stabilization iteration
+const POSITION_CORRECTION: f32 = 0.2;
+// Joints have no slop to jitter in, so all of their error goes at once, like rapier's `joint_erp`
+const JOINT_CORRECTION: f32 = 1.0;
+// Slower impacts don't bounce, otherwise resting bodies never settle
+const RESTITUTION_THRESHOLD: f32 = 0.5;
+// How long a whole island has to stay below its bodies' thresholds before it sleeps, as in rapier
+const TIME_UNTIL_SLEEP: f32 = 2.0;
+
+struct NativeCollider {
+    shape: SharedShape,
+    // None for shapes the narrow phase doesn't handle, which then don't collide
+    contact_shape: Option<Arc<Shape>>,
+    friction: Friction,
+    restitution: Restitution,
+    sensor: bool,
+}
+
+// Whether a body sleeps, with rapier's rule: below both thresholds for long enough. Negative
+// thresholds keep the body awake.
+#[derive(Clone, Copy)]
+struct Activation {
+    linear_threshold: f32,
+    angular_threshold: f32,
+    time_since_can_sleep: f32,
+    sleeping: bool,
+}
+
+impl Default for Activation {
+    fn default() -> Self {
+        let sleeping = Sleeping::default();
+        Self {
+            linear_threshold: sleeping.linear_threshold,
+            angular_threshold: sleeping.angular_threshold,
+            time_since_can_sleep: 0.0,
+            sleeping: false,
+        }
+    }
+}
+
+impl Activation {
+    fn update(&mut self, linvel: Vec3, angvel: Vec3, dt: f32) {
+        let slow = |velocity: Vec3, threshold: f32| velocity.length_squared() < threshold * threshold.abs();
+        if slow(linvel, self.linear_threshold) && slow(angvel, self.angular_threshold) {
+            self.time_since_can_sleep += dt;
+        } else {
+            self.time_since_can_sleep = 0.0;
+        }
+    }
+
+    fn wake_up(&mut self) {
+        self.sleeping = false;
+        self.time_since_can_sleep = 0.0;
+    }
+}
+
+struct NativeBody {
+    entity: Entity,
+    body_type: RigidBody,
+    // Pose of the body origin, which is where the entity is
+    position: Vec3,
+    rotation: Quat,
+    // Velocities of the center of mass
+    linvel: Vec3,
+    angvel: Vec3,
+    force: Vec3,
+    torque: Vec3,
+    linear_damping: f32,
+    angular_damping: f32,
+    inv_mass: f32,
+    local_center: Vec3,
+    // Inverse principal inertia, in the frame rotated by `inertia_frame`
+    inv_inertia: Vec3,
+    inertia_frame: Quat,
+    // As derived from the collider, also for the bodies that ignore it
+    mass_properties: MassProperties,
+    collider: Option<NativeCollider>,
+    activation: Activation,
+}
+
+impl NativeBody {
+    fn new(entity: Entity, desc: &BodyDesc) -> Self {
+        let mut body = Self {
+            entity,
+            body_type: desc.body_type,
+            position: desc.state.position,
+            rotation: desc.state.rotation,
+            linvel: desc.state.linvel,
+            angvel: desc.state.angvel,
+            force: desc.force.force,
+            torque: desc.force.torque,
+            linear_damping: desc.damping.linear_damping,
+            angular_damping: desc.damping.angular_damping,
+            inv_mass: 0.0,
+            local_center: Vec3::ZERO,
+            inv_inertia: Vec3::ZERO,
+            inertia_frame: Quat::IDENTITY,
+            mass_properties: desc.collider.as_ref()
+                .map(|collider| collider_mass_properties(&collider.shape, &collider.mass))
+                .unwrap_or_default(),
+            collider: desc.collider.as_ref().map(|collider| NativeCollider {
+                shape: collider.shape.raw.clone(),
+                contact_shape: Shape::from_collider(&collider.shape.raw).map(Arc::new),
+                friction: collider.friction,
+                restitution: collider.restitution,
+                sensor: collider.sensor,
+            }),
+            activation: Activation::default(),
+        };
+
+        // Only dynamic bodies respond to forces and contacts, the rest act as if infinitely heavy
+        if desc.body_type == RigidBody::Dynamic && desc.collider.is_some() {
+            let props = body.mass_properties;
+            let invert = |value: f32| if value > f32::EPSILON { 1.0 / value } else { 0.0 };
+            body.inv_mass = invert(props.mass);
+            body.local_center = props.local_center_of_mass;
+            body.inv_inertia = Vec3::new(
+                invert(props.principal_inertia.x),
+                invert(props.principal_inertia.y),
+                invert(props.principal_inertia.z),
+            );
+            body.inertia_frame = props.principal_inertia_local_frame;
+        }
+        body
+    }
+
+    // Dynamic bodies that aren't asleep and kinematic ones, which the editor may be moving
+    fn is_active(&self) -> bool {
+        match self.body_type {
+            RigidBody::Dynamic => !self.activation.sleeping,
+            RigidBody::KinematicPositionBased | RigidBody::KinematicVelocityBased => true,
+            RigidBody::Fixed => false,
+        }
+    }
+
+    fn sleep(&mut self) {
+        self.activation.sleeping = true;
+        self.activation.time_since_can_sleep = TIME_UNTIL_SLEEP;
+        self.linvel = Vec3::ZERO;
+        self.angvel = Vec3::ZERO;
+    }
+
+    fn center(&self) -> Vec3 {
+        self.position + self.rotation * self.local_center
+    }
+
+    fn pose(&self) -> Pose {
+        Pose::new(self.position, self.rotation)
+    }
+
+    fn isometry(&self) -> Isometry<f32> {
+        Isometry::from_parts(self.position.into(), self.rotation.into())
+    }
+
+    // Bounds of the collider, grown by the distance contacts are predicted at
+    fn aabb(&self) -> Option<Aabb> {
+        let aabb = self.collider.as_ref()?.shape.compute_aabb(&self.isometry()).loosened(PREDICTION);
+        Some(Aabb::new(aabb.mins.into(), aabb.maxs.into()))
+    }
+
+    fn inv_inertia_world(&self) -> Mat3 {
+        let frame = Mat3::from_quat(self.rotation * self.inertia_frame);
+        frame * Mat3::from_diagonal(self.inv_inertia) * frame.transpose()
+    }
+
+    // Velocity of the point `r` away from the center of mass
+    fn point_velocity(&self, r: Vec3) -> Vec3 {
+        self.linvel + self.angvel.cross(r)
+    }
+
+    fn apply_impulse(&mut self, impulse: Vec3, r: Vec3, inv_inertia: &Mat3) {
+        self.linvel += impulse * self.inv_mass;
+        self.angvel += *inv_inertia * r.cross(impulse);
+    }
+
+    // Moves the center of mass and turns around it, keeping `position` on the body origin
+    fn displace(&mut self, translation: Vec3, rotation: Vec3) {
+        let center = self.center() + translation;
+        self.rotation = (Quat::from_scaled_axis(rotation) * self.rotation).normalize();
+        self.position = center - self.rotation * self.local_center;
+    }
+}
+
+struct ContactConstraint {
+    body1: usize,
+    body2: usize,
+    // Where the point is in `manifolds`, to keep its impulses for the next step
+    pair: (Entity, Entity),
+    manifold: usize,
+    point: usize,
+    // Contact points in the body frames and the normal in the frame of body 1, for stabilization
+    local1: Vec3,
+    local2: Vec3,
+    local_normal: Vec3,
+    normal: Vec3,
+    tangents: [Vec3; 2],
+    r1: Vec3,
+    r2: Vec3,
+    friction: f32,
+    // Normal velocity the solver aims for, negative for contacts that are still closing in
+    target_velocity: f32,
+    normal_mass: f32,
+    tangent_mass: [f32; 2],
+    normal_impulse: f32,
+    tangent_impulse: [f32; 2],
+}
+
+// A joint's frames in the frames of its bodies, with anchors relative to the body origins
+struct NativeJoint {
+    body1: Entity,
+    body2: Entity,
+    anchor1: Vec3,
+    anchor2: Vec3,
+    basis1: Quat,
+    basis2: Quat,
+    // Bits of rapier's `JointAxesMask`: moving along X, Y and Z of frame 1, then turning around them
+    locked: u8,
+    limits: Vec<JointLimit>,
+    // Linear and angular impulses in frame 1 from the last step, to warm start from
+    impulses: [Vec3; 2],
+}
+
+// Which measure of a joint a limit keeps in range
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum LimitedAxis {
+    // Distance along an axis of frame 1
+    Linear(usize),
+    // Distance between the anchors, for rapier's coupled linear axes as used by ropes
+    Distance,
+    // Angle around an axis of frame 1
+    Angular(usize),
+}
+
+struct JointLimit {
+    axis: LimitedAxis,
+    range: [f32; 2],
+    // Impulses holding the lower and upper end from the last step
+    impulses: [f32; 2],
+}
+
+impl NativeJoint {
+    fn linear_locks(&self) -> [bool; 3] {
+        [0, 1, 2].map(|axis| self.locked & (1 << axis) != 0)
+    }
+
+    fn angular_locks(&self) -> [bool; 3] {
+        [3, 4, 5].map(|axis| self.locked & (1 << axis) != 0)
+    }
+
+    // Where linear limits push the bodies, relative to their centers. Bodies whose rotations are
+    // locked together are pushed through their centers, pushing at the anchors would only turn
+    // them against the lock.
+    fn limit_arms(&self, point1: Vec3, point2: Vec3, body1: &NativeBody, body2: &NativeBody) -> (Vec3, Vec3) {
+        if self.angular_locks() == [true; 3] {
+            (Vec3::ZERO, Vec3::ZERO)
+        } else {
+            (point1 - body1.center(), point2 - body2.center())
+        }
+    }
+
+    // Rapier's limits on the axes it leaves free
+    fn limits(raw: &bevy_rapier3d::rapier::dynamics::GenericJoint) -> Vec<JointLimit> {
+        let limited = raw.limit_axes.bits() & !raw.locked_axes.bits();
+        let coupled = raw.coupled_axes.bits();
+        let mut limits = Vec::new();
+        for index in (0..6).filter(|index| limited & (1 << index) != 0) {
+            let axis = match index {
+                _ if coupled & (1 << index) != 0 => LimitedAxis::Distance,
+                0..=2 => LimitedAxis::Linear(index),
+                _ => LimitedAxis::Angular(index - 3),
+            };
+            // Coupled axes share the limit of the first one
+            if axis == LimitedAxis::Distance && limits.iter().any(|limit: &JointLimit| limit.axis == axis) {
+                continue;
+            }
+            let range = raw.limits[index];
+            limits.push(JointLimit { axis, range: [range.min, range.max], impulses: [0.0; 2] });
+        }
+        limits
+    }
+
+    // Where a limited measure is now, and the world direction it grows along
+    fn measure(&self, axis: LimitedAxis, body1: &NativeBody, body2: &NativeBody) -> (f32, Vec3) {
+        let (point1, point2, frame1, frame2) = self.frames(body1, body2);
+        match axis {
+            LimitedAxis::Linear(index) => {
+                let direction = frame1 * Vec3::AXES[index];
+                ((point2 - point1).dot(direction), direction)
+            }
+            LimitedAxis::Distance => {
+                let offset = point2 - point1;
+                (offset.length(), offset.normalize_or_zero())
+            }
+            LimitedAxis::Angular(index) => {
+                let relative = frame1.inverse() * frame2;
+                let relative = if relative.w < 0.0 { -relative } else { relative };
+                (2.0 * relative.xyz()[index].atan2(relative.w), frame1 * Vec3::AXES[index])
+            }
+        }
+    }
+
+    // Anchors and frame rotations where the bodies are now
+    fn frames(&self, body1: &NativeBody, body2: &NativeBody) -> (Vec3, Vec3, Quat, Quat) {
+        (
+            body1.position + body1.rotation * self.anchor1,
+            body2.position + body2.rotation * self.anchor2,
+            body1.rotation * self.basis1,
+            body2.rotation * self.basis2,
+        )
+    }
+}
+
+struct JointConstraint {
+    joint: Entity,
+    body1: usize,
+    body2: usize,
+    r1: Vec3,
+    r2: Vec3,
+    // Axes of frame 1 as columns
+    frame: Mat3,
+    // Effective masses in frame 1, zero along the free axes
+    linear_mass: Mat3,
+    angular_mass: Mat3,
+    // Accumulated impulses in frame 1
+    linear_impulse: Vec3,
+    angular_impulse: Vec3,
+    // In the order of the joint's limits
+    limits: Vec<LimitConstraint>,
+}
+
+struct LimitConstraint {
+    angular: bool,
+    direction: Vec3,
+    r1: Vec3,
+    r2: Vec3,
+    mass: f32,
+    // Slowest and fastest change the limit allows this step. The ends may be reached but not
+    // passed, ends already passed are left to the stabilization like penetrating contacts.
+    velocity_range: [f32; 2],
+    // Accumulated, never negative at the lower end and never positive at the upper one
+    impulses: [f32; 2],
+}
+
+impl JointConstraint {
+    // `linear` and `angular` are in frame 1
+    fn apply(&self, body1: &mut NativeBody, body2: &mut NativeBody, linear: Vec3, angular: Vec3, inv_inertia1: &Mat3, inv_inertia2: &Mat3) {
+        let (linear, angular) = (self.frame * linear, self.frame * angular);
+        body1.apply_impulse(-linear, self.r1, inv_inertia1);
+        body2.apply_impulse(linear, self.r2, inv_inertia2);
+        body1.angvel -= *inv_inertia1 * angular;
+        body2.angvel += *inv_inertia2 * angular;
+    }
+
+    // `impulse` is along the limit's direction
+    fn apply_limit(&self, limit: &LimitConstraint, body1: &mut NativeBody, body2: &mut NativeBody, impulse: f32, inv_inertia1: &Mat3, inv_inertia2: &Mat3) {
+        let impulse = limit.direction * impulse;
+        if limit.angular {
+            body1.angvel -= *inv_inertia1 * impulse;
+            body2.angvel += *inv_inertia2 * impulse;
+        } else {
+            body1.apply_impulse(-impulse, limit.r1, inv_inertia1);
+            body2.apply_impulse(impulse, limit.r2, inv_inertia2);
+        }
+    }
+
+    // How fast the limit's measure changes
+    fn limit_velocity(&self, limit: &LimitConstraint, body1: &NativeBody, body2: &NativeBody) -> f32 {
+        if limit.angular {
+            (body2.angvel - body1.angvel).dot(limit.direction)
+        } else {
+            (body2.point_velocity(limit.r2) - body1.point_velocity(limit.r1)).dot(limit.direction)
+        }
+    }
+}
+
+fn angular_mass(inv_inertia1: &Mat3, inv_inertia2: &Mat3, direction: Vec3) -> f32 {
+    let inverse = direction.dot((*inv_inertia1 + *inv_inertia2) * direction);
+    if inverse > f32::EPSILON { 1.0 / inverse } else { 0.0 }
+}
+
+// A sequential impulse solver: forces are integrated with the chosen integrator, contact
+// impulses are solved iteratively on the velocities, then positions follow and leftover
+// penetration is pushed out directly.
+pub struct NativeBackend {
+    bodies: Vec<NativeBody>,
+    indices: HashMap<Entity, usize>,
+    // Every collider, sensors included so rays can hit them
+    broad_phase: Box<dyn BroadPhase<Entity>>,
+    broad_phase_kind: BroadPhaseKind,
+    // Overlapping colliders, in a fixed order so the solver runs the same way every time
+    pairs: BTreeSet<(Entity, Entity)>,
+    // Contacts of every pair from the last step, with the impulses the solver left on them
+    manifolds: HashMap<(Entity, Entity), Vec<ContactManifold>>,
+    // By joint entity, in a fixed order like the pairs
+    joints: BTreeMap<Entity, NativeJoint>,
+}
+
+impl Default for NativeBackend {
+    fn default() -> Self {
+        let broad_phase_kind = BroadPhaseKind::default();
+        Self {
+            bodies: Vec::new(),
+            indices: HashMap::default(),
+            broad_phase: broad_phase_kind.create(),
+            broad_phase_kind,
+            pairs: BTreeSet::new(),
+            manifolds: HashMap::default(),
+            joints: BTreeMap::new(),
+        }
+    }
+}
+
+// Rapier's rules: the later of the two colliders' rules in declaration order wins
+fn combine(a: f32, rule_a: CoefficientCombineRule, b: f32, rule_b: CoefficientCombineRule) -> f32 {
+    let rule = if rule_a as u8 >= rule_b as u8 { rule_a } else { rule_b };
+    match rule {
+        CoefficientCombineRule::Average => (a + b) * 0.5,
+        CoefficientCombineRule::Min => a.min(b),
+        CoefficientCombineRule::Multiply => a * b,
+        CoefficientCombineRule::Max => a.max(b),
+    }
+}
+
+fn effective_mass(body1: &NativeBody, inv_inertia1: &Mat3, r1: Vec3, body2: &NativeBody, inv_inertia2: &Mat3, r2: Vec3, direction: Vec3) -> f32 {
+    let angular1 = r1.cross(direction);
+    let angular2 = r2.cross(direction);
+    let inverse = body1.inv_mass
+        + body2.inv_mass
+        + angular1.dot(*inv_inertia1 * angular1)
+        + angular2.dot(*inv_inertia2 * angular2);
+    if inverse > f32::EPSILON { 1.0 / inverse } else { 0.0 }
+}
+
+// The matrix of `v.cross(u)`
+fn cross_matrix(v: Vec3) -> Mat3 {
+    Mat3::from_cols(Vec3::new(0.0, v.z, -v.y), Vec3::new(-v.z, 0.0, v.x), Vec3::new(v.y, -v.x, 0.0))
+}
+
+// How the relative velocity of two body points changes per unit of impulse between them
+fn point_inverse_mass(body1: &NativeBody, inv_inertia1: &Mat3, r1: Vec3, body2: &NativeBody, inv_inertia2: &Mat3, r2: Vec3) -> Mat3 {
+    let (cross1, cross2) = (cross_matrix(r1), cross_matrix(r2));
+    Mat3::from_diagonal(Vec3::splat(body1.inv_mass + body2.inv_mass))
+        - cross1 * *inv_inertia1 * cross1
+        - cross2 * *inv_inertia2 * cross2
+}
+
+// Inverse of `inverse_mass` on the locked axes of `frame`, in that frame. The locked axes are
+// solved together, one at a time they'd undo each other's work.
+fn locked_mass(inverse_mass: Mat3, frame: Mat3, locks: [bool; 3]) -> Mat3 {
+    let locked = Mat3::from_diagonal(Vec3::from(locks.map(|lock| if lock { 1.0 } else { 0.0 })));
+    let free = Mat3::IDENTITY - locked;
+    // Free rows and columns are swapped for the identity, whose inverse is then dropped again
+    let local = locked * frame.transpose() * inverse_mass * frame * locked + free;
+    // Only singular when both bodies are infinitely heavy
+    if local.determinant() != 0.0 {
+        locked * local.inverse() * locked
+    } else {
+        Mat3::ZERO
+    }
+}
+
+// How far frame 2 is turned away from frame 1, as small angles around the axes of frame 1
+fn angular_error(frame1: Quat, frame2: Quat) -> Vec3 {
+    let relative = frame1.inverse() * frame2;
+    let relative = if relative.w < 0.0 { -relative } else { relative };
+    relative.xyz() * 2.0
+}
+
+// Two mutable bodies out of the same list
+fn pair_mut(bodies: &mut [NativeBody], i: usize, j: usize) -> (&mut NativeBody, &mut NativeBody) {
+    if i < j {
+        let (left, right) = bodies.split_at_mut(j);
+        (&mut left[i], &mut right[0])
+    } else {
+        let (left, right) = bodies.split_at_mut(i);
+        (&mut right[0], &mut left[j])
+    }
+}
+
+impl NativeBackend {
+    // Moves every collider's box to where its body is now and updates the overlapping pairs
+    fn update_broad_phase(&mut self) {
+        for body in &self.bodies {
+            if let Some(aabb) = body.aabb() {
+                self.broad_phase.set(body.entity, aabb);
+            }
+        }
+        let events = self.broad_phase.update();
+        for removed in &events.removed {
+            self.pairs.remove(removed);
+        }
+        self.pairs.extend(events.added);
+    }
+
+    // Runs the narrow phase on every pair the broad phase found. Pairs that can't collide keep
+    // no manifolds, so their impulses start from zero once they can.
+    fn update_manifolds(&mut self) {
+        let mut manifolds = std::mem::take(&mut self.manifolds);
+        manifolds.retain(|pair, _| self.pairs.contains(pair));
+        for &(entity1, entity2) in &self.pairs {
+            let (Some(&index1), Some(&index2)) = (self.indices.get(&entity1), self.indices.get(&entity2)) else {
+                continue;
+            };
+            let (body1, body2) = (&self.bodies[index1], &self.bodies[index2]);
+            // Resting pairs keep last step's contacts, to warm start from once they wake up
+            if !body1.is_active() && !body2.is_active() {
+                continue;
+            }
+            let colliding = match (&body1.collider, &body2.collider) {
+                (Some(collider1), Some(collider2)) => {
+                    (body1.inv_mass > 0.0 || body2.inv_mass > 0.0) && !collider1.sensor && !collider2.sensor
+                }
+                _ => false,
+            };
+            let shapes = body1.collider.as_ref().zip(body2.collider.as_ref()).and_then(|(collider1, collider2)| {
+                Some((collider1.contact_shape.as_deref()?, collider2.contact_shape.as_deref()?))
+            });
+            let Some((shape1, shape2)) = shapes.filter(|_| colliding) else {
+                manifolds.remove(&(entity1, entity2));
+                continue;
+            };
+            let pos12 = body1.pose().inv_mul(&body2.pose());
+            let pair_manifolds = manifolds.entry((entity1, entity2)).or_default();
+            update_contact_manifolds(&pos12, shape1, shape2, PREDICTION, pair_manifolds);
+        }
+        self.manifolds = manifolds;
+    }
+
+    fn find_contacts(&self, dt: f32) -> Vec<ContactConstraint> {
+        let mut contacts = Vec::new();
+
+        for pair in &self.pairs {
+            let Some(manifolds) = self.manifolds.get(pair) else {
+                continue;
+            };
+            let (index1, index2) = (self.indices[&pair.0], self.indices[&pair.1]);
+            let (body1, body2) = (&self.bodies[index1], &self.bodies[index2]);
+            let (Some(collider1), Some(collider2)) = (&body1.collider, &body2.collider) else {
+                continue;
+            };
+            if !body1.is_active() && !body2.is_active() {
+                continue;
+            }
+
+            let friction = combine(
+                collider1.friction.coefficient,
+                collider1.friction.combine_rule,
+                collider2.friction.coefficient,
+                collider2.friction.combine_rule,
+            );
+            let restitution = combine(
+                collider1.restitution.coefficient,
+                collider1.restitution.combine_rule,
+                collider2.restitution.coefficient,
+                collider2.restitution.combine_rule,
+            );
+            let (inv_inertia1, inv_inertia2) = (body1.inv_inertia_world(), body2.inv_inertia_world());
+            let (center1, center2) = (body1.center(), body2.center());
+
+            for (manifold_index, manifold) in manifolds.iter().enumerate() {
+                let local_normal = manifold.local_normal1;
+                let normal = body1.rotation * local_normal;
+                let tangent1 = normal.any_orthonormal_vector();
+                let tangents = [tangent1, normal.cross(tangent1)];
+
+                for (point_index, point) in manifold.points.iter().enumerate() {
+                    let point1 = body1.position + body1.rotation * point.local1;
+                    let point2 = body2.position + body2.rotation * point.local2;
+                    let (r1, r2) = (point1 - center1, point2 - center2);
+
+                    let normal_velocity = (body2.point_velocity(r2) - body1.point_velocity(r1)).dot(normal);
+                    let target_velocity = if point.dist > 0.0 {
+                        // Not touching yet: the gap may close, but only within this step
+                        -point.dist / dt
+                    } else if normal_velocity < -RESTITUTION_THRESHOLD {
+                        -restitution * normal_velocity
+                    } else {
+                        0.0
+                    };
+
+                    contacts.push(ContactConstraint {
+                        body1: index1,
+                        body2: index2,
+                        pair: *pair,
+                        manifold: manifold_index,
+                        point: point_index,
+                        local1: point.local1,
+                        local2: point.local2,
+                        local_normal,
+                        normal,
+                        tangents,
+                        r1,
+                        r2,
+                        friction,
+                        target_velocity,
+                        normal_mass: effective_mass(body1, &inv_inertia1, r1, body2, &inv_inertia2, r2, normal),
+                        tangent_mass: tangents.map(|tangent| {
+                            effective_mass(body1, &inv_inertia1, r1, body2, &inv_inertia2, r2, tangent)
+                        }),
+                        normal_impulse: point.normal_impulse,
+                        tangent_impulse: point.tangent_impulse,
+                    });
+                }
+            }
+        }
+        contacts
+    }
+
+    fn find_joints(&self, dt: f32) -> Vec<JointConstraint> {
+        let mut constraints = Vec::new();
+        for (&entity, joint) in &self.joints {
+            let (Some(&index1), Some(&index2)) = (self.indices.get(&joint.body1), self.indices.get(&joint.body2)) else {
+                continue;
+            };
+            let (body1, body2) = (&self.bodies[index1], &self.bodies[index2]);
+            if !body1.is_active() && !body2.is_active() {
+                continue;
+            }
+            let (point1, point2, frame1, _) = joint.frames(body1, body2);
+            let (r1, r2) = (point1 - body1.center(), point2 - body2.center());
+            let (inv_inertia1, inv_inertia2) = (body1.inv_inertia_world(), body2.inv_inertia_world());
+            let frame = Mat3::from_quat(frame1);
+            let linear = point_inverse_mass(body1, &inv_inertia1, r1, body2, &inv_inertia2, r2);
+            let (arm1, arm2) = joint.limit_arms(point1, point2, body1, body2);
+            let limits = joint
+                .limits
+                .iter()
+                .map(|limit| {
+                    let (value, direction) = joint.measure(limit.axis, body1, body2);
+                    let angular = matches!(limit.axis, LimitedAxis::Angular(_));
+                    let mass = if angular {
+                        angular_mass(&inv_inertia1, &inv_inertia2, direction)
+                    } else {
+                        effective_mass(body1, &inv_inertia1, arm1, body2, &inv_inertia2, arm2, direction)
+                    };
+                    let [min, max] = limit.range;
+                    LimitConstraint {
+                        angular,
+                        direction,
+                        r1: arm1,
+                        r2: arm2,
+                        mass,
+                        velocity_range: [((min - value) / dt).min(0.0), ((max - value) / dt).max(0.0)],
+                        impulses: limit.impulses,
+                    }
+                })
+                .collect();
+            constraints.push(JointConstraint {
+                joint: entity,
+                body1: index1,
+                body2: index2,
+                r1,
+                r2,
+                frame,
+                linear_mass: locked_mass(linear, frame, joint.linear_locks()),
+                angular_mass: locked_mass(inv_inertia1 + inv_inertia2, frame, joint.angular_locks()),
+                linear_impulse: joint.impulses[0],
+                angular_impulse: joint.impulses[1],
+                limits,
+            });
+        }
+        constraints
+    }
+
+    // Joins touching or jointed dynamic bodies into islands and wakes every island with an awake
+    // body in it, or one a moving kinematic body pushes. Static bodies don't join islands, so piles
+    // resting on the same ground sleep and wake separately. Returns the islands left awake.
+    fn update_islands(&mut self) -> Vec<Vec<usize>> {
+        fn root(parents: &mut [usize], mut index: usize) -> usize {
+            while parents[index] != index {
+                parents[index] = parents[parents[index]];
+                index = parents[index];
+            }
+            index
+        }
+
+        let mut parents: Vec<usize> = (0..self.bodies.len()).collect();
+        let mut pushed = Vec::new();
+        let moving = |body: &NativeBody| body.linvel != Vec3::ZERO || body.angvel != Vec3::ZERO;
+        let touching = self
+            .manifolds
+            .iter()
+            .filter(|(_, manifolds)| manifolds.iter().any(|manifold| !manifold.points.is_empty()))
+            .map(|(pair, _)| *pair);
+        let jointed = self.joints.values().map(|joint| (joint.body1, joint.body2));
+        for pair in touching.chain(jointed) {
+            let (Some(&index1), Some(&index2)) = (self.indices.get(&pair.0), self.indices.get(&pair.1)) else {
+                continue;
+            };
+            let (body1, body2) = (&self.bodies[index1], &self.bodies[index2]);
+            match (body1.body_type, body2.body_type) {
+                (RigidBody::Dynamic, RigidBody::Dynamic) => {
+                    let (root1, root2) = (root(&mut parents, index1), root(&mut parents, index2));
+                    parents[root1] = root2;
+                }
+                (RigidBody::Dynamic, _) if moving(body2) => pushed.push(index1),
+                (_, RigidBody::Dynamic) if moving(body1) => pushed.push(index2),
+                _ => {}
+            }
+        }
+        for index in pushed {
+            self.bodies[index].activation.wake_up();
+        }
+
+        let mut islands: HashMap<usize, Vec<usize>> = HashMap::default();
+        for index in 0..self.bodies.len() {
+            if self.bodies[index].body_type == RigidBody::Dynamic {
+                islands.entry(root(&mut parents, index)).or_default().push(index);
+            }
+        }
+        let mut islands: Vec<Vec<usize>> = islands.into_values().collect();
+        islands.retain(|island| {
+            if island.iter().all(|&index| self.bodies[index].activation.sleeping) {
+                return false;
+            }
+            // Their time below the thresholds is kept, so the island can go back to sleep as
+            // soon as whatever woke it settles too
+            for &index in island {
+                self.bodies[index].activation.sleeping = false;
+            }
+            true
+        });
+        islands
+    }
+
+    // Puts an island to sleep once every body in it has been slow for long enough
+    fn update_sleeping(&mut self, islands: &[Vec<usize>], dt: f32) {
+        for island in islands {
+            for &index in island {
+                let body = &mut self.bodies[index];
+                let (linvel, angvel) = (body.linvel, body.angvel);
+                body.activation.update(linvel, angvel, dt);
+            }
+            if island.iter().all(|&index| self.bodies[index].activation.time_since_can_sleep >= TIME_UNTIL_SLEEP) {
+                for &index in island {
+                    self.bodies[index].sleep();
+                }
+            }
+        }
+    }
+
+    // Wakes the body and everything touching or jointed to it, after an edit moved, changed or
+    // removed it
+    fn wake_touching(&mut self, entity: Entity) {
+        let touching: Vec<Entity> = self
+            .manifolds
+            .keys()
+            .copied()
+            .chain(self.joints.values().map(|joint| (joint.body1, joint.body2)))
+            .filter_map(|(entity1, entity2)| match (entity1 == entity, entity2 == entity) {
+                (true, _) => Some(entity2),
+                (_, true) => Some(entity1),
+                _ => None,
+            })
+            .collect();
+        for other in touching.into_iter().chain([entity]) {
+            if let Some(&index) = self.indices.get(&other) {
+                self.bodies[index].activation.wake_up();
+            }
+        }
+    }
+
+    // Keeps the solved impulses on the manifold points and joints, where the next step starts
+    // from them
+    fn store_impulses(&mut self, contacts: &[ContactConstraint], joints: &[JointConstraint]) {
+        for constraint in joints {
+            if let Some(joint) = self.joints.get_mut(&constraint.joint) {
+                joint.impulses = [constraint.linear_impulse, constraint.angular_impulse];
+                for (limit, solved) in joint.limits.iter_mut().zip(&constraint.limits) {
+                    limit.impulses = solved.impulses;
+                }
+            }
+        }
+        for contact in contacts {
+            if let Some(point) = self
+                .manifolds
+                .get_mut(&contact.pair)
+                .and_then(|manifolds| manifolds.get_mut(contact.manifold))
+                .and_then(|manifold| manifold.points.get_mut(contact.point))
+            {
+                point.normal_impulse = contact.normal_impulse;
+                point.tangent_impulse = contact.tangent_impulse;
+            }
+        }
+    }
+
+    // Moves every body's velocity to the end of the step under gravity and external forces, with
+    // the world's integrator, then damps it. Returns how far each center of mass would travel
+    // without contacts or damping, together with the velocity that travel assumes.
+    fn integrate_forces(&mut self, params: &StepParams) -> Vec<(Vec3, Vec3)> {
+        let dt = params.dt;
+        self.bodies
+            .iter_mut()
+            .map(|body| {
+                if body.inv_mass == 0.0 || body.activation.sleeping {
+                    return (body.linvel * dt, body.linvel);
+                }
+                let (gravity, force) = (params.gravity, body.force * body.inv_mass);
+                let start = Kinematics { position: body.center(), velocity: body.linvel };
+                let end = params.integrator.step(start, dt, |_, _| gravity + force);
+                // Rotation stays semi-implicit whatever the integrator
+                body.angvel += body.inv_inertia_world() * body.torque * dt;
+
+                // Rapier's damping model for both velocities. Positions take the damped velocity
+                // up like a contact impulse, as `integrate_positions` does for the solver's.
+                body.linvel = end.velocity / (1.0 + dt * body.linear_damping);
+                body.angvel /= 1.0 + dt * body.angular_damping;
+                (end.position - start.position, end.velocity)
+            })
+            .collect()
+    }
+
+    fn solve_velocities(&mut self, contacts: &mut [ContactConstraint], joints: &mut [JointConstraint], params: &StepParams) {
+        let inv_inertia: Vec<Mat3> = self.bodies.iter().map(NativeBody::inv_inertia_world).collect();
+
+        // Warm start with last step's impulses
+        for joint in joints.iter() {
+            let (body1, body2) = pair_mut(&mut self.bodies, joint.body1, joint.body2);
+            joint.apply(body1, body2, joint.linear_impulse, joint.angular_impulse, &inv_inertia[joint.body1], &inv_inertia[joint.body2]);
+            for limit in &joint.limits {
+                let impulse = limit.impulses[0] + limit.impulses[1];
+                joint.apply_limit(limit, body1, body2, impulse, &inv_inertia[joint.body1], &inv_inertia[joint.body2]);
+            }
+        }
+        for contact in contacts.iter() {
+            let impulse = contact.normal * contact.normal_impulse
+                + contact.tangents[0] * contact.tangent_impulse[0]
+                + contact.tangents[1] * contact.tangent_impulse[1];
+            let (body1, body2) = pair_mut(&mut self.bodies, contact.body1, contact.body2);
+            body1.apply_impulse(-impulse, contact.r1, &inv_inertia[contact.body1]);
+            body2.apply_impulse(impulse, contact.r2, &inv_inertia[contact.body2]);
+        }
+
+        for iteration in 0..params.velocity_iterations.max(1) {
+            // Locked axes keep the anchors and frames moving together
+            for joint in joints.iter_mut() {
+                let (body1, body2) = pair_mut(&mut self.bodies, joint.body1, joint.body2);
+                let (inv_inertia1, inv_inertia2) = (&inv_inertia[joint.body1], &inv_inertia[joint.body2]);
+                let relative = body2.point_velocity(joint.r2) - body1.point_velocity(joint.r1);
+                let linear = joint.linear_mass * (joint.frame.transpose() * -relative);
+                joint.apply(body1, body2, linear, Vec3::ZERO, inv_inertia1, inv_inertia2);
+                let relative = body2.angvel - body1.angvel;
+                let angular = joint.angular_mass * (joint.frame.transpose() * -relative);
+                joint.apply(body1, body2, Vec3::ZERO, angular, inv_inertia1, inv_inertia2);
+                joint.linear_impulse += linear;
+                joint.angular_impulse += angular;
+
+                // Limits push only from the end the measure is heading past
+                for index in 0..joint.limits.len() {
+                    let limit = &joint.limits[index];
+                    let mut impulses = limit.impulses;
+                    for (end, impulse) in impulses.iter_mut().enumerate() {
+                        let velocity = joint.limit_velocity(limit, body1, body2);
+                        let delta = limit.mass * (limit.velocity_range[end] - velocity);
+                        let total = if end == 0 { (*impulse + delta).max(0.0) } else { (*impulse + delta).min(0.0) };
+                        joint.apply_limit(limit, body1, body2, total - *impulse, inv_inertia1, inv_inertia2);
+                        *impulse = total;
+                    }
+                    joint.limits[index].impulses = impulses;
+                }
+            }
+
+            for contact in contacts.iter_mut() {
+                let (body1, body2) = pair_mut(&mut self.bodies, contact.body1, contact.body2);
+                let (inv_inertia1, inv_inertia2) = (&inv_inertia[contact.body1], &inv_inertia[contact.body2]);
+
+                // Non-penetration, accumulated impulses never pull the bodies together
+                let relative = body2.point_velocity(contact.r2) - body1.point_velocity(contact.r1);
+                let delta = contact.normal_mass * (contact.target_velocity - relative.dot(contact.normal));
+                let total = (contact.normal_impulse + delta).max(0.0);
+                let impulse = contact.normal * (total - contact.normal_impulse);
+                contact.normal_impulse = total;
+                body1.apply_impulse(-impulse, contact.r1, inv_inertia1);
+                body2.apply_impulse(impulse, contact.r2, inv_inertia2);
+
+                // Coulomb friction, bounded by the normal impulse
+                if iteration >= params.friction_iterations {
+                    continue;
+                }
+                let limit = contact.friction * contact.normal_impulse;
+                for axis in 0..2 {
+                    let tangent = contact.tangents[axis];
+                    let relative = body2.point_velocity(contact.r2) - body1.point_velocity(contact.r1);
+                    let delta = -contact.tangent_mass[axis] * relative.dot(tangent);
+                    let total = (contact.tangent_impulse[axis] + delta).clamp(-limit, limit);
+                    let impulse = tangent * (total - contact.tangent_impulse[axis]);
+                    contact.tangent_impulse[axis] = total;
+                    body1.apply_impulse(-impulse, contact.r1, inv_inertia1);
+                    body2.apply_impulse(impulse, contact.r2, inv_inertia2);
+                }
+            }
+        }
+    }
+
+    // The free motion, plus the contacts' velocity change as if applied evenly over the step
+    fn integrate_positions(&mut self, params: &StepParams, free: &[(Vec3, Vec3)]) {
+        let dt = params.dt;
+        let weight = params.integrator.displacement_weight();
+        for (body, &(displacement, velocity)) in self.bodies.iter_mut().zip(free) {
+            match body.body_type {
+                _ if body.activation.sleeping => {}
+                RigidBody::Dynamic | RigidBody::KinematicVelocityBased => {
+                    let translation = displacement + (body.linvel - velocity) * (weight * dt);
+                    let rotation = body.angvel * dt;
+                    body.displace(translation, rotation);
+                }
+                // Moved by the editor only
+                RigidBody::Fixed | RigidBody::KinematicPositionBased => {}
+            }
+        }
+    }
+
+    // Pushes penetrating bodies apart and jointed ones back together without adding velocity,
+    // so resolving overlap can't launch them
+    fn stabilize(&mut self, contacts: &[ContactConstraint], joints: &[JointConstraint], params: &StepParams) {
+        for _ in 0..params.stabilization_iterations {
+            for constraint in joints {
+                let joint = &self.joints[&constraint.joint];
+                let (body1, body2) = pair_mut(&mut self.bodies, constraint.body1, constraint.body2);
+                // The anchors first, then the rotations from where that left the bodies
+                let (point1, point2, frame1, _) = joint.frames(body1, body2);
+                let frame = Mat3::from_quat(frame1);
+                let (r1, r2) = (point1 - body1.center(), point2 - body2.center());
+                let (inv_inertia1, inv_inertia2) = (body1.inv_inertia_world(), body2.inv_inertia_world());
+                let mass = locked_mass(point_inverse_mass(body1, &inv_inertia1, r1, body2, &inv_inertia2, r2), frame, joint.linear_locks());
+                let correction = frame * (mass * (frame.transpose() * (point2 - point1)) * -JOINT_CORRECTION);
+                body1.displace(-correction * body1.inv_mass, inv_inertia1 * r1.cross(-correction));
+                body2.displace(correction * body2.inv_mass, inv_inertia2 * r2.cross(correction));
+
+                let (_, _, frame1, frame2) = joint.frames(body1, body2);
+                let frame = Mat3::from_quat(frame1);
+                let (inv_inertia1, inv_inertia2) = (body1.inv_inertia_world(), body2.inv_inertia_world());
+                let mass = locked_mass(inv_inertia1 + inv_inertia2, frame, joint.angular_locks());
+                let correction = frame * (mass * angular_error(frame1, frame2) * -JOINT_CORRECTION);
+                body1.displace(Vec3::ZERO, inv_inertia1 * -correction);
+                body2.displace(Vec3::ZERO, inv_inertia2 * correction);
+
+                // Then back inside the limits
+                for limit in &joint.limits {
+                    let (value, direction) = joint.measure(limit.axis, body1, body2);
+                    let error = value - value.clamp(limit.range[0], limit.range[1]);
+                    if error == 0.0 {
+                        continue;
+                    }
+                    let (inv_inertia1, inv_inertia2) = (body1.inv_inertia_world(), body2.inv_inertia_world());
+                    if let LimitedAxis::Angular(_) = limit.axis {
+                        let correction = direction * (-JOINT_CORRECTION * error * angular_mass(&inv_inertia1, &inv_inertia2, direction));
+                        body1.displace(Vec3::ZERO, inv_inertia1 * -correction);
+                        body2.displace(Vec3::ZERO, inv_inertia2 * correction);
+                    } else {
+                        let (point1, point2, _, _) = joint.frames(body1, body2);
+                        let (r1, r2) = joint.limit_arms(point1, point2, body1, body2);
+                        let mass = effective_mass(body1, &inv_inertia1, r1, body2, &inv_inertia2, r2, direction);
+                        let correction = direction * (-JOINT_CORRECTION * error * mass);
+                        body1.displace(-correction * body1.inv_mass, inv_inertia1 * r1.cross(-correction));
+                        body2.displace(correction * body2.inv_mass, inv_inertia2 * r2.cross(correction));
+                    }
+                }
+            }
+            for contact in contacts {
+                let (body1, body2) = pair_mut(&mut self.bodies, contact.body1, contact.body2);
+                let normal = body1.rotation * contact.local_normal;
+                let point1 = body1.position + body1.rotation * contact.local1;
+                let point2 = body2.position + body2.rotation * contact.local2;
+                let separation = (point2 - point1).dot(normal);
+                let error = (separation + ALLOWED_PENETRATION).min(0.0);
+                if error == 0.0 {
+                    continue;
+                }
+
+                let (r1, r2) = (point1 - body1.center(), point2 - body2.center());
+                let (inv_inertia1, inv_inertia2) = (body1.inv_inertia_world(), body2.inv_inertia_world());
+                let mass = effective_mass(body1, &inv_inertia1, r1, body2, &inv_inertia2, r2, normal);
+                let correction = normal * (-POSITION_CORRECTION * error * mass);
+                body1.displace(-correction * body1.inv_mass, inv_inertia1 * r1.cross(-correction));
+                body2.displace(correction * body2.inv_mass, inv_inertia2 * r2.cross(correction));
+            }
+        }
+    }
+}
+
+impl PhysicsBackend for NativeBackend {
+    fn set_body(&mut self, entity: Entity, desc: &BodyDesc) {
+        let mut body = NativeBody::new(entity, desc);
+        if body.collider.is_none() {
+            self.broad_phase.remove(entity);
+        }
+        match self.indices.get(&entity) {
+            Some(&index) => {
+                // Edits keep the sleep thresholds
+                body.activation = Activation { time_since_can_sleep: 0.0, sleeping: false, ..self.bodies[index].activation };
+                self.bodies[index] = body;
+            }
+            None => {
+                self.indices.insert(entity, self.bodies.len());
+                self.bodies.push(body);
+            }
+        }
+        self.wake_touching(entity);
+    }
+
+    fn set_state(&mut self, entity: Entity, state: &BodyState) {
+        let Some(&index) = self.indices.get(&entity) else {
+            return;
+        };
+        let body = &mut self.bodies[index];
+        // Unchanged values are skipped so resting bodies can fall asleep
+        if *state == (BodyState { position: body.position, rotation: body.rotation, linvel: body.linvel, angvel: body.angvel }) {
+            return;
+        }
+        body.position = state.position;
+        body.rotation = state.rotation;
+        body.linvel = state.linvel;
+        body.angvel = state.angvel;
+        self.wake_touching(entity);
+    }
+
+    fn remove_body(&mut self, entity: Entity) {
+        let Some(&index) = self.indices.get(&entity) else {
+            return;
+        };
+        // What rested on the body falls once it's gone
+        self.wake_touching(entity);
+        self.indices.remove(&entity);
+        self.broad_phase.remove(entity);
+        self.bodies.swap_remove(index);
+        if let Some(moved) = self.bodies.get(index) {
+            self.indices.insert(moved.entity, index);
+        }
+    }
+
+    // Locked axes are held together and free ones kept within their limits
+    fn set_joint(&mut self, entity: Entity, desc: &JointDesc) {
+        let raw = &desc.joint.raw;
+        self.remove_joint(entity);
+        self.joints.insert(entity, NativeJoint {
+            body1: desc.body1,
+            body2: desc.body2,
+            anchor1: desc.joint.local_anchor1(),
+            anchor2: desc.joint.local_anchor2(),
+            basis1: desc.joint.local_basis1(),
+            basis2: desc.joint.local_basis2(),
+            locked: raw.locked_axes.bits(),
+            limits: NativeJoint::limits(raw),
+            impulses: [Vec3::ZERO; 2],
+        });
+        self.wake_touching(desc.body1);
+        self.wake_touching(desc.body2);
+    }
+
+    fn remove_joint(&mut self, entity: Entity) {
+        if let Some(joint) = self.joints.remove(&entity) {
+            self.wake_touching(joint.body1);
+            self.wake_touching(joint.body2);
+        }
+    }
+
+    fn update_queries(&mut self) {
+        self.update_broad_phase();
+    }
+
+    // The old structure is dropped and every collider is added to the new one
+    fn set_broad_phase(&mut self, kind: BroadPhaseKind) {
+        if kind == self.broad_phase_kind {
+            return;
+        }
+        self.broad_phase = kind.create();
+        self.broad_phase_kind = kind;
+        self.pairs.clear();
+        self.update_broad_phase();
+    }
+
+    fn step(&mut self, params: &StepParams) {
+        if params.dt <= 0.0 {
+            return;
+        }
+        self.update_broad_phase();
+        self.update_manifolds();
+        let islands = self.update_islands();
+        let free = self.integrate_forces(params);
+        let mut contacts = self.find_contacts(params.dt);
+        let mut joints = self.find_joints(params.dt);
+        self.solve_velocities(&mut contacts, &mut joints, params);
+        self.store_impulses(&contacts, &joints);
+        self.integrate_positions(params, &free);
+        self.stabilize(&contacts, &joints, params);
+        self.update_sleeping(&islands, params.dt);
+    }
+
+    fn set_sleeping(&mut self, entity: Entity, sleeping: &Sleeping) {
+        let Some(&index) = self.indices.get(&entity) else {
+            return;
+        };
+        let body = &mut self.bodies[index];
+        body.activation.linear_threshold = sleeping.linear_threshold;
+        body.activation.angular_threshold = sleeping.angular_threshold;
+        if sleeping.sleeping && !body.activation.sleeping && body.body_type == RigidBody::Dynamic {
+            body.sleep();
+        } else if !sleeping.sleeping && body.activation.sleeping {
+            self.wake_touching(entity);
+        }
+    }
+
+    fn is_sleeping(&self, entity: Entity) -> bool {
+        self.indices.get(&entity).is_some_and(|&index| self.bodies[index].activation.sleeping)
+    }
+
+    fn mass_properties(&self, entity: Entity) -> Option<MassProperties> {
+        self.indices.get(&entity).map(|&index| self.bodies[index].mass_properties)
+    }
+
+    fn body_state(&self, entity: Entity) -> Option<BodyState> {
+        let body = &self.bodies[*self.indices.get(&entity)?];
+        Some(BodyState {
+            position: body.position,
+            rotation: body.rotation,
+            linvel: body.linvel,
+            angvel: body.angvel,
+        })
+    }
+
+    fn cast_ray(&self, ray: Ray, max_distance: f32, filter: &dyn Fn(Entity) -> bool) -> Option<(Entity, f32)> {
+        let raw = RawRay::new(ray.origin.into(), ray.direction.into());
+        let mut closest: Option<(Entity, f32)> = None;
+        self.broad_phase.query_ray(ray.origin, ray.direction, max_distance, &mut |entity, entered| {
+            // Boxes entered after the closest hit so far can't hold a closer one
+            if closest.is_some_and(|(_, distance)| entered > distance) || !filter(entity) {
+                return;
+            }
+            let Some(body) = self.indices.get(&entity).map(|&index| &self.bodies[index]) else {
+                return;
+            };
+            let Some(collider) = &body.collider else {
+                return;
+            };
+            if let Some(distance) = collider.shape.cast_ray(&body.isometry(), &raw, max_distance, true) {
+                if closest.is_none_or(|(_, closest)| distance < closest) {
+                    closest = Some((entity, distance));
+                }
+            }
+        });
+        closest
+    }
+
+    fn debug_boxes(&self, visit: &mut dyn FnMut(&Aabb, DebugBox)) {
+        self.broad_phase.debug_boxes(visit);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::physics::rapier_backend::RapierBackend;
+    use crate::physics::{ColliderDesc, Integrator};
+
+    fn ball(y: f32) -> BodyDesc {
+        BodyDesc {
+            body_type: RigidBody::Dynamic,
+            state: BodyState { position: Vec3::Y * y, ..default() },
+            collider: Some(ColliderDesc {
+                shape: Collider::ball(0.5),
+                mass: ColliderMassProperties::Mass(2.0),
+                friction: Friction::default(),
+                restitution: Restitution::default(),
+                sensor: false,
+            }),
+            damping: Damping::default(),
+            force: ExternalForce::default(),
+            ccd: false,
+        }
+    }
+
+    fn params(integrator: Integrator) -> StepParams {
+        StepParams {
+            dt: 1.0 / 60.0,
+            gravity: Vec3::new(0.0, -9.81, 0.0),
+            velocity_iterations: 4,
+            friction_iterations: 8,
+            stabilization_iterations: 1,
+            max_ccd_substeps: 1,
+            integrator,
+        }
+    }
+
+    #[test]
+    fn free_fall_uses_the_world_integrator() {
+        let entity = Entity::from_raw(1);
+        let steps = 60;
+        let t = steps as f32 / 60.0;
+        let expected = 100.0 - 0.5 * 9.81 * t * t;
+
+        for integrator in Integrator::ALL {
+            let mut backend = NativeBackend::default();
+            backend.set_body(entity, &ball(100.0));
+            for _ in 0..steps {
+                backend.step(&params(integrator));
+            }
+            let state = backend.body_state(entity).unwrap();
+            assert!((state.linvel.y + 9.81 * t).abs() < 1e-3, "{}: velocity {}", integrator.label(), state.linvel.y);
+            let error = (state.position.y - expected).abs();
+            match integrator {
+                Integrator::ExplicitEuler | Integrator::SemiImplicitEuler => assert!(error > 0.05, "{}: error {error}", integrator.label()),
+                Integrator::VelocityVerlet | Integrator::Rk4 => assert!(error < 1e-3, "{}: error {error}", integrator.label()),
+            }
+        }
+    }
+
+    #[test]
+    fn ball_comes_to_rest_on_the_ground() {
+        let mut backend = NativeBackend::default();
+        let mut ground = ball(0.0);
+        ground.body_type = RigidBody::Fixed;
+        ground.collider.as_mut().unwrap().shape = Collider::cuboid(10.0, 0.5, 10.0);
+        backend.set_body(Entity::from_raw(1), &ground);
+        backend.set_body(Entity::from_raw(2), &ball(3.0));
+
+        for integrator in Integrator::ALL {
+            backend.set_state(Entity::from_raw(2), &BodyState { position: Vec3::Y * 3.0, ..default() });
+            for _ in 0..240 {
+                backend.step(&params(integrator));
+            }
+            let state = backend.body_state(Entity::from_raw(2)).unwrap();
+            assert!((state.position.y - 1.0).abs() < 0.01, "{}: height {}", integrator.label(), state.position.y);
+            assert!(state.linvel.length() < 0.01, "{}: velocity {}", integrator.label(), state.linvel);
+        }
+    }
+
+    #[test]
+    fn box_rests_flat_on_four_corners() {
+        let mut backend = NativeBackend::default();
+        let mut ground = ball(0.0);
+        ground.body_type = RigidBody::Fixed;
+        ground.collider.as_mut().unwrap().shape = Collider::cuboid(10.0, 0.5, 10.0);
+        let mut cube = ball(1.2);
+        cube.collider.as_mut().unwrap().shape = Collider::cuboid(0.5, 0.5, 0.5);
+        cube.state.rotation = Quat::from_rotation_y(0.4);
+        backend.set_body(Entity::from_raw(1), &ground);
+        backend.set_body(Entity::from_raw(2), &cube);
+
+        for _ in 0..120 {
+            backend.step(&params(Integrator::default()));
+        }
+        let state = backend.body_state(Entity::from_raw(2)).unwrap();
+        assert!((state.position.y - 1.0).abs() < 0.01, "height {}", state.position.y);
+        assert!(state.rotation.angle_between(cube.state.rotation) < 1e-3, "rotation {}", state.rotation);
+        assert!(state.linvel.length() < 0.01 && state.angvel.length() < 0.01);
+
+        // All four corners carry the weight, their impulses kept from one step to the next
+        let manifolds = &backend.manifolds[&(Entity::from_raw(1), Entity::from_raw(2))];
+        let impulses: Vec<f32> = manifolds.iter().flat_map(|manifold| &manifold.points).map(|point| point.normal_impulse).collect();
+        assert_eq!(impulses.len(), 4);
+        assert!(impulses.iter().all(|impulse| *impulse > 0.0), "impulses {impulses:?}");
+        let weight = 2.0 * 9.81 / 60.0;
+        let total: f32 = impulses.iter().sum();
+        assert!((total - weight).abs() < weight * 0.05, "total impulse {total}, expected {weight}");
+    }
+
+    #[test]
+    fn mass_properties_match_rapier() {
+        let mut desc = ball(3.0);
+        desc.collider.as_mut().unwrap().shape = Collider::cuboid(0.5, 0.25, 1.0);
+        let entity = Entity::from_raw(1);
+        let mut native = NativeBackend::default();
+        let mut rapier = RapierBackend::default();
+        native.set_body(entity, &desc);
+        rapier.set_body(entity, &desc);
+        native.step(&params(Integrator::default()));
+        rapier.step(&params(Integrator::default()));
+
+        let (native, rapier) = (native.mass_properties(entity).unwrap(), rapier.mass_properties(entity).unwrap());
+        assert!((native.mass - 2.0).abs() < 1e-5 && (rapier.mass - 2.0).abs() < 1e-5);
+        assert!(native.local_center_of_mass.distance(rapier.local_center_of_mass) < 1e-5);
+        assert!(native.principal_inertia.distance(rapier.principal_inertia) < 1e-4);
+    }
+
+    #[test]
+    fn damping_matches_rapier() {
+        let mut desc = ball(0.0);
+        desc.state.linvel = Vec3::new(3.0, 0.0, -1.0);
+        desc.state.angvel = Vec3::new(0.0, 2.0, 1.0);
+        desc.damping = Damping { linear_damping: 0.8, angular_damping: 1.5 };
+        let entity = Entity::from_raw(1);
+        let mut native = NativeBackend::default();
+        let mut rapier = RapierBackend::default();
+        native.set_body(entity, &desc);
+        rapier.set_body(entity, &desc);
+        for _ in 0..60 {
+            native.step(&params(Integrator::default()));
+            rapier.step(&params(Integrator::default()));
+        }
+
+        let (native, rapier) = (native.body_state(entity).unwrap(), rapier.body_state(entity).unwrap());
+        assert!(native.linvel.distance(rapier.linvel) < 1e-4, "linear {} against {}", native.linvel, rapier.linvel);
+        assert!(native.angvel.distance(rapier.angvel) < 1e-4, "angular {} against {}", native.angvel, rapier.angvel);
+        assert!(native.position.distance(rapier.position) < 1e-3, "position {} against {}", native.position, rapier.position);
+    }
+
+    // A unit cube one to the side of a fixed body at the origin, jointed to it there
+    fn jointed(joint: impl Into<GenericJoint>) -> NativeBackend {
+        let mut backend = NativeBackend::default();
+        let mut anchor = ball(0.0);
+        anchor.body_type = RigidBody::Fixed;
+        anchor.collider = None;
+        backend.set_body(Entity::from_raw(1), &anchor);
+        let mut cube = ball(0.0);
+        cube.collider.as_mut().unwrap().shape = Collider::cuboid(0.25, 0.25, 0.25);
+        cube.state.position = Vec3::X;
+        backend.set_body(Entity::from_raw(2), &cube);
+        let mut joint = joint.into();
+        joint.set_local_anchor2(-Vec3::X);
+        backend.set_joint(Entity::from_raw(3), &JointDesc { body1: Entity::from_raw(1), body2: Entity::from_raw(2), joint });
+        backend
+    }
+
+    #[test]
+    fn spherical_joint_keeps_the_pendulum_length() {
+        let mut backend = jointed(SphericalJointBuilder::new());
+        let mut lowest = 0.0_f32;
+        for _ in 0..120 {
+            backend.step(&params(Integrator::default()));
+            let state = backend.body_state(Entity::from_raw(2)).unwrap();
+            let anchor = state.position + state.rotation * -Vec3::X;
+            assert!(anchor.length() < 0.001, "the anchor drifted to {anchor}");
+            lowest = lowest.min(state.position.y);
+        }
+        assert!(lowest < -0.9, "the pendulum only swung down to {lowest}");
+    }
+
+    #[test]
+    fn jointed_chain_stays_connected() {
+        // Five more cubes, each one jointed to the end of the previous one
+        let mut backend = jointed(SphericalJointBuilder::new());
+        let link = SphericalJointBuilder::new().local_anchor1(Vec3::X * 0.5).local_anchor2(-Vec3::X * 0.5);
+        for i in 3..8 {
+            let mut cube = ball(0.0);
+            cube.collider.as_mut().unwrap().shape = Collider::cuboid(0.25, 0.25, 0.25);
+            cube.state.position = Vec3::X * (i - 1) as f32;
+            backend.set_body(Entity::from_raw(i * 10), &cube);
+            let body1 = Entity::from_raw(if i == 3 { 2 } else { (i - 1) * 10 });
+            backend.set_joint(Entity::from_raw(i * 10 + 1), &JointDesc { body1, body2: Entity::from_raw(i * 10), joint: link.into() });
+        }
+        for _ in 0..300 {
+            backend.step(&params(Integrator::default()));
+        }
+        let links = [2, 30, 40, 50, 60, 70].map(|i| backend.body_state(Entity::from_raw(i)).unwrap());
+        for pair in links.windows(2) {
+            let end = pair[0].position + pair[0].rotation * Vec3::X * 0.5;
+            let start = pair[1].position + pair[1].rotation * -Vec3::X * 0.5;
+            assert!(end.distance(start) < 0.01, "links {} apart", end.distance(start));
+        }
+        assert!(links[5].position.y < -1.0, "the chain didn't swing down");
+    }
+
+    #[test]
+    fn fixed_joint_holds_its_body() {
+        let mut backend = jointed(FixedJointBuilder::new());
+        for _ in 0..120 {
+            backend.step(&params(Integrator::default()));
+        }
+        let state = backend.body_state(Entity::from_raw(2)).unwrap();
+        assert!(state.position.distance(Vec3::X) < 0.02, "position {}", state.position);
+        assert!(state.rotation.angle_between(Quat::IDENTITY) < 0.02, "rotation {}", state.rotation);
+        assert!(state.linvel.length() < 0.05 && state.angvel.length() < 0.05);
+    }
+
+    #[test]
+    fn revolute_joint_turns_only_around_its_axis() {
+        let mut backend = jointed(RevoluteJointBuilder::new(Vec3::Z));
+        let mut state = backend.body_state(Entity::from_raw(2)).unwrap();
+        state.angvel = Vec3::new(1.0, 1.0, 0.0);
+        backend.set_state(Entity::from_raw(2), &state);
+        for _ in 0..60 {
+            backend.step(&params(Integrator::default()));
+        }
+        let state = backend.body_state(Entity::from_raw(2)).unwrap();
+        let (axis, angle) = state.rotation.to_axis_angle();
+        assert!(angle > 0.5 && axis.z.abs() > 0.999, "rotated by {angle} around {axis}");
+        assert!(state.position.z.abs() < 0.01 && (state.position.length() - 1.0).abs() < 0.02, "position {}", state.position);
+    }
+
+    #[test]
+    fn rope_joint_stops_the_fall_at_its_length() {
+        let mut backend = jointed(RopeJointBuilder::new().limits([0.0, 2.0]));
+        let mut longest = 0.0_f32;
+        for _ in 0..180 {
+            backend.step(&params(Integrator::default()));
+            let state = backend.body_state(Entity::from_raw(2)).unwrap();
+            let length = (state.position + state.rotation * -Vec3::X).length();
+            assert!(length < 2.02, "the rope stretched to {length}");
+            longest = longest.max(length);
+        }
+        assert!(longest > 1.95, "the rope never went taut, longest {longest}");
+    }
+
+    #[test]
+    fn revolute_joint_stops_at_its_limits() {
+        // Gravity swings the cube down, clockwise around Z
+        let mut backend = jointed(RevoluteJointBuilder::new(Vec3::Z).limits([-0.5, 0.5]));
+        for _ in 0..180 {
+            backend.step(&params(Integrator::default()));
+            let rotation = backend.body_state(Entity::from_raw(2)).unwrap().rotation;
+            let angle = 2.0 * rotation.z.atan2(rotation.w);
+            assert!(angle > -0.52 && angle < 0.52, "turned to {angle}");
+        }
+        let rotation = backend.body_state(Entity::from_raw(2)).unwrap().rotation;
+        let angle = 2.0 * rotation.z.atan2(rotation.w);
+        assert!((angle + 0.5).abs() < 0.02, "came to rest at {angle}");
+    }
+
+    #[test]
+    fn prismatic_joint_stops_at_its_limits() {
+        let mut backend = jointed(PrismaticJointBuilder::new(Vec3::Y).limits([-0.5, 0.5]));
+        for _ in 0..120 {
+            backend.step(&params(Integrator::default()));
+            let position = backend.body_state(Entity::from_raw(2)).unwrap().position;
+            assert!(position.y > -0.52, "slid down to {position}");
+        }
+        let state = backend.body_state(Entity::from_raw(2)).unwrap();
+        assert!(state.position.distance(Vec3::new(1.0, -0.5, 0.0)) < 0.02, "came to rest at {}", state.position);
+        assert!(state.linvel.length() < 0.05, "still moving at {}", state.linvel);
+    }
+
+    // A ground box at entity 0, where it's kept whatever else is added
+    fn ground(backend: &mut NativeBackend) {
+        let mut ground = ball(0.0);
+        ground.body_type = RigidBody::Fixed;
+        ground.collider.as_mut().unwrap().shape = Collider::cuboid(10.0, 0.5, 10.0);
+        backend.set_body(Entity::from_raw(0), &ground);
+    }
+
+    // A column of unit cubes resting on the ground, numbered from `first` upwards
+    fn stack(backend: &mut NativeBackend, x: f32, first: u32, height: u32) {
+        for i in 0..height {
+            let mut cube = ball(0.0);
+            cube.collider.as_mut().unwrap().shape = Collider::cuboid(0.5, 0.5, 0.5);
+            cube.state.position = Vec3::new(x, 1.0 + i as f32, 0.0);
+            backend.set_body(Entity::from_raw(first + i), &cube);
+        }
+    }
+
+    fn asleep(backend: &NativeBackend, entities: std::ops::RangeInclusive<u32>) -> bool {
+        entities.into_iter().all(|i| backend.is_sleeping(Entity::from_raw(i)))
+    }
+
+    fn awake(backend: &NativeBackend, entities: std::ops::RangeInclusive<u32>) -> bool {
+        entities.into_iter().all(|i| !backend.is_sleeping(Entity::from_raw(i)))
+    }
+
+    #[test]
+    fn resting_stacks_sleep_and_wake_as_separate_islands() {
+        let mut backend = NativeBackend::default();
+        ground(&mut backend);
+        stack(&mut backend, -3.0, 1, 3);
+        stack(&mut backend, 3.0, 4, 3);
+        for _ in 0..180 {
+            backend.step(&params(Integrator::default()));
+        }
+        assert!(asleep(&backend, 1..=6));
+
+        // Sleeping bodies don't move at all
+        let states: Vec<Option<BodyState>> = (1..=6).map(|i| backend.body_state(Entity::from_raw(i))).collect();
+        for _ in 0..60 {
+            backend.step(&params(Integrator::default()));
+        }
+        assert_eq!(states, (1..=6).map(|i| backend.body_state(Entity::from_raw(i))).collect::<Vec<_>>());
+
+        // Nudging the top of one stack wakes all of it, but not the other one on the same ground
+        let mut top = backend.body_state(Entity::from_raw(3)).unwrap();
+        top.linvel = Vec3::X * 0.5;
+        backend.set_state(Entity::from_raw(3), &top);
+        backend.step(&params(Integrator::default()));
+        assert!(awake(&backend, 1..=3));
+        assert!(asleep(&backend, 4..=6));
+
+        for _ in 0..300 {
+            backend.step(&params(Integrator::default()));
+        }
+        assert!(asleep(&backend, 1..=6));
+    }
+
+    #[test]
+    fn falling_body_wakes_the_island_it_lands_on() {
+        let mut backend = NativeBackend::default();
+        ground(&mut backend);
+        stack(&mut backend, 0.0, 1, 2);
+        for _ in 0..180 {
+            backend.step(&params(Integrator::default()));
+        }
+        assert!(asleep(&backend, 1..=2));
+
+        backend.set_body(Entity::from_raw(3), &ball(4.0));
+        assert!(asleep(&backend, 1..=2));
+        let mut woken = false;
+        for _ in 0..60 {
+            backend.step(&params(Integrator::default()));
+            woken |= awake(&backend, 1..=2);
+        }
+        assert!(woken);
+        let state = backend.body_state(Entity::from_raw(3)).unwrap();
+        assert!(state.position.y > 2.9, "the ball fell through the stack to {}", state.position.y);
+    }
+
+    #[test]
+    fn sleeping_follows_the_component() {
+        let mut backend = NativeBackend::default();
+        ground(&mut backend);
+        let mut left = ball(1.0);
+        left.state.position.x = -2.0;
+        let mut right = ball(1.0);
+        right.state.position.x = 2.0;
+        backend.set_body(Entity::from_raw(1), &left);
+        backend.set_body(Entity::from_raw(2), &right);
+        backend.set_sleeping(Entity::from_raw(2), &Sleeping::disabled());
+        for _ in 0..180 {
+            backend.step(&params(Integrator::default()));
+        }
+        assert!(backend.is_sleeping(Entity::from_raw(1)));
+        assert!(!backend.is_sleeping(Entity::from_raw(2)));
+
+        // Like rapier, a body can be put to sleep or woken directly
+        backend.set_sleeping(Entity::from_raw(1), &Sleeping::default());
+        assert!(!backend.is_sleeping(Entity::from_raw(1)));
+        backend.set_sleeping(Entity::from_raw(2), &Sleeping { sleeping: true, ..Sleeping::disabled() });
+        assert!(backend.is_sleeping(Entity::from_raw(2)));
+    }
+
+    // A ground box with `count` balls above it, in layers of a 10 by 10 grid
+    fn pile(broad_phase: BroadPhaseKind, count: u32) -> NativeBackend {
+        let mut backend = NativeBackend::default();
+        backend.set_broad_phase(broad_phase);
+        let mut ground = ball(0.0);
+        ground.body_type = RigidBody::Fixed;
+        ground.collider.as_mut().unwrap().shape = Collider::cuboid(20.0, 0.5, 20.0);
+        backend.set_body(Entity::from_raw(0), &ground);
+        for i in 0..count {
+            let mut desc = ball(0.0);
+            desc.state.position = Vec3::new((i % 10) as f32 * 1.1 - 5.0, 2.0 + (i / 100) as f32 * 1.1, (i / 10 % 10) as f32 * 1.1 - 5.0);
+            backend.set_body(Entity::from_raw(i + 1), &desc);
+        }
+        backend
+    }
+
+    #[test]
+    fn broad_phases_give_the_same_simulation() {
+        let [mut sweep, mut tree] = BroadPhaseKind::ALL.map(|kind| pile(kind, 100));
+        for _ in 0..120 {
+            sweep.step(&params(Integrator::default()));
+            tree.step(&params(Integrator::default()));
+        }
+        assert_eq!(sweep.pairs, tree.pairs);
+        for i in 1..=100 {
+            let entity = Entity::from_raw(i);
+            assert_eq!(sweep.body_state(entity), tree.body_state(entity));
+        }
+    }
+
+    // Both broad phases in a stepping world, where thousands of balls fall onto the ground and
+    // pile up. The broad phase is timed on its own before each step, which then finds nothing
+    // left to update.
+    // `cargo test --release native_benchmark -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn native_benchmark() {
+        const STEPS: u32 = 120;
+        for count in [1000, 2000, 4000] {
+            let mut pairs = Vec::new();
+            for kind in BroadPhaseKind::ALL {
+                let mut backend = pile(kind, count);
+                let (mut broad_phase, mut total) = (std::time::Duration::ZERO, std::time::Duration::ZERO);
+                for _ in 0..STEPS {
+                    let start = std::time::Instant::now();
+                    backend.update_broad_phase();
+                    broad_phase += start.elapsed();
+                    backend.step(&params(Integrator::default()));
+                    total += start.elapsed();
+                }
+                let per_step = |duration: std::time::Duration| duration.as_secs_f64() * 1000.0 / STEPS as f64;
+                println!(
+                    "{count:>5} balls {:>18}: broad phase {:>6.3} ms of {:>7.2} ms per step, {} pairs",
+                    kind.label(),
+                    per_step(broad_phase),
+                    per_step(total),
+                    backend.pairs.len(),
+                );
+                pairs.push(backend.pairs);
+            }
+            assert!(pairs.windows(2).all(|pairs| pairs[0] == pairs[1]), "the broad phases found different pairs");
+        }
+    }
+}
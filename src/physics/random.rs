@@ -0,0 +1,29 @@
+use bevy::prelude::*;
+
+// Small deterministic generator for the tests, so failures can be reproduced
+pub struct Random(pub u64);
+
+impl Random {
+    // Between 0 and 1
+    pub fn next(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    // Each coordinate between 0 and `extent`
+    pub fn vec3(&mut self, extent: f32) -> Vec3 {
+        Vec3::new(self.next(), self.next(), self.next()) * extent
+    }
+
+    // Each coordinate between -1 and 1
+    pub fn signed(&mut self) -> Vec3 {
+        self.vec3(2.0) - Vec3::ONE
+    }
+
+    pub fn rotation(&mut self) -> Quat {
+        let axis = self.signed().try_normalize().unwrap_or(Vec3::Y);
+        Quat::from_axis_angle(axis, self.next() * std::f32::consts::TAU)
+    }
+}
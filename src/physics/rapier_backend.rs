@@ -0,0 +1,264 @@
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use bevy_rapier3d::prelude::*;
+use bevy_rapier3d::rapier::prelude::{
+    CCDSolver, ColliderBuilder, ColliderHandle, ColliderMassProps, ColliderSet, ImpulseJointHandle, ImpulseJointSet,
+    IntegrationParameters, IslandManager, MultibodyJointSet, NarrowPhase, PhysicsPipeline, QueryPipeline,
+    RigidBodyBuilder, RigidBodyHandle, RigidBodySet, RigidBodyType,
+};
+use bevy_rapier3d::rapier::prelude::{BroadPhase, Isometry, QueryFilter as RawQueryFilter, Ray as RawRay};
+
+use super::broadphase::{Aabb, DebugBox};
+use super::{BodyDesc, BodyState, JointDesc, PhysicsBackend, StepParams};
+
+// Rapier run on its own sets, separate from the ones bevy_rapier keeps for scene queries
+#[derive(Default)]
+pub struct RapierBackend {
+    bodies: RigidBodySet,
+    colliders: ColliderSet,
+    impulse_joints: ImpulseJointSet,
+    multibody_joints: MultibodyJointSet,
+    islands: IslandManager,
+    broad_phase: BroadPhase,
+    narrow_phase: NarrowPhase,
+    ccd_solver: CCDSolver,
+    query_pipeline: QueryPipeline,
+    pipeline: PhysicsPipeline,
+    handles: HashMap<Entity, (RigidBodyHandle, Option<ColliderHandle>)>,
+    // Joints are kept while one of their bodies is missing and attached once both exist
+    joints: HashMap<Entity, (JointDesc, Option<ImpulseJointHandle>)>,
+    // Bodies moved from outside since the query pipeline was last updated
+    moved: Vec<RigidBodyHandle>,
+}
+
+fn isometry(state: &BodyState) -> Isometry<f32> {
+    Isometry::from_parts(state.position.into(), state.rotation.into())
+}
+
+fn mass_props(mass: &ColliderMassProperties) -> ColliderMassProps {
+    match mass {
+        ColliderMassProperties::Density(density) => ColliderMassProps::Density(*density),
+        ColliderMassProperties::Mass(mass) => ColliderMassProps::Mass(*mass),
+        ColliderMassProperties::MassProperties(props) => ColliderMassProps::MassProperties(Box::new(props.into_rapier(1.0))),
+    }
+}
+
+impl RapierBackend {
+    fn attach_joints(&mut self) {
+        for (desc, handle) in self.joints.values_mut() {
+            if handle.is_some() {
+                continue;
+            }
+            if let (Some(&(body1, _)), Some(&(body2, _))) = (self.handles.get(&desc.body1), self.handles.get(&desc.body2)) {
+                *handle = Some(self.impulse_joints.insert(body1, body2, desc.joint.raw, true));
+            }
+        }
+    }
+}
+
+impl PhysicsBackend for RapierBackend {
+    fn set_body(&mut self, entity: Entity, desc: &BodyDesc) {
+        let body_type = RigidBodyType::from(desc.body_type);
+        let handle = match self.handles.get(&entity) {
+            Some(&(handle, _)) => handle,
+            None => {
+                let body = RigidBodyBuilder::new(body_type).user_data(entity.to_bits() as u128).build();
+                let handle = self.bodies.insert(body);
+                self.handles.insert(entity, (handle, None));
+                handle
+            }
+        };
+
+        let body = &mut self.bodies[handle];
+        body.set_body_type(body_type, true);
+        body.set_position(isometry(&desc.state), true);
+        body.set_linvel(desc.state.linvel.into(), true);
+        body.set_angvel(desc.state.angvel.into(), true);
+        body.set_linear_damping(desc.damping.linear_damping);
+        body.set_angular_damping(desc.damping.angular_damping);
+        body.enable_ccd(desc.ccd);
+        body.reset_forces(false);
+        body.reset_torques(false);
+        body.add_force(desc.force.force.into(), true);
+        body.add_torque(desc.force.torque.into(), true);
+
+        // The collider is rebuilt, its properties only change on edits
+        if let Some(collider) = self.handles.get_mut(&entity).and_then(|(_, collider)| collider.take()) {
+            self.colliders.remove(collider, &mut self.islands, &mut self.bodies, true);
+        }
+        if let Some(collider) = &desc.collider {
+            let mut builder = ColliderBuilder::new(collider.shape.raw.clone())
+                .friction(collider.friction.coefficient)
+                .friction_combine_rule(collider.friction.combine_rule.into())
+                .restitution(collider.restitution.coefficient)
+                .restitution_combine_rule(collider.restitution.combine_rule.into())
+                .sensor(collider.sensor)
+                .user_data(entity.to_bits() as u128);
+            builder.mass_properties = mass_props(&collider.mass);
+            let collider = self.colliders.insert_with_parent(builder, handle, &mut self.bodies);
+            self.handles.insert(entity, (handle, Some(collider)));
+        }
+
+        self.moved.push(handle);
+        self.attach_joints();
+    }
+
+    fn set_state(&mut self, entity: Entity, state: &BodyState) {
+        let Some(&(handle, _)) = self.handles.get(&entity) else {
+            return;
+        };
+        let body = &mut self.bodies[handle];
+        // Unchanged values are skipped so resting bodies can fall asleep
+        let position = isometry(state);
+        if *body.position() != position {
+            if body.is_kinematic() {
+                body.set_next_kinematic_position(position);
+            } else {
+                body.set_position(position, true);
+            }
+            self.moved.push(handle);
+        }
+        if *body.linvel() != state.linvel.into() {
+            body.set_linvel(state.linvel.into(), true);
+        }
+        if *body.angvel() != state.angvel.into() {
+            body.set_angvel(state.angvel.into(), true);
+        }
+    }
+
+    fn remove_body(&mut self, entity: Entity) {
+        let Some((handle, _)) = self.handles.remove(&entity) else {
+            return;
+        };
+        // Rapier drops the joints of removed bodies too
+        self.bodies.remove(
+            handle,
+            &mut self.islands,
+            &mut self.colliders,
+            &mut self.impulse_joints,
+            &mut self.multibody_joints,
+            true,
+        );
+        for (desc, joint) in self.joints.values_mut() {
+            if desc.body1 == entity || desc.body2 == entity {
+                *joint = None;
+            }
+        }
+    }
+
+    fn set_joint(&mut self, entity: Entity, desc: &JointDesc) {
+        self.remove_joint(entity);
+        self.joints.insert(entity, (*desc, None));
+        self.attach_joints();
+    }
+
+    fn remove_joint(&mut self, entity: Entity) {
+        if let Some((_, Some(handle))) = self.joints.remove(&entity) {
+            self.impulse_joints.remove(handle, true);
+        }
+    }
+
+    fn update_queries(&mut self) {
+        // Colliders follow their bodies only when the pipeline steps
+        for handle in self.moved.drain(..) {
+            let Some(body) = self.bodies.get(handle) else {
+                continue;
+            };
+            for &collider in body.colliders() {
+                if let Some(collider) = self.colliders.get_mut(collider) {
+                    let offset = collider.position_wrt_parent().copied().unwrap_or_default();
+                    collider.set_position(body.position() * offset);
+                }
+            }
+        }
+        self.query_pipeline.update(&self.bodies, &self.colliders);
+    }
+
+    fn step(&mut self, params: &StepParams) {
+        let integration_parameters = IntegrationParameters {
+            dt: params.dt,
+            max_velocity_iterations: params.velocity_iterations,
+            max_velocity_friction_iterations: params.friction_iterations,
+            max_stabilization_iterations: params.stabilization_iterations,
+            max_ccd_substeps: params.max_ccd_substeps,
+            ..default()
+        };
+        self.pipeline.step(
+            &params.gravity.into(),
+            &integration_parameters,
+            &mut self.islands,
+            &mut self.broad_phase,
+            &mut self.narrow_phase,
+            &mut self.bodies,
+            &mut self.colliders,
+            &mut self.impulse_joints,
+            &mut self.multibody_joints,
+            &mut self.ccd_solver,
+            Some(&mut self.query_pipeline),
+            &(),
+            &(),
+        );
+        self.moved.clear();
+    }
+
+    // What bevy_rapier does with a changed `Sleeping`
+    fn set_sleeping(&mut self, entity: Entity, sleeping: &Sleeping) {
+        let Some(&(handle, _)) = self.handles.get(&entity) else {
+            return;
+        };
+        let body = &mut self.bodies[handle];
+        let activation = body.activation_mut();
+        activation.linear_threshold = sleeping.linear_threshold;
+        activation.angular_threshold = sleeping.angular_threshold;
+        if !sleeping.sleeping && activation.sleeping {
+            body.wake_up(true);
+        } else if sleeping.sleeping && !activation.sleeping {
+            body.sleep();
+        }
+    }
+
+    fn is_sleeping(&self, entity: Entity) -> bool {
+        self.handles.get(&entity).and_then(|&(handle, _)| self.bodies.get(handle)).is_some_and(|body| body.is_sleeping())
+    }
+
+    fn mass_properties(&self, entity: Entity) -> Option<MassProperties> {
+        let &(handle, _) = self.handles.get(&entity)?;
+        let body = self.bodies.get(handle)?;
+        Some(MassProperties::from_rapier(body.mass_properties().local_mprops, 1.0))
+    }
+
+    fn body_state(&self, entity: Entity) -> Option<BodyState> {
+        let &(handle, _) = self.handles.get(&entity)?;
+        let body = self.bodies.get(handle)?;
+        Some(BodyState {
+            position: body.position().translation.vector.into(),
+            rotation: body.position().rotation.into(),
+            linvel: (*body.linvel()).into(),
+            angvel: (*body.angvel()).into(),
+        })
+    }
+
+    fn cast_ray(&self, ray: Ray, max_distance: f32, filter: &dyn Fn(Entity) -> bool) -> Option<(Entity, f32)> {
+        let entity = |collider: &bevy_rapier3d::rapier::prelude::Collider| Entity::from_bits(collider.user_data as u64);
+        let predicate = |_, collider: &bevy_rapier3d::rapier::prelude::Collider| filter(entity(collider));
+        let (handle, distance) = self.query_pipeline.cast_ray(
+            &self.bodies,
+            &self.colliders,
+            &RawRay::new(ray.origin.into(), ray.direction.into()),
+            max_distance,
+            true,
+            RawQueryFilter::default().predicate(&predicate),
+        )?;
+        Some((entity(&self.colliders[handle]), distance))
+    }
+
+    // Rapier keeps a contact pair for every overlap its broad phase finds
+    fn debug_boxes(&self, visit: &mut dyn FnMut(&Aabb, DebugBox)) {
+        for (handle, collider) in self.colliders.iter() {
+            let aabb = collider.compute_aabb();
+            let paired = self.narrow_phase.contacts_with(handle).next().is_some()
+                || self.narrow_phase.intersections_with(handle).next().is_some();
+            visit(&Aabb::new(aabb.mins.into(), aabb.maxs.into()), DebugBox::Proxy { paired });
+        }
+    }
+}
@@ -0,0 +1,227 @@
+use std::any::Any;
+
+use bevy::ecs::query::WorldQuery;
+use bevy::ecs::world::EntityRef;
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use crate::history::{EditCommand, EntityRemap};
+use crate::mesh_import::{mesh_collider, MeshColliderKind};
+
+// Every physics component an editor object can carry. Each one is optional so that visual-only
+// objects, and whatever setup undo has to restore, can be described too.
+#[derive(Clone, Default)]
+pub struct PhysicsComponents {
+    pub rigid_body: Option<RigidBody>,
+    pub collider: Option<Collider>,
+    pub mass: Option<ColliderMassProperties>,
+    pub damping: Option<Damping>,
+    pub restitution: Option<Restitution>,
+    pub friction: Option<Friction>,
+    pub velocity: Option<Velocity>,
+    pub sensor: Option<Sensor>,
+    pub sleeping: Option<Sleeping>,
+}
+
+#[derive(WorldQuery)]
+pub struct PhysicsQuery {
+    rigid_body: Option<&'static RigidBody>,
+    collider: Option<&'static Collider>,
+    mass: Option<&'static ColliderMassProperties>,
+    damping: Option<&'static Damping>,
+    restitution: Option<&'static Restitution>,
+    friction: Option<&'static Friction>,
+    velocity: Option<&'static Velocity>,
+    sensor: Option<&'static Sensor>,
+    sleeping: Option<&'static Sleeping>,
+}
+
+impl From<PhysicsQueryItem<'_>> for PhysicsComponents {
+    fn from(item: PhysicsQueryItem) -> Self {
+        Self {
+            rigid_body: item.rigid_body.copied(),
+            collider: item.collider.cloned(),
+            mass: item.mass.copied(),
+            damping: item.damping.copied(),
+            restitution: item.restitution.copied(),
+            friction: item.friction.copied(),
+            velocity: item.velocity.copied(),
+            sensor: item.sensor.copied(),
+            sleeping: item.sleeping.copied(),
+        }
+    }
+}
+
+impl PhysicsComponents {
+    // The dynamic body new objects are spawned with
+    pub fn defaults(collider: Collider) -> Self {
+        Self {
+            rigid_body: Some(RigidBody::Dynamic),
+            collider: Some(collider),
+            mass: Some(ColliderMassProperties::Mass(1.0)),
+            damping: Some(Damping {
+                linear_damping: 0.5,
+                angular_damping: 0.5,
+            }),
+            restitution: Some(Restitution::coefficient(0.7)),
+            friction: Some(Friction::coefficient(0.5)),
+            velocity: Some(Velocity::default()),
+            sensor: None,
+            sleeping: Some(Sleeping::default()),
+        }
+    }
+
+    pub fn capture(entity: EntityRef) -> Self {
+        Self {
+            rigid_body: entity.get::<RigidBody>().copied(),
+            collider: entity.get::<Collider>().cloned(),
+            mass: entity.get::<ColliderMassProperties>().copied(),
+            damping: entity.get::<Damping>().copied(),
+            restitution: entity.get::<Restitution>().copied(),
+            friction: entity.get::<Friction>().copied(),
+            velocity: entity.get::<Velocity>().copied(),
+            sensor: entity.get::<Sensor>().copied(),
+            sleeping: entity.get::<Sleeping>().copied(),
+        }
+    }
+
+    // Fills in every component missing here from `other`
+    pub fn or(self, other: Self) -> Self {
+        Self {
+            rigid_body: self.rigid_body.or(other.rigid_body),
+            collider: self.collider.or(other.collider),
+            mass: self.mass.or(other.mass),
+            damping: self.damping.or(other.damping),
+            restitution: self.restitution.or(other.restitution),
+            friction: self.friction.or(other.friction),
+            velocity: self.velocity.or(other.velocity),
+            sensor: self.sensor.or(other.sensor),
+            sleeping: self.sleeping.or(other.sleeping),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rigid_body.is_none()
+            && self.collider.is_none()
+            && self.mass.is_none()
+            && self.damping.is_none()
+            && self.restitution.is_none()
+            && self.friction.is_none()
+            && self.velocity.is_none()
+            && self.sensor.is_none()
+            && self.sleeping.is_none()
+    }
+
+    // Makes the entity carry exactly these components, inserting and removing as needed
+    pub fn apply(&self, entity: &mut EntityWorldMut) {
+        fn set<C: Component + Clone>(entity: &mut EntityWorldMut, component: &Option<C>) {
+            match component {
+                Some(component) => {
+                    entity.insert(component.clone());
+                }
+                None => {
+                    entity.remove::<C>();
+                }
+            }
+        }
+        set(entity, &self.rigid_body);
+        set(entity, &self.collider);
+        set(entity, &self.mass);
+        set(entity, &self.damping);
+        set(entity, &self.restitution);
+        set(entity, &self.friction);
+        set(entity, &self.velocity);
+        set(entity, &self.sensor);
+        set(entity, &self.sleeping);
+    }
+
+    // `apply` once the commands are flushed
+    pub fn apply_deferred(self, commands: &mut Commands, entity: Entity) {
+        commands.entity(entity).add(move |mut entity: EntityWorldMut| self.apply(&mut entity));
+    }
+}
+
+// How Add Physics shapes the collider around an object's mesh
+#[derive(Default, PartialEq, Clone, Copy, Debug)]
+pub enum ColliderFit {
+    #[default]
+    Aabb,
+    ConvexHull,
+}
+
+impl ColliderFit {
+    pub const ALL: [ColliderFit; 2] = [ColliderFit::Aabb, ColliderFit::ConvexHull];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ColliderFit::Aabb => "Bounding Box",
+            ColliderFit::ConvexHull => "Convex Hull",
+        }
+    }
+}
+
+// A collider wrapped around the mesh, or None when the mesh has no usable geometry
+pub fn fitted_collider(mesh: &Mesh, fit: ColliderFit) -> Option<Collider> {
+    match fit {
+        ColliderFit::Aabb => {
+            let aabb = mesh.compute_aabb()?;
+            let half_extents = Vec3::from(aabb.half_extents).max(Vec3::splat(0.01));
+            let cuboid = Collider::cuboid(half_extents.x, half_extents.y, half_extents.z);
+            let center = Vec3::from(aabb.center);
+            if center.length_squared() < 1e-8 {
+                Some(cuboid)
+            } else {
+                // Meshes that aren't centered on their origin need the box moved onto them
+                Some(Collider::compound(vec![(center, Quat::IDENTITY, cuboid)]))
+            }
+        }
+        ColliderFit::ConvexHull => mesh_collider(mesh, MeshColliderKind::ConvexHull).ok(),
+    }
+}
+
+// Swapping the whole physics setup of one entity, as done by Add / Remove Physics
+pub struct PhysicsChange {
+    entity: Entity,
+    before: PhysicsComponents,
+    after: PhysicsComponents,
+}
+
+impl PhysicsChange {
+    pub fn new(entity: Entity, before: PhysicsComponents, after: PhysicsComponents) -> Self {
+        Self { entity, before, after }
+    }
+}
+
+impl EditCommand for PhysicsChange {
+    fn label(&self) -> String {
+        if self.after.is_empty() {
+            "Remove Physics".to_string()
+        } else {
+            "Add Physics".to_string()
+        }
+    }
+
+    fn undo(&mut self, world: &mut World) -> EntityRemap {
+        if let Some(mut entity) = world.get_entity_mut(self.entity) {
+            self.before.apply(&mut entity);
+        }
+        Vec::new()
+    }
+
+    fn redo(&mut self, world: &mut World) -> EntityRemap {
+        if let Some(mut entity) = world.get_entity_mut(self.entity) {
+            self.after.apply(&mut entity);
+        }
+        Vec::new()
+    }
+
+    fn remap_entity(&mut self, from: Entity, to: Entity) {
+        if self.entity == from {
+            self.entity = to;
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
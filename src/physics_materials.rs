@@ -0,0 +1,623 @@
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use bevy::prelude::*;
+use bevy::render::primitives::Aabb;
+use bevy_egui::{egui, EguiContexts};
+use bevy_rapier3d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::history::{CommandGroup, ComponentChange};
+use crate::ui::{Selectable, UiState};
+
+pub struct PhysicsMaterialsPlugin;
+
+impl Plugin for PhysicsMaterialsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<OpenMaterialLibrary>()
+           .insert_resource(MaterialLibrary::load(Path::new(LIBRARY_PATH)))
+           .add_systems(Update, (
+               open_material_library,
+               material_library_window.after(open_material_library),
+               save_material_library.after(material_library_window),
+               draw_material_hints,
+           ));
+    }
+}
+
+// User-defined materials are kept in the working directory, built-ins are never written
+const LIBRARY_PATH: &str = "physics_materials.ron";
+// Bump this whenever the library file layout changes
+const LIBRARY_VERSION: u32 = 1;
+
+// Sent by the Edit menu in the toolbar
+#[derive(Event)]
+pub struct OpenMaterialLibrary;
+
+// Serializable mirror of rapier's `CoefficientCombineRule`. When two colliders disagree, the rule
+// later in this list wins.
+#[derive(Serialize, Deserialize, Default, PartialEq, Clone, Copy, Debug)]
+pub enum CombineRule {
+    #[default]
+    Average,
+    Min,
+    Multiply,
+    Max,
+}
+
+impl CombineRule {
+    pub const ALL: [CombineRule; 4] = [
+        CombineRule::Average,
+        CombineRule::Min,
+        CombineRule::Multiply,
+        CombineRule::Max,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            CombineRule::Average => "Average",
+            CombineRule::Min => "Min",
+            CombineRule::Multiply => "Multiply",
+            CombineRule::Max => "Max",
+        }
+    }
+}
+
+impl From<CoefficientCombineRule> for CombineRule {
+    fn from(rule: CoefficientCombineRule) -> Self {
+        match rule {
+            CoefficientCombineRule::Average => CombineRule::Average,
+            CoefficientCombineRule::Min => CombineRule::Min,
+            CoefficientCombineRule::Multiply => CombineRule::Multiply,
+            CoefficientCombineRule::Max => CombineRule::Max,
+        }
+    }
+}
+
+impl From<CombineRule> for CoefficientCombineRule {
+    fn from(rule: CombineRule) -> Self {
+        match rule {
+            CombineRule::Average => CoefficientCombineRule::Average,
+            CombineRule::Min => CoefficientCombineRule::Min,
+            CombineRule::Multiply => CoefficientCombineRule::Multiply,
+            CombineRule::Max => CoefficientCombineRule::Max,
+        }
+    }
+}
+
+pub fn combine_rule_editor(ui: &mut egui::Ui, id_source: impl std::hash::Hash, rule: &mut CombineRule) {
+    egui::ComboBox::from_id_source(id_source)
+        .selected_text(rule.label())
+        .show_ui(ui, |ui| {
+            for option in CombineRule::ALL {
+                ui.selectable_value(rule, option, option.label());
+            }
+        });
+}
+
+// Density, friction and restitution that are assigned together, plus the colour objects with this
+// material are outlined in
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct PhysicsMaterial {
+    pub name: String,
+    pub density: f32,
+    pub friction: f32,
+    pub friction_combine: CombineRule,
+    pub restitution: f32,
+    pub restitution_combine: CombineRule,
+    pub color: [f32; 4],
+}
+
+impl PhysicsMaterial {
+    pub fn friction(&self) -> Friction {
+        Friction {
+            coefficient: self.friction,
+            combine_rule: self.friction_combine.into(),
+        }
+    }
+
+    pub fn restitution(&self) -> Restitution {
+        Restitution {
+            coefficient: self.restitution,
+            combine_rule: self.restitution_combine.into(),
+        }
+    }
+
+    pub fn mass(&self) -> ColliderMassProperties {
+        ColliderMassProperties::Density(self.density)
+    }
+
+    pub fn color(&self) -> Color {
+        Color::rgba(self.color[0], self.color[1], self.color[2], self.color[3])
+    }
+
+    // Whether the components still hold this material's values, edits in the Inspector break the match
+    pub fn matches(&self, friction: Option<&Friction>, restitution: Option<&Restitution>, mass: Option<&ColliderMassProperties>) -> bool {
+        friction.is_none_or(|friction| *friction == self.friction())
+            && restitution.is_none_or(|restitution| *restitution == self.restitution())
+            && mass.is_none_or(|mass| *mass == self.mass())
+    }
+
+    // Sets the material's components on `entity` and records the changes in `group`. Components the
+    // object doesn't carry are left out.
+    pub fn assign(
+        &self,
+        commands: &mut Commands,
+        group: &mut CommandGroup,
+        entity: Entity,
+        before: (Option<Friction>, Option<Restitution>, Option<ColliderMassProperties>),
+        name: Option<&PhysicsMaterialName>,
+    ) {
+        let (friction, restitution, mass) = before;
+        if let Some(friction) = friction {
+            commands.entity(entity).insert(self.friction());
+            group.add(ComponentChange::new(entity, friction, self.friction()));
+        }
+        if let Some(restitution) = restitution {
+            commands.entity(entity).insert(self.restitution());
+            group.add(ComponentChange::new(entity, restitution, self.restitution()));
+        }
+        if let Some(mass) = mass {
+            commands.entity(entity).insert(self.mass());
+            group.add(ComponentChange::new(entity, mass, self.mass()));
+        }
+        let after = PhysicsMaterialName(Some(self.name.clone()));
+        commands.entity(entity).insert(after.clone());
+        group.add(ComponentChange::new(entity, name.cloned().unwrap_or_default(), after));
+    }
+}
+
+// Densities in kg/m³
+fn built_in_materials() -> Vec<PhysicsMaterial> {
+    let material = |name: &str, density, friction, friction_combine, restitution, restitution_combine, color: Color| {
+        PhysicsMaterial {
+            name: name.to_string(),
+            density,
+            friction,
+            friction_combine,
+            restitution,
+            restitution_combine,
+            color: color.as_rgba_f32(),
+        }
+    };
+    vec![
+        material("Rubber", 1100.0, 0.9, CombineRule::Max, 0.8, CombineRule::Max, Color::rgb(0.9, 0.3, 0.2)),
+        material("Ice", 917.0, 0.02, CombineRule::Min, 0.1, CombineRule::Average, Color::rgb(0.6, 0.9, 1.0)),
+        material("Wood", 700.0, 0.5, CombineRule::Average, 0.3, CombineRule::Average, Color::rgb(0.7, 0.5, 0.25)),
+        material("Steel", 7850.0, 0.4, CombineRule::Average, 0.5, CombineRule::Average, Color::rgb(0.6, 0.65, 0.75)),
+        material("Concrete", 2400.0, 0.7, CombineRule::Average, 0.1, CombineRule::Min, Color::rgb(0.55, 0.55, 0.5)),
+    ]
+}
+
+// The library material last assigned to an object, None or missing for hand-tuned values
+#[derive(Component, Default, PartialEq, Clone, Debug)]
+pub struct PhysicsMaterialName(pub Option<String>);
+
+#[derive(Debug)]
+pub enum MaterialLibraryError {
+    Io(std::io::Error),
+    Parse(ron::error::SpannedError),
+    Serialize(ron::Error),
+    UnsupportedVersion(u32),
+}
+
+impl fmt::Display for MaterialLibraryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MaterialLibraryError::Io(err) => write!(f, "could not access material library: {}", err),
+            MaterialLibraryError::Parse(err) => write!(f, "material library is malformed: {}", err),
+            MaterialLibraryError::Serialize(err) => write!(f, "could not serialize material library: {}", err),
+            MaterialLibraryError::UnsupportedVersion(version) => write!(
+                f,
+                "material library version {} is not supported (this build reads version {})",
+                version, LIBRARY_VERSION
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MaterialLibraryError {}
+
+impl From<std::io::Error> for MaterialLibraryError {
+    fn from(err: std::io::Error) -> Self {
+        MaterialLibraryError::Io(err)
+    }
+}
+
+impl From<ron::error::SpannedError> for MaterialLibraryError {
+    fn from(err: ron::error::SpannedError) -> Self {
+        MaterialLibraryError::Parse(err)
+    }
+}
+
+impl From<ron::Error> for MaterialLibraryError {
+    fn from(err: ron::Error) -> Self {
+        MaterialLibraryError::Serialize(err)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct LibraryFile {
+    version: u32,
+    materials: Vec<PhysicsMaterial>,
+}
+
+#[derive(Resource)]
+pub struct MaterialLibrary {
+    built_in: Vec<PhysicsMaterial>,
+    custom: Vec<PhysicsMaterial>,
+    // Outline objects in the colour of their material
+    pub show_hints: bool,
+    open: bool,
+    // Custom materials changed since they were last written to disk
+    dirty: bool,
+    // The file on disk couldn't be read, nothing is written over it until it is reloaded or reset
+    unreadable: bool,
+    error: Option<String>,
+}
+
+impl MaterialLibrary {
+    fn load(path: &Path) -> Self {
+        let mut library = Self {
+            built_in: built_in_materials(),
+            custom: Vec::new(),
+            show_hints: true,
+            open: false,
+            dirty: false,
+            unreadable: false,
+            error: None,
+        };
+        library.reload(path);
+        library
+    }
+
+    // Replaces the custom materials with the ones on disk
+    fn reload(&mut self, path: &Path) {
+        self.custom.clear();
+        self.dirty = false;
+        self.unreadable = false;
+        self.error = None;
+        if path.exists() {
+            match read_library(path) {
+                Ok(custom) => self.custom = custom,
+                Err(err) => {
+                    error!("Failed to load {}: {}", path.display(), err);
+                    self.unreadable = true;
+                    self.error = Some(format!("{}. Changes are not saved until the file is fixed or reset.", err));
+                }
+            }
+        }
+    }
+
+    // Moves an unreadable file aside so the custom materials can be saved in its place
+    fn reset(&mut self, path: &Path) {
+        let backup = path.with_extension("ron.bak");
+        match fs::rename(path, &backup) {
+            Ok(()) => {
+                info!("Moved unreadable {} to {}", path.display(), backup.display());
+                self.unreadable = false;
+                self.dirty = true;
+                self.error = None;
+            }
+            Err(err) => {
+                error!("Failed to move {} aside: {}", path.display(), err);
+                self.error = Some(MaterialLibraryError::from(err).to_string());
+            }
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &PhysicsMaterial> {
+        self.built_in.iter().chain(&self.custom)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&PhysicsMaterial> {
+        self.iter().find(|material| material.name == name)
+    }
+
+    fn unique_name(&self, base: &str) -> String {
+        (1..)
+            .map(|index| if index == 1 { base.to_string() } else { format!("{} {}", base, index) })
+            .find(|name| self.get(name).is_none())
+            .unwrap_or_default()
+    }
+
+    // What the Inspector shows for an object's material
+    pub fn describe(
+        &self,
+        name: Option<&PhysicsMaterialName>,
+        friction: Option<&Friction>,
+        restitution: Option<&Restitution>,
+        mass: Option<&ColliderMassProperties>,
+    ) -> (String, Option<Color>) {
+        let Some(name) = name.and_then(|name| name.0.as_deref()) else {
+            return ("Custom".to_string(), None);
+        };
+        match self.get(name) {
+            Some(material) if material.matches(friction, restitution, mass) => (name.to_string(), Some(material.color())),
+            Some(material) => (format!("{} (modified)", name), Some(material.color())),
+            None => (format!("{} (missing)", name), None),
+        }
+    }
+}
+
+fn read_library(path: &Path) -> Result<Vec<PhysicsMaterial>, MaterialLibraryError> {
+    let file: LibraryFile = ron::from_str(&fs::read_to_string(path)?)?;
+    if file.version != LIBRARY_VERSION {
+        return Err(MaterialLibraryError::UnsupportedVersion(file.version));
+    }
+    Ok(file.materials)
+}
+
+fn write_library(path: &Path, materials: &[PhysicsMaterial]) -> Result<(), MaterialLibraryError> {
+    let file = LibraryFile {
+        version: LIBRARY_VERSION,
+        materials: materials.to_vec(),
+    };
+    fs::write(path, ron::ser::to_string_pretty(&file, ron::ser::PrettyConfig::default())?)?;
+    Ok(())
+}
+
+fn color_swatch(ui: &mut egui::Ui, color: Color) {
+    let [r, g, b, _] = color.as_rgba_f32();
+    let (rect, _) = ui.allocate_exact_size(egui::vec2(12.0, 12.0), egui::Sense::hover());
+    ui.painter().rect_filled(rect, 2.0, egui::Rgba::from_rgb(r, g, b));
+}
+
+// "Material" picker of the Inspector's Physics section, returns the material chosen this frame
+pub fn physics_material_picker(ui: &mut egui::Ui, library: &MaterialLibrary, current: (String, Option<Color>)) -> Option<PhysicsMaterial> {
+    let (label, color) = current;
+    let mut chosen = None;
+    ui.horizontal(|ui| {
+        ui.label("Material");
+        if let Some(color) = color {
+            color_swatch(ui, color);
+        }
+        egui::ComboBox::from_id_source("physics_material")
+            .selected_text(label)
+            .show_ui(ui, |ui| {
+                for material in library.iter() {
+                    ui.horizontal(|ui| {
+                        color_swatch(ui, material.color());
+                        if ui.selectable_label(false, &material.name).clicked() {
+                            chosen = Some(material.clone());
+                        }
+                    });
+                }
+            });
+    });
+    chosen
+}
+
+fn open_material_library(
+    mut events: EventReader<OpenMaterialLibrary>,
+    mut library: ResMut<MaterialLibrary>,
+) {
+    for _ in events.read() {
+        library.open = true;
+    }
+}
+
+fn material_editor(ui: &mut egui::Ui, index: usize, material: &mut PhysicsMaterial) {
+    ui.horizontal(|ui| {
+        ui.label("Name");
+        ui.text_edit_singleline(&mut material.name);
+    });
+    ui.add(egui::DragValue::new(&mut material.density)
+        .prefix("Density: ")
+        .suffix(" kg/m³")
+        .speed(1.0)
+        .clamp_range(0.001..=f32::MAX));
+    ui.horizontal(|ui| {
+        ui.add(egui::Slider::new(&mut material.friction, 0.0..=1.0).text("Friction"));
+        combine_rule_editor(ui, ("friction_combine", index), &mut material.friction_combine);
+    });
+    ui.horizontal(|ui| {
+        ui.add(egui::Slider::new(&mut material.restitution, 0.0..=1.0).text("Restitution"));
+        combine_rule_editor(ui, ("restitution_combine", index), &mut material.restitution_combine);
+    });
+    ui.horizontal(|ui| {
+        ui.label("Hint colour");
+        ui.color_edit_button_rgba_unmultiplied(&mut material.color);
+    });
+}
+
+fn material_library_window(
+    mut contexts: EguiContexts,
+    mut library: ResMut<MaterialLibrary>,
+) {
+    if !library.open {
+        return;
+    }
+
+    let library = &mut *library;
+    let mut open = true;
+    let mut duplicate = None;
+    let mut delete = None;
+    let mut reload = false;
+    let mut reset = false;
+    let before = library.custom.clone();
+
+    egui::Window::new("Physics Materials")
+        .open(&mut open)
+        .default_width(320.0)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.checkbox(&mut library.show_hints, "Outline objects in their material's colour");
+            if let Some(error) = &library.error {
+                ui.colored_label(egui::Color32::RED, error);
+            }
+            if library.unreadable {
+                ui.horizontal(|ui| {
+                    if ui.button("⟳ Reload").on_hover_text(format!("Read {} again, discarding changes made since", LIBRARY_PATH)).clicked() {
+                        reload = true;
+                    }
+                    if ui.button("Reset").on_hover_text(format!("Move {} to a .bak file and save the materials listed here", LIBRARY_PATH)).clicked() {
+                        reset = true;
+                    }
+                });
+            }
+            ui.separator();
+
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                ui.label("Built-in");
+                for material in &library.built_in {
+                    ui.horizontal(|ui| {
+                        color_swatch(ui, material.color());
+                        ui.label(&material.name).on_hover_text(format!(
+                            "{} kg/m³, friction {:.2} ({}), restitution {:.2} ({})",
+                            material.density,
+                            material.friction,
+                            material.friction_combine.label(),
+                            material.restitution,
+                            material.restitution_combine.label(),
+                        ));
+                        if ui.small_button("⧉").on_hover_text("Copy into a new material").clicked() {
+                            duplicate = Some(material.clone());
+                        }
+                    });
+                }
+
+                ui.separator();
+                ui.label(format!("Custom (saved to {})", LIBRARY_PATH));
+                for (index, material) in library.custom.iter_mut().enumerate() {
+                    ui.push_id(index, |ui| {
+                        ui.group(|ui| {
+                            material_editor(ui, index, material);
+                            ui.horizontal(|ui| {
+                                if ui.button("⧉ Duplicate").clicked() {
+                                    duplicate = Some(material.clone());
+                                }
+                                if ui.button("🗑 Delete").clicked() {
+                                    delete = Some(index);
+                                }
+                            });
+                        });
+                    });
+                }
+                if ui.button("➕ New Material").clicked() {
+                    duplicate = Some(PhysicsMaterial {
+                        name: "Material".to_string(),
+                        density: 1000.0,
+                        friction: 0.5,
+                        friction_combine: CombineRule::Average,
+                        restitution: 0.3,
+                        restitution_combine: CombineRule::Average,
+                        color: Color::WHITE.as_rgba_f32(),
+                    });
+                }
+            });
+        });
+
+    if let Some(index) = delete {
+        library.custom.remove(index);
+    }
+    if let Some(mut material) = duplicate {
+        material.name = library.unique_name(&material.name);
+        library.custom.push(material);
+    }
+    if library.custom != before {
+        library.dirty = true;
+    }
+    if reload {
+        library.reload(Path::new(LIBRARY_PATH));
+    } else if reset {
+        library.reset(Path::new(LIBRARY_PATH));
+    }
+    library.open = open;
+}
+
+// Written once the pointer is released, so dragging a value doesn't rewrite the file every frame
+fn save_material_library(
+    mut contexts: EguiContexts,
+    mut library: ResMut<MaterialLibrary>,
+) {
+    if !library.dirty || library.unreadable || contexts.ctx_mut().input(|input| input.pointer.any_down()) {
+        return;
+    }
+    library.dirty = false;
+
+    let names: Vec<&str> = library.iter().map(|material| material.name.as_str()).collect();
+    let duplicated = names.iter().enumerate().any(|(index, name)| names[..index].contains(name));
+    if duplicated || names.iter().any(|name| name.trim().is_empty()) {
+        library.error = Some("Material names must be unique and not empty".to_string());
+        return;
+    }
+    library.error = match write_library(Path::new(LIBRARY_PATH), &library.custom) {
+        Ok(()) => None,
+        Err(err) => {
+            error!("Failed to save {}: {}", LIBRARY_PATH, err);
+            Some(err.to_string())
+        }
+    };
+}
+
+// Outlines objects in the colour of their material, selected objects keep their selection outline
+fn draw_material_hints(
+    mut gizmos: Gizmos,
+    ui_state: Res<UiState>,
+    library: Res<MaterialLibrary>,
+    objects: Query<(Entity, &GlobalTransform, &Aabb, &ViewVisibility, &PhysicsMaterialName), With<Selectable>>,
+) {
+    if !library.show_hints {
+        return;
+    }
+    for (entity, transform, aabb, visibility, name) in objects.iter() {
+        if !visibility.get() || ui_state.selection.contains(&entity) {
+            continue;
+        }
+        let Some(material) = name.0.as_deref().and_then(|name| library.get(name)) else {
+            continue;
+        };
+        let local = Transform::from_translation(aabb.center.into())
+            .with_scale(Vec3::from(aabb.half_extents) * 2.0);
+        gizmos.cuboid(transform.mul_transform(local), material.color());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("{}-{}.ron", name, std::process::id()))
+    }
+
+    #[test]
+    fn library_round_trip() {
+        let path = temp_path("material-library-round-trip");
+        let mut materials = built_in_materials();
+        materials[0].name = "Custom Steel".to_string();
+        materials[1].friction_combine = CombineRule::Max;
+        write_library(&path, &materials).expect("library writes");
+        let read = read_library(&path);
+        fs::remove_file(&path).ok();
+        assert_eq!(read.expect("library reads"), materials);
+    }
+
+    #[test]
+    fn other_versions_are_rejected() {
+        let path = temp_path("material-library-version");
+        let file = LibraryFile { version: LIBRARY_VERSION + 1, materials: built_in_materials() };
+        fs::write(&path, ron::to_string(&file).unwrap()).unwrap();
+        let read = read_library(&path);
+        fs::remove_file(&path).ok();
+        assert!(matches!(read, Err(MaterialLibraryError::UnsupportedVersion(version)) if version == LIBRARY_VERSION + 1));
+    }
+
+    #[test]
+    fn unreadable_library_is_kept_until_reset() {
+        let path = temp_path("material-library-unreadable");
+        fs::write(&path, "not a library").unwrap();
+        let mut library = MaterialLibrary::load(&path);
+        assert!(library.unreadable && library.error.is_some());
+        assert!(library.custom.is_empty());
+
+        let backup = path.with_extension("ron.bak");
+        library.reset(&path);
+        let backed_up = fs::read_to_string(&backup);
+        fs::remove_file(&backup).ok();
+        assert_eq!(backed_up.expect("file was moved aside"), "not a library");
+        assert!(!path.exists());
+        assert!(!library.unreadable && library.dirty && library.error.is_none());
+    }
+}
@@ -0,0 +1,186 @@
+use bevy::prelude::*;
+use bevy_egui::EguiContexts;
+use bevy_rapier3d::prelude::*;
+
+use crate::history::{History, ObjectLifetime};
+use crate::primitives::{Primitive, PrimitiveShape};
+use crate::snapping::rest_on_surface;
+use crate::ui::spawn_primitive;
+
+pub struct PlacementPlugin;
+
+impl Plugin for PlacementPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<AddObject>()
+           .init_resource::<Placement>()
+           .add_systems(Update, (
+               add_objects,
+               place_object.after(add_objects),
+           ));
+    }
+}
+
+// Sent by the Add Object menu
+#[derive(Event)]
+pub struct AddObject(pub Primitive);
+
+#[derive(Resource, Default)]
+pub struct Placement {
+    // Place new objects with a click instead of under the viewport center
+    pub follow_cursor: bool,
+    // Shape waiting to be placed and the ghost previewing it
+    pending: Option<(Primitive, Entity)>,
+    // The click that placed the last object, kept until the button is released so it doesn't
+    // also select or start a gizmo drag
+    holding_click: bool,
+}
+
+impl Placement {
+    // Whether left clicks in the viewport belong to placement
+    pub fn is_placing(&self) -> bool {
+        self.pending.is_some() || self.holding_click
+    }
+}
+
+// How far in front of the camera objects go when there's no surface to put them on
+const FALLBACK_DISTANCE: f32 = 10.0;
+const MAX_PLACE_DISTANCE: f32 = 1000.0;
+
+// Where a new object with this collider rests on the first collider along `ray`, with its up
+// axis along the surface normal. Without a hit it floats at a fixed distance along the ray.
+pub fn resting_transform(rapier_context: &RapierContext, collider: &Collider, ray: Ray) -> Transform {
+    let filter = QueryFilter::default().exclude_sensors();
+    let Some((_, hit)) = rapier_context.cast_ray_and_get_normal(ray.origin, ray.direction, MAX_PLACE_DISTANCE, true, filter) else {
+        return Transform::from_translation(ray.get_point(FALLBACK_DISTANCE));
+    };
+    let normal = hit.normal.try_normalize().unwrap_or(Vec3::Y);
+
+    // Start clear of the surface, then sweep the collider back down onto it
+    let bounds = collider.raw.compute_local_aabb();
+    let lift = (Vec3::from(bounds.half_extents()).length() + Vec3::from(bounds.center()).length()) * 2.0 + 0.01;
+    let start = Transform::from_translation(hit.point + normal * lift);
+    rest_on_surface(rapier_context, &start, normal, collider, lift * 2.0, filter)
+        .unwrap_or(start)
+}
+
+fn cursor_ray(windows: &Query<&Window>, cameras: &Query<(&Camera, &GlobalTransform)>) -> Option<Ray> {
+    let (camera, camera_transform) = cameras.get_single().ok()?;
+    let cursor = windows.get_single().ok()?.cursor_position()?;
+    camera.viewport_to_world(camera_transform, cursor)
+}
+
+pub fn center_ray(windows: &Query<&Window>, cameras: &Query<(&Camera, &GlobalTransform)>) -> Option<Ray> {
+    let (camera, camera_transform) = cameras.get_single().ok()?;
+    let window = windows.get_single().ok()?;
+    camera.viewport_to_world(camera_transform, Vec2::new(window.width(), window.height()) * 0.5)
+}
+
+fn spawn_placed(
+    commands: &mut Commands,
+    meshes: &mut ResMut<Assets<Mesh>>,
+    materials: &mut ResMut<Assets<StandardMaterial>>,
+    history: &mut History,
+    shape: PrimitiveShape,
+    transform: Transform,
+) {
+    let entity = spawn_primitive(commands, meshes, materials, shape, transform.translation);
+    commands.entity(entity).insert(transform);
+    history.push(ObjectLifetime::spawned("Spawn Object", vec![entity]));
+    history.seal();
+}
+
+#[allow(clippy::too_many_arguments)]
+fn add_objects(
+    mut events: EventReader<AddObject>,
+    mut placement: ResMut<Placement>,
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut history: ResMut<History>,
+    rapier_context: Res<RapierContext>,
+    windows: Query<&Window>,
+    cameras: Query<(&Camera, &GlobalTransform)>,
+) {
+    for AddObject(primitive) in events.read() {
+        let shape = PrimitiveShape::new(*primitive);
+
+        if !placement.follow_cursor {
+            // Rest it on whatever is under the middle of the viewport
+            let Some(ray) = center_ray(&windows, &cameras) else {
+                continue;
+            };
+            let transform = resting_transform(&rapier_context, &shape.collider(), ray);
+            spawn_placed(&mut commands, &mut meshes, &mut materials, &mut history, shape, transform);
+            continue;
+        }
+
+        // Translucent stand-in without a collider, so rays and sweeps go straight through it
+        let ghost = commands.spawn(PbrBundle {
+            mesh: meshes.add(shape.mesh()),
+            material: materials.add(StandardMaterial {
+                base_color: Color::rgba(0.4, 0.7, 1.0, 0.4),
+                alpha_mode: AlphaMode::Blend,
+                unlit: true,
+                ..default()
+            }),
+            visibility: Visibility::Hidden,
+            ..default()
+        }).id();
+        if let Some((_, previous)) = placement.pending.replace((*primitive, ghost)) {
+            commands.entity(previous).despawn_recursive();
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn place_object(
+    mut placement: ResMut<Placement>,
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut history: ResMut<History>,
+    mouse_button: Res<Input<MouseButton>>,
+    keyboard: Res<Input<KeyCode>>,
+    rapier_context: Res<RapierContext>,
+    windows: Query<&Window>,
+    cameras: Query<(&Camera, &GlobalTransform)>,
+    mut ghosts: Query<(&mut Transform, &mut Visibility)>,
+    mut contexts: EguiContexts,
+) {
+    if !mouse_button.pressed(MouseButton::Left) {
+        placement.holding_click = false;
+    }
+    let Some((primitive, ghost)) = placement.pending else {
+        return;
+    };
+
+    if keyboard.just_pressed(KeyCode::Escape) || !placement.follow_cursor {
+        commands.entity(ghost).despawn_recursive();
+        placement.pending = None;
+        return;
+    }
+
+    let Ok((mut ghost_transform, mut visibility)) = ghosts.get_mut(ghost) else {
+        return;
+    };
+    let over_ui = contexts.ctx_mut().is_pointer_over_area();
+    let Some(ray) = cursor_ray(&windows, &cameras).filter(|_| !over_ui) else {
+        *visibility = Visibility::Hidden;
+        return;
+    };
+
+    let shape = PrimitiveShape::new(primitive);
+    let transform = resting_transform(&rapier_context, &shape.collider(), ray);
+    *ghost_transform = transform;
+    *visibility = Visibility::Visible;
+
+    if mouse_button.just_pressed(MouseButton::Left) {
+        spawn_placed(&mut commands, &mut meshes, &mut materials, &mut history, shape, transform);
+        placement.holding_click = true;
+        // Shift keeps placing copies of the same shape
+        if !keyboard.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+            commands.entity(ghost).despawn_recursive();
+            placement.pending = None;
+        }
+    }
+}
@@ -0,0 +1,827 @@
+use bevy::prelude::*;
+use bevy::render::mesh::{Indices, VertexAttributeValues};
+use bevy::render::primitives::Aabb;
+use bevy::render::render_resource::PrimitiveTopology;
+use bevy_egui::egui;
+use bevy_rapier3d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::joints::vec3_editor;
+use crate::ui::ShapeType;
+
+pub struct PrimitivesPlugin;
+
+impl Plugin for PrimitivesPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, rebuild_primitive_shapes);
+    }
+}
+
+// Smallest dimension the Inspector allows, rapier misbehaves with degenerate shapes
+const MIN_SIZE: f32 = 0.01;
+
+// Dimensions of a built-in shape. The same numbers drive its render mesh and its collider.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Primitive {
+    Cuboid { half_extents: [f32; 3] },
+    Sphere { radius: f32 },
+    Cylinder { radius: f32, half_height: f32 },
+    Cone { radius: f32, half_height: f32 },
+    Capsule { radius: f32, half_height: f32 },
+    // Ring around Y, `minor_radius` is the thickness of the ring
+    Torus { major_radius: f32, minor_radius: f32 },
+    // Ramp rising towards +X at `angle` degrees
+    Wedge { half_length: f32, half_width: f32, angle: f32 },
+    // Solid staircase climbing towards +X
+    Stairs { half_extents: [f32; 3], steps: u32 },
+    // Hollow cylinder
+    Tube { outer_radius: f32, inner_radius: f32, half_height: f32 },
+    // Thin board lying flat
+    Plate { half_size: [f32; 2], half_thickness: f32 },
+    RoundCuboid { half_extents: [f32; 3], border_radius: f32 },
+}
+
+// Segments around the ring of tubes and tori, shared by mesh and collider so they match
+const RING_SEGMENTS: u32 = 24;
+// Samples around the cross section of a torus
+const TORUS_SIDES: u32 = 12;
+
+impl Primitive {
+    // The shape offered in the Add Object menu before any parameters are changed
+    pub fn unit(shape_type: ShapeType) -> Option<Self> {
+        match shape_type {
+            ShapeType::Torus => Some(Primitive::Torus { major_radius: 0.5, minor_radius: 0.15 }),
+            ShapeType::Wedge => Some(Primitive::Wedge { half_length: 0.5, half_width: 0.5, angle: 30.0 }),
+            ShapeType::Stairs => Some(Primitive::Stairs { half_extents: [0.5; 3], steps: 4 }),
+            ShapeType::Tube => Some(Primitive::Tube { outer_radius: 0.5, inner_radius: 0.35, half_height: 0.5 }),
+            ShapeType::Plate => Some(Primitive::Plate { half_size: [1.0, 1.0], half_thickness: 0.025 }),
+            ShapeType::RoundCuboid => Some(Primitive::RoundCuboid { half_extents: [0.5; 3], border_radius: 0.1 }),
+            _ => Self::fitting(shape_type, Vec3::splat(0.5)),
+        }
+    }
+
+    // A shape of the given type filling roughly the same box
+    pub fn fitting(shape_type: ShapeType, half_extents: Vec3) -> Option<Self> {
+        let radius = half_extents.x.max(half_extents.z);
+        let primitive = match shape_type {
+            ShapeType::Cube => Primitive::Cuboid { half_extents: half_extents.to_array() },
+            ShapeType::Sphere => Primitive::Sphere { radius: half_extents.max_element() },
+            ShapeType::Cylinder => Primitive::Cylinder { radius, half_height: half_extents.y },
+            ShapeType::Cone => Primitive::Cone { radius, half_height: half_extents.y },
+            ShapeType::Capsule => Primitive::Capsule { radius, half_height: half_extents.y },
+            ShapeType::Torus => {
+                let minor_radius = half_extents.y.min(radius * 0.5);
+                Primitive::Torus { major_radius: radius - minor_radius, minor_radius }
+            }
+            ShapeType::Wedge => Primitive::Wedge {
+                half_length: half_extents.x,
+                half_width: half_extents.z,
+                angle: half_extents.y.atan2(half_extents.x).to_degrees(),
+            },
+            ShapeType::Stairs => Primitive::Stairs { half_extents: half_extents.to_array(), steps: 4 },
+            ShapeType::Tube => Primitive::Tube {
+                outer_radius: radius,
+                inner_radius: radius * 0.7,
+                half_height: half_extents.y,
+            },
+            ShapeType::Plate => Primitive::Plate {
+                half_size: [half_extents.x, half_extents.z],
+                half_thickness: half_extents.y.min(0.025),
+            },
+            ShapeType::RoundCuboid => Primitive::RoundCuboid {
+                half_extents: half_extents.to_array(),
+                border_radius: half_extents.min_element() * 0.2,
+            },
+            ShapeType::Imported => return None,
+        };
+        Some(primitive.clamped())
+    }
+
+    pub fn shape_type(self) -> ShapeType {
+        match self {
+            Primitive::Cuboid { .. } => ShapeType::Cube,
+            Primitive::Sphere { .. } => ShapeType::Sphere,
+            Primitive::Cylinder { .. } => ShapeType::Cylinder,
+            Primitive::Cone { .. } => ShapeType::Cone,
+            Primitive::Capsule { .. } => ShapeType::Capsule,
+            Primitive::Torus { .. } => ShapeType::Torus,
+            Primitive::Wedge { .. } => ShapeType::Wedge,
+            Primitive::Stairs { .. } => ShapeType::Stairs,
+            Primitive::Tube { .. } => ShapeType::Tube,
+            Primitive::Plate { .. } => ShapeType::Plate,
+            Primitive::RoundCuboid { .. } => ShapeType::RoundCuboid,
+        }
+    }
+
+    // Keeps parameters that depend on each other valid, e.g. a tube's hole inside its wall
+    pub fn clamped(self) -> Self {
+        let size = |value: f32| value.max(MIN_SIZE);
+        match self {
+            Primitive::Torus { major_radius, minor_radius } => {
+                let major_radius = size(major_radius);
+                Primitive::Torus { major_radius, minor_radius: size(minor_radius).min(major_radius) }
+            }
+            Primitive::Wedge { half_length, half_width, angle } => Primitive::Wedge {
+                half_length: size(half_length),
+                half_width: size(half_width),
+                angle: angle.clamp(1.0, 80.0),
+            },
+            Primitive::Stairs { half_extents, steps } => Primitive::Stairs {
+                half_extents: half_extents.map(size),
+                steps: steps.clamp(1, 50),
+            },
+            Primitive::Tube { outer_radius, inner_radius, half_height } => {
+                let outer_radius = size(outer_radius).max(MIN_SIZE * 2.0);
+                Primitive::Tube {
+                    outer_radius,
+                    inner_radius: size(inner_radius).min(outer_radius - MIN_SIZE),
+                    half_height: size(half_height),
+                }
+            }
+            Primitive::RoundCuboid { half_extents, border_radius } => {
+                let half_extents = half_extents.map(size);
+                let smallest = half_extents.into_iter().fold(f32::MAX, f32::min);
+                Primitive::RoundCuboid { half_extents, border_radius: border_radius.clamp(0.0, smallest) }
+            }
+            primitive => primitive,
+        }
+    }
+
+    // Half size of the box around the shape, ignoring the rounded ends of capsules
+    pub fn half_extents(self) -> Vec3 {
+        match self {
+            Primitive::Cuboid { half_extents }
+            | Primitive::Stairs { half_extents, .. }
+            | Primitive::RoundCuboid { half_extents, .. } => Vec3::from_array(half_extents),
+            Primitive::Sphere { radius } => Vec3::splat(radius),
+            Primitive::Cylinder { radius, half_height }
+            | Primitive::Cone { radius, half_height }
+            | Primitive::Capsule { radius, half_height }
+            | Primitive::Tube { outer_radius: radius, half_height, .. } => Vec3::new(radius, half_height, radius),
+            Primitive::Torus { major_radius, minor_radius } => {
+                let radius = major_radius + minor_radius;
+                Vec3::new(radius, minor_radius, radius)
+            }
+            Primitive::Wedge { half_length, half_width, angle } => {
+                Vec3::new(half_length, half_length * angle.to_radians().tan(), half_width)
+            }
+            Primitive::Plate { half_size: [x, z], half_thickness } => Vec3::new(x, half_thickness, z),
+        }
+    }
+
+    pub fn mesh(self) -> Mesh {
+        match self {
+            Primitive::Cuboid { half_extents: [x, y, z] } => Mesh::from(shape::Box::new(x * 2.0, y * 2.0, z * 2.0)),
+            Primitive::Sphere { radius } => Mesh::from(shape::UVSphere {
+                radius,
+                sectors: 32,
+                stacks: 16,
+            }),
+            Primitive::Cylinder { radius, half_height } => Mesh::from(shape::Cylinder {
+                radius,
+                height: half_height * 2.0,
+                resolution: 32,
+                segments: 1,
+            }),
+            Primitive::Cone { radius, half_height } => cone_mesh(radius, half_height, 32),
+            Primitive::Capsule { radius, half_height } => Mesh::from(shape::Capsule {
+                radius,
+                rings: 16,
+                depth: half_height * 2.0,
+                latitudes: 16,
+                longitudes: 32,
+                uv_profile: default(),
+            }),
+            Primitive::Torus { major_radius, minor_radius } => Mesh::from(shape::Torus {
+                radius: major_radius,
+                ring_radius: minor_radius,
+                subdivisions_segments: RING_SEGMENTS as usize,
+                subdivisions_sides: TORUS_SIDES as usize,
+            }),
+            Primitive::Wedge { .. } => {
+                let mut mesh = MeshBuilder::default();
+                let [bottom, top] = wedge_corners(self.half_extents());
+                mesh.face(&[bottom[0], bottom[1], bottom[2], bottom[3]], Vec3::NEG_Y);
+                mesh.face(&[bottom[2], bottom[3], top[0], top[1]], Vec3::X);
+                mesh.face(&[bottom[0], bottom[1], top[1], top[0]], Vec3::Y);
+                mesh.face(&[bottom[0], bottom[3], top[0]], Vec3::NEG_Z);
+                mesh.face(&[bottom[1], bottom[2], top[1]], Vec3::Z);
+                mesh.build()
+            }
+            Primitive::Stairs { half_extents, steps } => {
+                let mut mesh = MeshBuilder::default();
+                for (center, half) in stair_steps(Vec3::from_array(half_extents), steps) {
+                    mesh.cuboid(center, half);
+                }
+                mesh.build()
+            }
+            Primitive::Tube { outer_radius, inner_radius, half_height } => {
+                tube_mesh(outer_radius, inner_radius, half_height)
+            }
+            Primitive::Plate { half_size: [x, z], half_thickness } => {
+                Mesh::from(shape::Box::new(x * 2.0, half_thickness * 2.0, z * 2.0))
+            }
+            Primitive::RoundCuboid { half_extents, border_radius } => {
+                round_cuboid_mesh(Vec3::from_array(half_extents), border_radius)
+            }
+        }
+    }
+
+    pub fn collider(self) -> Collider {
+        match self {
+            Primitive::Cuboid { half_extents: [x, y, z] } => Collider::cuboid(x, y, z),
+            Primitive::Sphere { radius } => Collider::ball(radius),
+            Primitive::Cylinder { radius, half_height } => Collider::cylinder(half_height, radius),
+            Primitive::Cone { radius, half_height } => Collider::cone(half_height, radius),
+            Primitive::Capsule { radius, half_height } => Collider::capsule_y(half_height, radius),
+            // Rapier has no torus, so each segment of the ring becomes a convex piece
+            Primitive::Torus { major_radius, minor_radius } => {
+                let section = |segment: u32| -> Vec<Vec3> {
+                    let (sin, cos) = ring_angle(segment).sin_cos();
+                    (0..TORUS_SIDES)
+                        .map(|side| {
+                            let phi = side as f32 / TORUS_SIDES as f32 * std::f32::consts::TAU;
+                            let distance = major_radius + minor_radius * phi.cos();
+                            Vec3::new(cos * distance, minor_radius * phi.sin(), sin * distance)
+                        })
+                        .collect()
+                };
+                convex_ring(|segment| [section(segment), section(segment + 1)].concat())
+            }
+            Primitive::Wedge { .. } => wedge_collider(self.half_extents()),
+            Primitive::Stairs { half_extents, steps } => Collider::compound(
+                stair_steps(Vec3::from_array(half_extents), steps)
+                    .into_iter()
+                    .map(|(center, half)| (center, Quat::IDENTITY, Collider::cuboid(half.x, half.y, half.z)))
+                    .collect(),
+            ),
+            // The wall is split into convex pieces, one per mesh segment
+            Primitive::Tube { outer_radius, inner_radius, half_height } => convex_ring(|segment| {
+                [segment, segment + 1]
+                    .into_iter()
+                    .flat_map(|segment| {
+                        let direction = ring_direction(segment);
+                        [outer_radius, inner_radius].into_iter().flat_map(move |radius| {
+                            [-half_height, half_height].map(|y| direction * radius + Vec3::Y * y)
+                        })
+                    })
+                    .collect()
+            }),
+            Primitive::Plate { half_size: [x, z], half_thickness } => Collider::cuboid(x, half_thickness, z),
+            Primitive::RoundCuboid { half_extents: [x, y, z], border_radius } => {
+                let radius = border_radius;
+                Collider::round_cuboid(x - radius, y - radius, z - radius, radius)
+            }
+        }
+    }
+}
+
+fn ring_angle(segment: u32) -> f32 {
+    segment as f32 / RING_SEGMENTS as f32 * std::f32::consts::TAU
+}
+
+// Unit vector in the XZ plane pointing at a segment boundary of a ring
+fn ring_direction(segment: u32) -> Vec3 {
+    let (sin, cos) = ring_angle(segment).sin_cos();
+    Vec3::new(cos, 0.0, sin)
+}
+
+// One convex hull per ring segment, built from the points `segment_points` returns for it
+fn convex_ring(segment_points: impl Fn(u32) -> Vec<Vec3>) -> Collider {
+    Collider::compound(
+        (0..RING_SEGMENTS)
+            .filter_map(|segment| Collider::convex_hull(&segment_points(segment)))
+            .map(|hull| (Vec3::ZERO, Quat::IDENTITY, hull))
+            .collect(),
+    )
+}
+
+// Bottom rectangle and top edge of a ramp filling `half_extents`, the top edge above +X
+fn wedge_corners(half_extents: Vec3) -> [Vec<Vec3>; 2] {
+    let Vec3 { x, y, z } = half_extents;
+    [
+        vec![Vec3::new(-x, -y, -z), Vec3::new(-x, -y, z), Vec3::new(x, -y, z), Vec3::new(x, -y, -z)],
+        vec![Vec3::new(x, y, -z), Vec3::new(x, y, z)],
+    ]
+}
+
+// The hull of the corners. Clamped wedges always have some volume, so building it can't fail.
+fn wedge_collider(half_extents: Vec3) -> Collider {
+    let [bottom, top] = wedge_corners(half_extents);
+    Collider::convex_hull(&[bottom, top].concat()).expect("a wedge's corners span a volume")
+}
+
+// Center and half size of every step, each one a block reaching down to the floor
+fn stair_steps(half_extents: Vec3, steps: u32) -> Vec<(Vec3, Vec3)> {
+    let depth = half_extents.x * 2.0 / steps as f32;
+    let rise = half_extents.y * 2.0 / steps as f32;
+    (0..steps)
+        .map(|step| {
+            let half = Vec3::new(depth * 0.5, rise * (step + 1) as f32 * 0.5, half_extents.z);
+            let center = Vec3::new(
+                -half_extents.x + depth * (step as f32 + 0.5),
+                -half_extents.y + half.y,
+                0.0,
+            );
+            (center, half)
+        })
+        .collect()
+}
+
+// Triangle lists for the procedural shapes
+#[derive(Default)]
+struct MeshBuilder {
+    positions: Vec<[f32; 3]>,
+    normals: Vec<[f32; 3]>,
+    uvs: Vec<[f32; 2]>,
+    indices: Vec<u32>,
+}
+
+// Each face of a box as (normal, u, v) with u × v = normal, so quads walk counter-clockwise
+const BOX_FACES: [(Vec3, Vec3, Vec3); 6] = [
+    (Vec3::X, Vec3::Y, Vec3::Z),
+    (Vec3::NEG_X, Vec3::Z, Vec3::Y),
+    (Vec3::Y, Vec3::Z, Vec3::X),
+    (Vec3::NEG_Y, Vec3::X, Vec3::Z),
+    (Vec3::Z, Vec3::X, Vec3::Y),
+    (Vec3::NEG_Z, Vec3::Y, Vec3::X),
+];
+
+impl MeshBuilder {
+    fn vertex(&mut self, position: Vec3, normal: Vec3, uv: [f32; 2]) -> u32 {
+        self.positions.push(position.to_array());
+        self.normals.push(normal.to_array());
+        self.uvs.push(uv);
+        self.positions.len() as u32 - 1
+    }
+
+    // Fan-triangulates a convex polygon, flipping it if needed so it faces `outward`
+    fn polygon(&mut self, vertices: &[u32], outward: Vec3) {
+        let corner = |index: usize| Vec3::from(self.positions[vertices[index] as usize]);
+        let normal = (corner(1) - corner(0)).cross(corner(2) - corner(0));
+        let flip = normal.dot(outward) < 0.0;
+        for index in 1..vertices.len() - 1 {
+            let (b, c) = if flip { (index + 1, index) } else { (index, index + 1) };
+            self.indices.extend_from_slice(&[vertices[0], vertices[b], vertices[c]]);
+        }
+    }
+
+    // Flat-shaded convex face with UVs projected onto its plane
+    fn face(&mut self, corners: &[Vec3], outward: Vec3) {
+        let normal = (corners[1] - corners[0]).cross(corners[2] - corners[0]).normalize_or_zero();
+        let normal = if normal.dot(outward) < 0.0 { -normal } else { normal };
+        let (tangent, bitangent) = normal.any_orthonormal_pair();
+        let vertices: Vec<u32> = corners
+            .iter()
+            .map(|corner| self.vertex(*corner, normal, [corner.dot(tangent), corner.dot(bitangent)]))
+            .collect();
+        self.polygon(&vertices, normal);
+    }
+
+    fn cuboid(&mut self, center: Vec3, half: Vec3) {
+        for (normal, u, v) in BOX_FACES {
+            let (u, v) = (u * half, v * half);
+            let middle = center + normal * half;
+            self.face(&[middle - u - v, middle + u - v, middle + u + v, middle - u + v], normal);
+        }
+    }
+
+    fn build(self) -> Mesh {
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, self.positions);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, self.normals);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, self.uvs);
+        mesh.set_indices(Some(Indices::U32(self.indices)));
+        mesh
+    }
+}
+
+fn tube_mesh(outer_radius: f32, inner_radius: f32, half_height: f32) -> Mesh {
+    let mut mesh = MeshBuilder::default();
+    for segment in 0..RING_SEGMENTS {
+        let (start, end) = (ring_direction(segment), ring_direction(segment + 1));
+        let (u0, u1) = (segment as f32 / RING_SEGMENTS as f32, (segment + 1) as f32 / RING_SEGMENTS as f32);
+        let middle = (start + end).normalize();
+
+        // Outer and inner walls are smooth, the inner one faces the hole
+        for (radius, side) in [(outer_radius, 1.0), (inner_radius, -1.0)] {
+            let wall = [
+                mesh.vertex(start * radius - Vec3::Y * half_height, start * side, [u0, 1.0]),
+                mesh.vertex(end * radius - Vec3::Y * half_height, end * side, [u1, 1.0]),
+                mesh.vertex(end * radius + Vec3::Y * half_height, end * side, [u1, 0.0]),
+                mesh.vertex(start * radius + Vec3::Y * half_height, start * side, [u0, 0.0]),
+            ];
+            mesh.polygon(&wall, middle * side);
+        }
+        for y in [-half_height, half_height] {
+            let up = Vec3::Y * y.signum();
+            mesh.face(&[
+                start * inner_radius + Vec3::Y * y,
+                start * outer_radius + Vec3::Y * y,
+                end * outer_radius + Vec3::Y * y,
+                end * inner_radius + Vec3::Y * y,
+            ], up);
+        }
+    }
+    mesh.build()
+}
+
+// Arc samples across each rounded edge of a round cuboid
+const ROUND_STEPS: u32 = 4;
+
+// A subdivided box whose vertices are pushed onto the rounded surface, like rapier's round cuboid:
+// the inner box grown by `radius` in every direction
+fn round_cuboid_mesh(half_extents: Vec3, radius: f32) -> Mesh {
+    let inner = (half_extents - Vec3::splat(radius)).max(Vec3::ZERO);
+    // Grid coordinates along one axis: dense across the rounded edges, one span across the flat middle
+    let grid = |half: f32, inner: f32| -> Vec<f32> {
+        let edge = (0..=ROUND_STEPS).map(|step| inner + (half - inner) * step as f32 / ROUND_STEPS as f32);
+        let mut coordinates: Vec<f32> = edge.clone().rev().map(|coordinate| -coordinate).collect();
+        coordinates.extend(edge);
+        coordinates
+    };
+
+    let mut mesh = MeshBuilder::default();
+    for (normal, u, v) in BOX_FACES {
+        let depth = normal.abs().dot(half_extents);
+        let us = grid(u.dot(half_extents), u.dot(inner));
+        let vs = grid(v.dot(half_extents), v.dot(inner));
+        let first = mesh.positions.len() as u32;
+        for (i, a) in us.iter().enumerate() {
+            for (j, b) in vs.iter().enumerate() {
+                let on_box = normal * depth + u * *a + v * *b;
+                let core = on_box.clamp(-inner, inner);
+                let direction = (on_box - core).try_normalize().unwrap_or(normal);
+                let uv = [i as f32 / (us.len() - 1) as f32, j as f32 / (vs.len() - 1) as f32];
+                mesh.vertex(core + direction * radius, direction, uv);
+            }
+        }
+        let columns = vs.len() as u32;
+        for i in 0..us.len() as u32 - 1 {
+            for j in 0..columns - 1 {
+                let corner = |i: u32, j: u32| first + i * columns + j;
+                mesh.indices.extend_from_slice(&[
+                    corner(i, j), corner(i + 1, j), corner(i + 1, j + 1),
+                    corner(i, j), corner(i + 1, j + 1), corner(i, j + 1),
+                ]);
+            }
+        }
+    }
+    mesh.build()
+}
+
+// A cone along Y with its apex at +half_height, laid out like rapier's cone collider. The apex is
+// repeated for every side segment so each one gets its own slanted normal and UV column.
+fn cone_mesh(radius: f32, half_height: f32, resolution: u32) -> Mesh {
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut uvs = Vec::new();
+    let mut indices = Vec::new();
+
+    // Side normals lean outwards by the slope of the side
+    let height = half_height * 2.0;
+    let step = std::f32::consts::TAU / resolution as f32;
+    for segment in 0..=resolution {
+        let angle = segment as f32 * step;
+        let (sin, cos) = angle.sin_cos();
+        let normal = Vec3::new(cos * height, radius, sin * height).normalize_or_zero().to_array();
+        let u = segment as f32 / resolution as f32;
+
+        positions.push([0.0, half_height, 0.0]);
+        normals.push(normal);
+        uvs.push([u, 0.0]);
+        positions.push([cos * radius, -half_height, sin * radius]);
+        normals.push(normal);
+        uvs.push([u, 1.0]);
+    }
+    for segment in 0..resolution {
+        let apex = segment * 2;
+        let (rim, next_rim) = (apex + 1, apex + 3);
+        indices.extend_from_slice(&[apex, next_rim, rim]);
+    }
+
+    // Base cap, facing down
+    let center = positions.len() as u32;
+    positions.push([0.0, -half_height, 0.0]);
+    normals.push([0.0, -1.0, 0.0]);
+    uvs.push([0.5, 0.5]);
+    for segment in 0..resolution {
+        let angle = segment as f32 * step;
+        let (sin, cos) = angle.sin_cos();
+        positions.push([cos * radius, -half_height, sin * radius]);
+        normals.push([0.0, -1.0, 0.0]);
+        uvs.push([0.5 + cos * 0.5, 0.5 + sin * 0.5]);
+    }
+    for segment in 0..resolution {
+        let rim = center + 1 + segment;
+        let next_rim = center + 1 + (segment + 1) % resolution;
+        indices.extend_from_slice(&[center, rim, next_rim]);
+    }
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh.set_indices(Some(Indices::U32(indices)));
+    mesh
+}
+
+// The editable shape of a built-in object, edited in the Inspector's Collider section.
+// Changing it rebuilds both the render mesh and the collider so they always match.
+#[derive(Component, Clone, Copy, PartialEq, Debug)]
+pub struct PrimitiveShape {
+    pub primitive: Primitive,
+    // Placement of the shape relative to the object's origin, applied to mesh and collider alike
+    pub offset: Vec3,
+    pub rotation: Quat,
+    pub sensor: bool,
+}
+
+impl PrimitiveShape {
+    pub fn new(primitive: Primitive) -> Self {
+        Self {
+            primitive,
+            offset: Vec3::ZERO,
+            rotation: Quat::IDENTITY,
+            sensor: false,
+        }
+    }
+
+    fn is_placed(&self) -> bool {
+        self.offset != Vec3::ZERO || self.rotation != Quat::IDENTITY
+    }
+
+    pub fn mesh(&self) -> Mesh {
+        let mut mesh = self.primitive.mesh();
+        if !self.is_placed() {
+            return mesh;
+        }
+        if let Some(VertexAttributeValues::Float32x3(positions)) = mesh.attribute_mut(Mesh::ATTRIBUTE_POSITION) {
+            for position in positions.iter_mut() {
+                *position = (self.offset + self.rotation * Vec3::from_array(*position)).to_array();
+            }
+        }
+        if let Some(VertexAttributeValues::Float32x3(normals)) = mesh.attribute_mut(Mesh::ATTRIBUTE_NORMAL) {
+            for normal in normals.iter_mut() {
+                *normal = (self.rotation * Vec3::from_array(*normal)).to_array();
+            }
+        }
+        mesh
+    }
+
+    pub fn collider(&self) -> Collider {
+        let collider = self.primitive.collider();
+        if self.is_placed() {
+            Collider::compound(vec![(self.offset, self.rotation, collider)])
+        } else {
+            collider
+        }
+    }
+}
+
+type ChangedShapeQuery<'w, 's> = Query<'w, 's, (
+    Entity,
+    Ref<'static, PrimitiveShape>,
+    &'static ShapeType,
+    Option<&'static Collider>,
+), Changed<PrimitiveShape>>;
+
+// Shapes are built when objects spawn, so only later edits (Inspector, undo) need a rebuild here
+fn rebuild_primitive_shapes(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    shapes: ChangedShapeQuery,
+) {
+    for (entity, shape, shape_type, collider) in shapes.iter() {
+        if shape.is_added() {
+            continue;
+        }
+        let mut entity = commands.entity(entity);
+        // Bevy only computes bounds for meshes that have none, and selection relies on them
+        entity.insert(meshes.add(shape.mesh())).remove::<Aabb>();
+        if shape.primitive.shape_type() != *shape_type {
+            entity.insert(shape.primitive.shape_type());
+        }
+        // Visual-only objects keep going without a collider
+        if collider.is_some() {
+            entity.insert(shape.collider());
+            if shape.sensor {
+                entity.insert(Sensor);
+            } else {
+                entity.remove::<Sensor>();
+            }
+        }
+    }
+}
+
+fn length_editor(ui: &mut egui::Ui, label: &str, value: &mut f32) {
+    ui.add(egui::DragValue::new(value)
+        .prefix(label)
+        .suffix(" m")
+        .speed(0.01)
+        .clamp_range(MIN_SIZE..=f32::MAX));
+}
+
+fn half_extents_editor(ui: &mut egui::Ui, half_extents: &mut [f32]) {
+    ui.label("Half Extents");
+    ui.horizontal(|ui| {
+        for (value, prefix) in half_extents.iter_mut().zip(["X: ", "Y: ", "Z: "]) {
+            length_editor(ui, prefix, value);
+        }
+    });
+}
+
+// Parameter widgets of a primitive, shared by the Inspector and the Add Object menu
+pub fn primitive_editor(ui: &mut egui::Ui, primitive: &mut Primitive) {
+    match primitive {
+        Primitive::Cuboid { half_extents } => half_extents_editor(ui, half_extents),
+        Primitive::Sphere { radius } => {
+            length_editor(ui, "Radius: ", radius);
+        }
+        Primitive::Cylinder { radius, half_height }
+        | Primitive::Cone { radius, half_height }
+        | Primitive::Capsule { radius, half_height } => {
+            length_editor(ui, "Radius: ", radius);
+            length_editor(ui, "Half Height: ", half_height);
+        }
+        Primitive::Torus { major_radius, minor_radius } => {
+            length_editor(ui, "Ring Radius: ", major_radius);
+            length_editor(ui, "Thickness: ", minor_radius);
+        }
+        Primitive::Wedge { half_length, half_width, angle } => {
+            length_editor(ui, "Half Length: ", half_length);
+            length_editor(ui, "Half Width: ", half_width);
+            ui.add(egui::DragValue::new(angle).prefix("Angle: ").suffix("°").speed(0.5).clamp_range(1.0..=80.0));
+        }
+        Primitive::Stairs { half_extents, steps } => {
+            half_extents_editor(ui, half_extents);
+            ui.add(egui::DragValue::new(steps).prefix("Steps: ").speed(0.1).clamp_range(1..=50));
+        }
+        Primitive::Tube { outer_radius, inner_radius, half_height } => {
+            length_editor(ui, "Outer Radius: ", outer_radius);
+            length_editor(ui, "Inner Radius: ", inner_radius);
+            length_editor(ui, "Half Height: ", half_height);
+        }
+        Primitive::Plate { half_size, half_thickness } => {
+            ui.label("Half Size");
+            ui.horizontal(|ui| {
+                for (value, prefix) in half_size.iter_mut().zip(["X: ", "Z: "]) {
+                    length_editor(ui, prefix, value);
+                }
+            });
+            length_editor(ui, "Half Thickness: ", half_thickness);
+        }
+        Primitive::RoundCuboid { half_extents, border_radius } => {
+            half_extents_editor(ui, half_extents);
+            ui.add(egui::DragValue::new(border_radius)
+                .prefix("Border Radius: ")
+                .suffix(" m")
+                .speed(0.01)
+                .clamp_range(0.0..=f32::MAX));
+        }
+    }
+    *primitive = primitive.clamped();
+}
+
+// "Collider" section of the Inspector for built-in shapes
+pub fn collider_inspector(ui: &mut egui::Ui, shape: &mut PrimitiveShape) {
+    ui.collapsing("Collider", |ui| {
+        let current = shape.primitive.shape_type();
+        egui::ComboBox::from_id_source("primitive_shape")
+            .selected_text(current.label())
+            .show_ui(ui, |ui| {
+                for shape_type in ShapeType::PRIMITIVES {
+                    if ui.selectable_label(shape_type == current, shape_type.label()).clicked() && shape_type != current {
+                        if let Some(primitive) = Primitive::fitting(shape_type, shape.primitive.half_extents()) {
+                            shape.primitive = primitive;
+                        }
+                    }
+                }
+            });
+
+        primitive_editor(ui, &mut shape.primitive);
+
+        ui.separator();
+        vec3_editor(ui, "Offset", &mut shape.offset);
+        ui.horizontal(|ui| {
+            ui.label("Rotation");
+            let (x, y, z) = shape.rotation.to_euler(EulerRot::XYZ);
+            let mut rotation = [x, y, z];
+            let mut changed = false;
+            for (value, prefix) in rotation.iter_mut().zip(["X: ", "Y: ", "Z: "]) {
+                changed |= ui.add(egui::DragValue::new(value).prefix(prefix).speed(0.01)).changed();
+            }
+            if changed {
+                shape.rotation = Quat::from_euler(EulerRot::XYZ, rotation[0], rotation[1], rotation[2]);
+            }
+        });
+
+        ui.checkbox(&mut shape.sensor, "Sensor")
+            .on_hover_text("Reports overlaps without pushing other bodies away");
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Signed volume enclosed by the mesh, positive when every triangle faces outwards
+    fn mesh_volume(mesh: &Mesh) -> f32 {
+        let Some(VertexAttributeValues::Float32x3(positions)) = mesh.attribute(Mesh::ATTRIBUTE_POSITION) else {
+            panic!("mesh has no positions");
+        };
+        let Some(Indices::U32(indices)) = mesh.indices() else {
+            panic!("mesh has no u32 indices");
+        };
+        indices
+            .chunks_exact(3)
+            .map(|triangle| {
+                let [a, b, c] = [0, 1, 2].map(|corner| Vec3::from(positions[triangle[corner] as usize]));
+                a.dot(b.cross(c)) / 6.0
+            })
+            .sum()
+    }
+
+    #[test]
+    fn cone_collider_matches_mesh() {
+        for (radius, half_height) in [(0.5, 0.5), (1.5, 0.25), (0.2, 2.0)] {
+            let primitive = Primitive::Cone { radius, half_height };
+            let mesh = primitive.mesh();
+            let collider = primitive.collider();
+
+            // A density of one makes the mass equal to the volume
+            let collider_volume = collider.raw.mass_properties(1.0).mass();
+            let mesh_volume = mesh_volume(&mesh);
+            let exact = std::f32::consts::PI * radius * radius * half_height * 2.0 / 3.0;
+            assert!((collider_volume - exact).abs() / exact < 1e-3, "collider volume {collider_volume}, expected {exact}");
+            assert!((mesh_volume - collider_volume).abs() / collider_volume < 0.02, "mesh volume {mesh_volume}, collider volume {collider_volume}");
+
+            let mesh_aabb = mesh.compute_aabb().expect("mesh has positions");
+            let collider_aabb = collider.raw.compute_local_aabb();
+            let mesh_min = Vec3::from(mesh_aabb.min());
+            let mesh_max = Vec3::from(mesh_aabb.max());
+            let collider_min = Vec3::new(collider_aabb.mins.x, collider_aabb.mins.y, collider_aabb.mins.z);
+            let collider_max = Vec3::new(collider_aabb.maxs.x, collider_aabb.maxs.y, collider_aabb.maxs.z);
+            assert!(mesh_min.abs_diff_eq(collider_min, 1e-4), "mesh min {mesh_min}, collider min {collider_min}");
+            assert!(mesh_max.abs_diff_eq(collider_max, 1e-4), "mesh max {mesh_max}, collider max {collider_max}");
+        }
+    }
+
+    #[test]
+    fn wedge_collider_matches_mesh() {
+        for (half_length, half_width, angle) in [(0.5, 0.5, 30.0), (2.0, 0.25, 10.0), (0.2, 1.5, 80.0)] {
+            let primitive = Primitive::Wedge { half_length, half_width, angle };
+            let mesh = primitive.mesh();
+            let collider = primitive.collider();
+            assert_eq!(collider.raw.shape_type(), bevy_rapier3d::parry::shape::ShapeType::ConvexPolyhedron);
+
+            // Half of the box around it
+            let half_extents = primitive.half_extents();
+            let exact = half_extents.x * half_extents.y * half_extents.z * 4.0;
+            let collider_volume = collider.raw.mass_properties(1.0).mass();
+            let mesh_volume = mesh_volume(&mesh);
+            assert!((collider_volume - exact).abs() / exact < 1e-3, "collider volume {collider_volume}, expected {exact}");
+            assert!((mesh_volume - exact).abs() / exact < 1e-3, "mesh volume {mesh_volume}, expected {exact}");
+
+            let mesh_aabb = mesh.compute_aabb().expect("mesh has positions");
+            let collider_aabb = collider.raw.compute_local_aabb();
+            assert!(Vec3::from(mesh_aabb.min()).abs_diff_eq(collider_aabb.mins.into(), 1e-4));
+            assert!(Vec3::from(mesh_aabb.max()).abs_diff_eq(collider_aabb.maxs.into(), 1e-4));
+        }
+    }
+
+    #[test]
+    fn cone_normals_face_out_of_the_surface() {
+        for (radius, half_height) in [(0.5, 0.5), (1.5, 0.25), (0.2, 2.0)] {
+            let mesh = Primitive::Cone { radius, half_height }.mesh();
+            let Some(VertexAttributeValues::Float32x3(positions)) = mesh.attribute(Mesh::ATTRIBUTE_POSITION) else {
+                panic!("mesh has no positions");
+            };
+            let Some(VertexAttributeValues::Float32x3(normals)) = mesh.attribute(Mesh::ATTRIBUTE_NORMAL) else {
+                panic!("mesh has no normals");
+            };
+            let positions: Vec<Vec3> = positions.iter().copied().map(Vec3::from).collect();
+            let normals: Vec<Vec3> = normals.iter().copied().map(Vec3::from).collect();
+            for normal in &normals {
+                assert!((normal.length() - 1.0).abs() < 1e-5);
+            }
+
+            // Apex and rim pairs along the side, one more than there are segments, then the base
+            // center and its rim
+            let segments = (positions.len() - 3) / 3;
+            let side = 2 * (segments + 1);
+            for pair in (0..side).step_by(2) {
+                let (apex, rim) = (positions[pair], positions[pair + 1]);
+                let slant = rim - apex;
+                let radial = Vec3::new(rim.x, 0.0, rim.z).normalize();
+                for normal in [normals[pair], normals[pair + 1]] {
+                    assert!(normal.dot(slant).abs() < 1e-5, "normal {normal} isn't perpendicular to the side {slant}");
+                    assert!(normal.dot(radial) > 0.0, "normal {normal} points towards the axis");
+                }
+            }
+            for normal in &normals[side..] {
+                assert_eq!(*normal, Vec3::NEG_Y);
+            }
+        }
+    }
+}
@@ -0,0 +1,536 @@
+use std::any::Any;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy::render::texture::{CompressedImageFormats, ImageSampler, ImageType, TextureError};
+use bevy::utils::HashSet;
+use bevy_egui::egui;
+use serde::{Deserialize, Serialize};
+
+use crate::history::{ComponentChange, EditCommand, EntityRemap, History};
+use crate::ui::Selectable;
+
+pub struct RenderMaterialsPlugin;
+
+impl Plugin for RenderMaterialsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TextureLibrary>()
+           .init_resource::<MaterialEditorState>()
+           .add_systems(Update, generate_missing_tangents);
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub enum TextureSlot {
+    BaseColor,
+    Normal,
+    MetallicRoughness,
+    Emissive,
+    Occlusion,
+}
+
+impl TextureSlot {
+    pub const ALL: [TextureSlot; 5] = [
+        TextureSlot::BaseColor,
+        TextureSlot::Normal,
+        TextureSlot::MetallicRoughness,
+        TextureSlot::Emissive,
+        TextureSlot::Occlusion,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            TextureSlot::BaseColor => "Base Colour",
+            TextureSlot::Normal => "Normal Map",
+            TextureSlot::MetallicRoughness => "Metallic / Roughness",
+            TextureSlot::Emissive => "Emissive",
+            TextureSlot::Occlusion => "Occlusion",
+        }
+    }
+
+    // Colour textures are stored in sRGB, data textures are linear
+    fn is_srgb(self) -> bool {
+        matches!(self, TextureSlot::BaseColor | TextureSlot::Emissive)
+    }
+
+    fn get(self, material: &StandardMaterial) -> Option<&Handle<Image>> {
+        match self {
+            TextureSlot::BaseColor => material.base_color_texture.as_ref(),
+            TextureSlot::Normal => material.normal_map_texture.as_ref(),
+            TextureSlot::MetallicRoughness => material.metallic_roughness_texture.as_ref(),
+            TextureSlot::Emissive => material.emissive_texture.as_ref(),
+            TextureSlot::Occlusion => material.occlusion_texture.as_ref(),
+        }
+    }
+
+    fn set(self, material: &mut StandardMaterial, texture: Option<Handle<Image>>) {
+        match self {
+            TextureSlot::BaseColor => material.base_color_texture = texture,
+            TextureSlot::Normal => material.normal_map_texture = texture,
+            TextureSlot::MetallicRoughness => material.metallic_roughness_texture = texture,
+            TextureSlot::Emissive => material.emissive_texture = texture,
+            TextureSlot::Occlusion => material.occlusion_texture = texture,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum TextureLoadError {
+    Io(std::io::Error),
+    Decode(TextureError),
+}
+
+impl fmt::Display for TextureLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TextureLoadError::Io(err) => write!(f, "could not read image file: {}", err),
+            TextureLoadError::Decode(err) => write!(f, "invalid image (expected png, jpg or hdr): {}", err),
+        }
+    }
+}
+
+impl std::error::Error for TextureLoadError {}
+
+impl From<std::io::Error> for TextureLoadError {
+    fn from(err: std::io::Error) -> Self {
+        TextureLoadError::Io(err)
+    }
+}
+
+impl From<TextureError> for TextureLoadError {
+    fn from(err: TextureError) -> Self {
+        TextureLoadError::Decode(err)
+    }
+}
+
+struct LoadedTexture {
+    path: PathBuf,
+    srgb: bool,
+    handle: Handle<Image>,
+}
+
+// Images loaded from files for texture slots, remembered so scenes can save their paths and a
+// file used by several materials is only decoded once
+#[derive(Resource, Default)]
+pub struct TextureLibrary {
+    loaded: Vec<LoadedTexture>,
+}
+
+impl TextureLibrary {
+    pub fn load(&mut self, images: &mut Assets<Image>, path: &Path, slot: TextureSlot) -> Result<Handle<Image>, TextureLoadError> {
+        let srgb = slot.is_srgb();
+        if let Some(loaded) = self.loaded.iter().find(|loaded| loaded.path == path && loaded.srgb == srgb) {
+            return Ok(loaded.handle.clone());
+        }
+
+        let bytes = fs::read(path)?;
+        let extension = path.extension().and_then(|extension| extension.to_str()).unwrap_or_default();
+        let image = Image::from_buffer(
+            &bytes,
+            ImageType::Extension(extension),
+            CompressedImageFormats::NONE,
+            srgb,
+            ImageSampler::Default,
+        )?;
+        let handle = images.add(image);
+        self.loaded.push(LoadedTexture { path: path.to_path_buf(), srgb, handle: handle.clone() });
+        Ok(handle)
+    }
+
+    pub fn path(&self, handle: &Handle<Image>) -> Option<&Path> {
+        self.loaded
+            .iter()
+            .find(|loaded| loaded.handle == *handle)
+            .map(|loaded| loaded.path.as_path())
+    }
+}
+
+// Serializable mirror of `AlphaMode`
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy, Debug)]
+pub enum AlphaModeData {
+    Opaque,
+    Mask(f32),
+    Blend,
+    Premultiplied,
+    Add,
+    Multiply,
+}
+
+impl From<AlphaMode> for AlphaModeData {
+    fn from(mode: AlphaMode) -> Self {
+        match mode {
+            AlphaMode::Opaque => AlphaModeData::Opaque,
+            AlphaMode::Mask(cutoff) => AlphaModeData::Mask(cutoff),
+            AlphaMode::Blend => AlphaModeData::Blend,
+            AlphaMode::Premultiplied => AlphaModeData::Premultiplied,
+            AlphaMode::Add => AlphaModeData::Add,
+            AlphaMode::Multiply => AlphaModeData::Multiply,
+        }
+    }
+}
+
+impl From<AlphaModeData> for AlphaMode {
+    fn from(mode: AlphaModeData) -> Self {
+        match mode {
+            AlphaModeData::Opaque => AlphaMode::Opaque,
+            AlphaModeData::Mask(cutoff) => AlphaMode::Mask(cutoff),
+            AlphaModeData::Blend => AlphaMode::Blend,
+            AlphaModeData::Premultiplied => AlphaMode::Premultiplied,
+            AlphaModeData::Add => AlphaMode::Add,
+            AlphaModeData::Multiply => AlphaMode::Multiply,
+        }
+    }
+}
+
+const ALPHA_MODES: [(AlphaMode, &str); 6] = [
+    (AlphaMode::Opaque, "Opaque"),
+    (AlphaMode::Mask(0.5), "Mask"),
+    (AlphaMode::Blend, "Blend"),
+    (AlphaMode::Premultiplied, "Premultiplied"),
+    (AlphaMode::Add, "Add"),
+    (AlphaMode::Multiply, "Multiply"),
+];
+
+fn alpha_mode_label(mode: AlphaMode) -> &'static str {
+    ALPHA_MODES
+        .iter()
+        .find(|(option, _)| std::mem::discriminant(option) == std::mem::discriminant(&mode))
+        .map_or("Opaque", |(_, label)| label)
+}
+
+// One render material of a scene. Objects refer to it by index so shared materials stay shared.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct MaterialData {
+    pub base_color: [f32; 4],
+    pub metallic: f32,
+    pub perceptual_roughness: f32,
+    pub emissive: [f32; 4],
+    pub alpha_mode: AlphaModeData,
+    #[serde(default)]
+    pub textures: Vec<(TextureSlot, PathBuf)>,
+    // The asset this was captured from. Undo and paste reuse it while it is alive, so the
+    // objects keep sharing it with those that stayed in the world.
+    #[serde(skip)]
+    pub handle: Option<Handle<StandardMaterial>>,
+}
+
+impl MaterialData {
+    pub fn capture(material: &StandardMaterial, textures: &TextureLibrary, handle: &Handle<StandardMaterial>) -> Self {
+        Self {
+            base_color: material.base_color.as_rgba_f32(),
+            metallic: material.metallic,
+            perceptual_roughness: material.perceptual_roughness,
+            emissive: material.emissive.as_rgba_f32(),
+            alpha_mode: material.alpha_mode.into(),
+            textures: TextureSlot::ALL
+                .into_iter()
+                .filter_map(|slot| Some((slot, textures.path(slot.get(material)?)?.to_path_buf())))
+                .collect(),
+            handle: Some(handle.clone()),
+        }
+    }
+
+    // Textures that fail to load are left out with a warning
+    fn build(&self, images: &mut Assets<Image>, textures: &mut TextureLibrary) -> StandardMaterial {
+        let [r, g, b, a] = self.base_color;
+        let [er, eg, eb, ea] = self.emissive;
+        let mut material = StandardMaterial {
+            base_color: Color::rgba(r, g, b, a),
+            metallic: self.metallic,
+            perceptual_roughness: self.perceptual_roughness,
+            emissive: Color::rgba(er, eg, eb, ea),
+            alpha_mode: self.alpha_mode.into(),
+            ..default()
+        };
+        for (slot, path) in &self.textures {
+            match textures.load(images, path, *slot) {
+                Ok(texture) => slot.set(&mut material, Some(texture)),
+                Err(err) => warn!("Leaving out the {} texture {}: {}", slot.label(), path.display(), err),
+            }
+        }
+        material
+    }
+
+    // The captured asset if it still exists, otherwise a new one
+    pub fn resolve(
+        &self,
+        materials: &mut Assets<StandardMaterial>,
+        images: &mut Assets<Image>,
+        textures: &mut TextureLibrary,
+    ) -> Handle<StandardMaterial> {
+        match &self.handle {
+            Some(handle) if materials.contains(handle) => handle.clone(),
+            _ => materials.add(self.build(images, textures)),
+        }
+    }
+}
+
+// Collects the materials of the objects being captured, one entry per asset
+#[derive(Default)]
+pub struct MaterialTable {
+    ids: Vec<AssetId<StandardMaterial>>,
+    pub materials: Vec<MaterialData>,
+}
+
+impl MaterialTable {
+    pub fn index(
+        &mut self,
+        handle: &Handle<StandardMaterial>,
+        materials: &Assets<StandardMaterial>,
+        textures: &TextureLibrary,
+    ) -> Option<usize> {
+        if let Some(index) = self.ids.iter().position(|id| *id == handle.id()) {
+            return Some(index);
+        }
+        let material = materials.get(handle)?;
+        self.ids.push(handle.id());
+        self.materials.push(MaterialData::capture(material, textures, handle));
+        Some(self.materials.len() - 1)
+    }
+}
+
+// An edit of a material asset, shared by every object using it
+pub struct MaterialChange {
+    handle: Handle<StandardMaterial>,
+    before: StandardMaterial,
+    after: StandardMaterial,
+}
+
+impl MaterialChange {
+    pub fn new(handle: Handle<StandardMaterial>, before: StandardMaterial, after: StandardMaterial) -> Self {
+        Self { handle, before, after }
+    }
+}
+
+impl EditCommand for MaterialChange {
+    fn label(&self) -> String {
+        "Edit Material".to_string()
+    }
+
+    fn undo(&mut self, world: &mut World) -> EntityRemap {
+        world.resource_mut::<Assets<StandardMaterial>>().insert(&self.handle, self.before.clone());
+        Vec::new()
+    }
+
+    fn redo(&mut self, world: &mut World) -> EntityRemap {
+        world.resource_mut::<Assets<StandardMaterial>>().insert(&self.handle, self.after.clone());
+        Vec::new()
+    }
+
+    fn remap_entity(&mut self, _from: Entity, _to: Entity) {}
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn merge(&mut self, newer: &dyn EditCommand) -> bool {
+        let Some(newer) = newer.as_any().downcast_ref::<Self>() else {
+            return false;
+        };
+        if newer.handle != self.handle {
+            return false;
+        }
+        self.after = newer.after.clone();
+        true
+    }
+}
+
+// Paths typed into the texture slots and the last loading error
+#[derive(Resource, Default)]
+pub struct MaterialEditorState {
+    paths: [String; 5],
+    error: Option<String>,
+}
+
+#[derive(SystemParam)]
+pub struct MaterialEditor<'w, 's> {
+    materials: ResMut<'w, Assets<StandardMaterial>>,
+    images: ResMut<'w, Assets<Image>>,
+    textures: ResMut<'w, TextureLibrary>,
+    state: ResMut<'w, MaterialEditorState>,
+    objects: Query<'w, 's, &'static Handle<StandardMaterial>, With<Selectable>>,
+}
+
+// "Material" section of the Inspector. Edits the primary selection's material asset, which
+// changes every object sharing it. `selection` holds the other selected objects.
+pub fn material_inspector(
+    ui: &mut egui::Ui,
+    primary: Entity,
+    selection: &[Entity],
+    editor: &mut MaterialEditor,
+    commands: &mut Commands,
+    history: &mut History,
+) {
+    let Ok(handle) = editor.objects.get(primary).cloned() else {
+        return;
+    };
+    let Some(before) = editor.materials.get(&handle).cloned() else {
+        return;
+    };
+
+    ui.collapsing("Material", |ui| {
+        let users: Vec<Entity> = selection
+            .iter()
+            .filter(|entity| editor.objects.get(**entity).is_ok_and(|other| *other != handle))
+            .copied()
+            .collect();
+        let shared = editor.objects.iter().filter(|other| **other == handle).count();
+
+        ui.horizontal(|ui| {
+            ui.label(match shared {
+                1 => "Used by this object only".to_string(),
+                count => format!("Shared by {} objects", count),
+            });
+            if ui.add_enabled(shared > 1, egui::Button::new("Make Unique"))
+                .on_hover_text("Give this object its own copy of the material")
+                .clicked()
+            {
+                let copy = editor.materials.add(before.clone());
+                commands.entity(primary).insert(copy.clone());
+                history.push(ComponentChange::new(primary, handle.clone(), copy));
+                history.seal();
+            }
+        });
+        if !users.is_empty()
+            && ui.button(format!("Use on {} selected", users.len()))
+                .on_hover_text("Make the other selected objects share this material")
+                .clicked()
+        {
+            let mut changes = Vec::new();
+            for entity in users {
+                if let Ok(old) = editor.objects.get(entity) {
+                    commands.entity(entity).insert(handle.clone());
+                    changes.push((entity, old.clone(), handle.clone()));
+                }
+            }
+            history.push(ComponentChange::batch(changes));
+            history.seal();
+        }
+
+        let mut edited = before.clone();
+        let mut changed = false;
+
+        ui.horizontal(|ui| {
+            ui.label("Base Colour");
+            let mut color = edited.base_color.as_rgba_f32();
+            if ui.color_edit_button_rgba_unmultiplied(&mut color).changed() {
+                edited.base_color = Color::rgba(color[0], color[1], color[2], color[3]);
+                changed = true;
+            }
+        });
+        changed |= ui.add(egui::Slider::new(&mut edited.metallic, 0.0..=1.0).text("Metallic")).changed();
+        changed |= ui.add(egui::Slider::new(&mut edited.perceptual_roughness, 0.089..=1.0).text("Roughness")).changed();
+
+        // Emissive colours go above 1 for bloom, so the strength is edited separately from the hue
+        ui.horizontal(|ui| {
+            ui.label("Emissive");
+            let [r, g, b, _] = edited.emissive.as_rgba_f32();
+            let mut strength = r.max(g).max(b);
+            let mut color = if strength > 0.0 { [r / strength, g / strength, b / strength] } else { [1.0; 3] };
+            let color_changed = ui.color_edit_button_rgb(&mut color).changed();
+            let strength_changed = ui.add(egui::DragValue::new(&mut strength)
+                .prefix("Strength: ")
+                .speed(0.05)
+                .clamp_range(0.0..=100.0))
+                .changed();
+            if color_changed || strength_changed {
+                edited.emissive = Color::rgb(color[0] * strength, color[1] * strength, color[2] * strength);
+                changed = true;
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Alpha");
+            egui::ComboBox::from_id_source("alpha_mode")
+                .selected_text(alpha_mode_label(edited.alpha_mode))
+                .show_ui(ui, |ui| {
+                    for (mode, label) in ALPHA_MODES {
+                        let current = alpha_mode_label(edited.alpha_mode) == label;
+                        if ui.selectable_label(current, label).clicked() && !current {
+                            edited.alpha_mode = mode;
+                            changed = true;
+                        }
+                    }
+                });
+            if let AlphaMode::Mask(cutoff) = &mut edited.alpha_mode {
+                changed |= ui.add(egui::Slider::new(cutoff, 0.0..=1.0).text("Cutoff")).changed();
+            }
+        });
+
+        ui.separator();
+        ui.label("Textures (png, jpg, hdr)");
+        for (index, slot) in TextureSlot::ALL.into_iter().enumerate() {
+            ui.push_id(index, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(slot.label());
+                    match slot.get(&edited) {
+                        Some(texture) => {
+                            let name = editor.textures
+                                .path(texture)
+                                .and_then(|path| path.file_name())
+                                .map_or("(embedded)".to_string(), |name| name.to_string_lossy().into_owned());
+                            ui.label(name);
+                            if ui.small_button("✖").on_hover_text("Remove texture").clicked() {
+                                slot.set(&mut edited, None);
+                                changed = true;
+                            }
+                        }
+                        None => {
+                            ui.text_edit_singleline(&mut editor.state.paths[index]);
+                            let path = editor.state.paths[index].trim().to_string();
+                            if ui.add_enabled(!path.is_empty(), egui::Button::new("Load")).clicked() {
+                                match editor.textures.load(&mut editor.images, Path::new(&path), slot) {
+                                    Ok(texture) => {
+                                        slot.set(&mut edited, Some(texture));
+                                        editor.state.paths[index].clear();
+                                        editor.state.error = None;
+                                        changed = true;
+                                    }
+                                    Err(err) => {
+                                        error!("Failed to load texture {}: {}", path, err);
+                                        editor.state.error = Some(format!("{}: {}", path, err));
+                                    }
+                                }
+                            }
+                        }
+                    }
+                });
+            });
+        }
+        if let Some(error) = &editor.state.error {
+            ui.colored_label(egui::Color32::RED, error);
+        }
+
+        if changed {
+            if let Some(material) = editor.materials.get_mut(&handle) {
+                *material = edited.clone();
+            }
+            history.push(MaterialChange::new(handle, before, edited));
+        }
+    });
+}
+
+// Normal maps need tangents, which neither the built-in shapes nor imported meshes carry
+fn generate_missing_tangents(
+    objects: Query<(&Handle<Mesh>, &Handle<StandardMaterial>)>,
+    materials: Res<Assets<StandardMaterial>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut failed: Local<HashSet<AssetId<Mesh>>>,
+) {
+    for (mesh, material) in objects.iter() {
+        let needs_tangents = materials.get(material).is_some_and(|material| material.normal_map_texture.is_some())
+            && meshes.get(mesh).is_some_and(|mesh| mesh.attribute(Mesh::ATTRIBUTE_TANGENT).is_none());
+        if !needs_tangents || failed.contains(&mesh.id()) {
+            continue;
+        }
+        if let Some(mesh_data) = meshes.get_mut(mesh) {
+            if let Err(err) = mesh_data.generate_tangents() {
+                warn!("Can't show the normal map on {:?}: {}", mesh.id(), err);
+                failed.insert(mesh.id());
+            }
+        }
+    }
+}
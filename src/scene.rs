@@ -0,0 +1,1066 @@
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use bevy::ecs::system::SystemState;
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use bevy_rapier3d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::history::History;
+use crate::joints::{spawn_joint, JointKind, JointSpec};
+use crate::mesh_import::{spawn_imported, ImportedMesh};
+use crate::outliner::{is_locked, Locked};
+use crate::physics_components::{PhysicsComponents, PhysicsQuery};
+use crate::physics_materials::{CombineRule, PhysicsMaterialName};
+use crate::primitives::{Primitive, PrimitiveShape};
+use crate::render_materials::{MaterialData, MaterialTable, TextureLibrary};
+use crate::ui::{spawn_primitive, spawn_shape, Selectable, ShapeType, UiState};
+use crate::world_settings::WorldSettings;
+
+// Bump this whenever the on-disk layout changes and add a migration step to `migrate`
+pub const SCENE_VERSION: u32 = 12;
+
+pub struct ScenePlugin;
+
+impl Plugin for ScenePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SceneAction>()
+           .init_resource::<SceneState>()
+           .add_systems(Update, (
+               scene_menu_actions,
+               scene_dialog_system,
+               save_scene.after(scene_dialog_system),
+               load_scene.after(scene_dialog_system),
+           ));
+    }
+}
+
+// Sent by the File menu in the toolbar
+#[derive(Event, Clone, Copy, PartialEq)]
+pub enum SceneAction {
+    Save,
+    SaveAs,
+    Open,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum DialogKind {
+    SaveAs,
+    Open,
+}
+
+struct FileDialog {
+    kind: DialogKind,
+    path: String,
+}
+
+#[derive(Resource, Default)]
+pub struct SceneState {
+    current_path: Option<PathBuf>,
+    dialog: Option<FileDialog>,
+    pending_save: Option<PathBuf>,
+    pending_load: Option<PathBuf>,
+    error: Option<String>,
+}
+
+// Marks the ground plane so it is written to and restored from scene files
+#[derive(Component)]
+pub struct Ground;
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io(std::io::Error),
+    Parse(ron::error::SpannedError),
+    Serialize(ron::Error),
+    MissingVersion,
+    UnsupportedVersion(u32),
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneError::Io(err) => write!(f, "could not access scene file: {}", err),
+            SceneError::Parse(err) => write!(f, "scene file is malformed: {}", err),
+            SceneError::Serialize(err) => write!(f, "could not serialize scene: {}", err),
+            SceneError::MissingVersion => write!(f, "file has no scene version, it is not a scene file"),
+            SceneError::UnsupportedVersion(version) if *version > SCENE_VERSION => write!(
+                f,
+                "scene version {} was written by a newer build (this build reads up to version {})",
+                version, SCENE_VERSION
+            ),
+            SceneError::UnsupportedVersion(version) => {
+                write!(f, "scene version {} is no longer supported", version)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+impl From<std::io::Error> for SceneError {
+    fn from(err: std::io::Error) -> Self {
+        SceneError::Io(err)
+    }
+}
+
+impl From<ron::error::SpannedError> for SceneError {
+    fn from(err: ron::error::SpannedError) -> Self {
+        SceneError::Parse(err)
+    }
+}
+
+impl From<ron::Error> for SceneError {
+    fn from(err: ron::Error) -> Self {
+        SceneError::Serialize(err)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct TransformData {
+    pub translation: [f32; 3],
+    pub rotation: [f32; 4],
+    pub scale: [f32; 3],
+}
+
+impl From<&Transform> for TransformData {
+    fn from(transform: &Transform) -> Self {
+        Self {
+            translation: transform.translation.to_array(),
+            rotation: transform.rotation.to_array(),
+            scale: transform.scale.to_array(),
+        }
+    }
+}
+
+impl From<TransformData> for Transform {
+    fn from(data: TransformData) -> Self {
+        Transform {
+            translation: Vec3::from_array(data.translation),
+            rotation: Quat::from_array(data.rotation).normalize(),
+            scale: Vec3::from_array(data.scale),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum BodyData {
+    Dynamic,
+    Fixed,
+    KinematicPositionBased,
+    KinematicVelocityBased,
+}
+
+impl From<RigidBody> for BodyData {
+    fn from(body: RigidBody) -> Self {
+        match body {
+            RigidBody::Dynamic => BodyData::Dynamic,
+            RigidBody::Fixed => BodyData::Fixed,
+            RigidBody::KinematicPositionBased => BodyData::KinematicPositionBased,
+            RigidBody::KinematicVelocityBased => BodyData::KinematicVelocityBased,
+        }
+    }
+}
+
+impl From<BodyData> for RigidBody {
+    fn from(body: BodyData) -> Self {
+        match body {
+            BodyData::Dynamic => RigidBody::Dynamic,
+            BodyData::Fixed => RigidBody::Fixed,
+            BodyData::KinematicPositionBased => RigidBody::KinematicPositionBased,
+            BodyData::KinematicVelocityBased => RigidBody::KinematicVelocityBased,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum MassData {
+    Density(f32),
+    Mass(f32),
+    Explicit {
+        local_center_of_mass: [f32; 3],
+        mass: f32,
+        principal_inertia_local_frame: [f32; 4],
+        principal_inertia: [f32; 3],
+    },
+}
+
+impl From<&ColliderMassProperties> for MassData {
+    fn from(props: &ColliderMassProperties) -> Self {
+        match props {
+            ColliderMassProperties::Density(density) => MassData::Density(*density),
+            ColliderMassProperties::Mass(mass) => MassData::Mass(*mass),
+            ColliderMassProperties::MassProperties(props) => MassData::Explicit {
+                local_center_of_mass: props.local_center_of_mass.to_array(),
+                mass: props.mass,
+                principal_inertia_local_frame: props.principal_inertia_local_frame.to_array(),
+                principal_inertia: props.principal_inertia.to_array(),
+            },
+        }
+    }
+}
+
+impl From<MassData> for ColliderMassProperties {
+    fn from(data: MassData) -> Self {
+        match data {
+            MassData::Density(density) => ColliderMassProperties::Density(density),
+            MassData::Mass(mass) => ColliderMassProperties::Mass(mass),
+            MassData::Explicit {
+                local_center_of_mass,
+                mass,
+                principal_inertia_local_frame,
+                principal_inertia,
+            } => ColliderMassProperties::MassProperties(MassProperties {
+                local_center_of_mass: Vec3::from_array(local_center_of_mass),
+                mass,
+                principal_inertia_local_frame: Quat::from_array(principal_inertia_local_frame).normalize(),
+                principal_inertia: Vec3::from_array(principal_inertia),
+            }),
+        }
+    }
+}
+
+// Speeds below which a body falls asleep, its current sleep state isn't saved
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct SleepData {
+    pub linear_threshold: f32,
+    pub angular_threshold: f32,
+}
+
+impl Default for SleepData {
+    fn default() -> Self {
+        (&Sleeping::default()).into()
+    }
+}
+
+impl From<&Sleeping> for SleepData {
+    fn from(sleeping: &Sleeping) -> Self {
+        Self {
+            linear_threshold: sleeping.linear_threshold,
+            angular_threshold: sleeping.angular_threshold,
+        }
+    }
+}
+
+impl From<SleepData> for Sleeping {
+    fn from(data: SleepData) -> Self {
+        Sleeping {
+            linear_threshold: data.linear_threshold,
+            angular_threshold: data.angular_threshold,
+            sleeping: false,
+        }
+    }
+}
+
+// Dimensions, placement and sensor flag of a built-in shape
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct PrimitiveData {
+    pub primitive: Primitive,
+    pub offset: [f32; 3],
+    pub rotation: [f32; 4],
+    pub sensor: bool,
+}
+
+impl From<&PrimitiveShape> for PrimitiveData {
+    fn from(shape: &PrimitiveShape) -> Self {
+        Self {
+            primitive: shape.primitive,
+            offset: shape.offset.to_array(),
+            rotation: shape.rotation.to_array(),
+            sensor: shape.sensor,
+        }
+    }
+}
+
+impl From<PrimitiveData> for PrimitiveShape {
+    fn from(data: PrimitiveData) -> Self {
+        Self {
+            primitive: data.primitive,
+            offset: Vec3::from_array(data.offset),
+            rotation: Quat::from_array(data.rotation).normalize(),
+            sensor: data.sensor,
+        }
+    }
+}
+
+type SpawnParams<'w, 's> = (
+    Commands<'w, 's>,
+    ResMut<'w, Assets<Mesh>>,
+    ResMut<'w, Assets<StandardMaterial>>,
+    ResMut<'w, Assets<Image>>,
+    ResMut<'w, TextureLibrary>,
+);
+
+// Everything `spawn_shape` sets up for a single editor object
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SceneObject {
+    pub shape: ShapeType,
+    pub transform: TransformData,
+    pub body: BodyData,
+    pub linear_damping: f32,
+    pub angular_damping: f32,
+    pub restitution: f32,
+    pub friction: f32,
+    pub mass: MassData,
+    pub color: [f32; 4],
+    // Added in version 3, only set for imported meshes
+    #[serde(default)]
+    pub mesh: Option<ImportedMesh>,
+    // Added in version 4, set from the Outliner
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub hidden: bool,
+    #[serde(default)]
+    pub locked: bool,
+    // Added in version 5, objects whose physics were removed in the Inspector
+    #[serde(default)]
+    pub visual_only: bool,
+    // Added in version 6, shapes resized in the Collider section. Unit shapes before that.
+    #[serde(default)]
+    pub primitive: Option<PrimitiveData>,
+    // Added in version 7, set by physics materials or the Inspector
+    #[serde(default)]
+    pub friction_combine: CombineRule,
+    #[serde(default)]
+    pub restitution_combine: CombineRule,
+    #[serde(default)]
+    pub physics_material: Option<String>,
+    // Added in version 8, index into `SceneFile::materials`. `color` is only used without it.
+    #[serde(default)]
+    pub material: Option<usize>,
+    // Added in version 12, rapier's thresholds before that
+    #[serde(default)]
+    pub sleep: SleepData,
+}
+
+impl SceneObject {
+    // Physics components the object lacks are saved with the defaults of new objects
+    pub fn from_components(
+        shape: ShapeType,
+        transform: &Transform,
+        physics: &PhysicsComponents,
+        material: Option<&StandardMaterial>,
+    ) -> Self {
+        let defaults = PhysicsComponents::defaults(Collider::default());
+        let damping = physics.damping.or(defaults.damping).unwrap_or_default();
+        let restitution = physics.restitution.or(defaults.restitution).unwrap_or_default();
+        let friction = physics.friction.or(defaults.friction).unwrap_or_default();
+        let sleeping = physics.sleeping.or(defaults.sleeping).unwrap_or_default();
+        Self {
+            shape,
+            transform: transform.into(),
+            body: physics.rigid_body.unwrap_or(RigidBody::Dynamic).into(),
+            linear_damping: damping.linear_damping,
+            angular_damping: damping.angular_damping,
+            restitution: restitution.coefficient,
+            friction: friction.coefficient,
+            mass: (&physics.mass.or(defaults.mass).unwrap_or_default()).into(),
+            color: material
+                .map(|material| material.base_color.as_rgba_f32())
+                .unwrap_or([1.0; 4]),
+            mesh: None,
+            name: None,
+            hidden: false,
+            locked: false,
+            visual_only: physics.rigid_body.is_none(),
+            primitive: None,
+            friction_combine: friction.combine_rule.into(),
+            restitution_combine: restitution.combine_rule.into(),
+            physics_material: None,
+            material: None,
+            sleep: (&sleeping).into(),
+        }
+    }
+
+    pub fn with_primitive(mut self, shape: Option<&PrimitiveShape>) -> Self {
+        self.primitive = shape.map(PrimitiveData::from);
+        self
+    }
+
+    pub fn with_material(mut self, index: Option<usize>) -> Self {
+        self.material = index;
+        self
+    }
+
+    pub fn with_physics_material(mut self, name: Option<&PhysicsMaterialName>) -> Self {
+        self.physics_material = name.and_then(|name| name.0.clone());
+        self
+    }
+
+    pub fn with_mesh(mut self, mesh: Option<&ImportedMesh>) -> Self {
+        self.mesh = mesh.cloned();
+        self
+    }
+
+    pub fn with_outliner_state(mut self, name: Option<&Name>, visibility: &Visibility, locked: Option<&Locked>) -> Self {
+        self.name = name.map(|name| name.to_string());
+        self.hidden = *visibility == Visibility::Hidden;
+        self.locked = is_locked(locked);
+        self
+    }
+
+    // Reads an editor object straight from the world, used by commands that run exclusively
+    pub fn capture(world: &World, entity: Entity) -> Option<Self> {
+        let entity = world.get_entity(entity)?;
+        let material = entity
+            .get::<Handle<StandardMaterial>>()
+            .and_then(|handle| world.resource::<Assets<StandardMaterial>>().get(handle));
+        Some(Self::from_components(
+            *entity.get::<ShapeType>()?,
+            entity.get::<Transform>()?,
+            &PhysicsComponents::capture(entity),
+            material,
+        )
+        .with_mesh(entity.get::<ImportedMesh>())
+        .with_primitive(entity.get::<PrimitiveShape>())
+        .with_physics_material(entity.get::<PhysicsMaterialName>())
+        .with_outliner_state(entity.get::<Name>(), entity.get::<Visibility>()?, entity.get::<Locked>()))
+    }
+
+    // Spawns the object through `spawn_primitive`, `spawn_shape` or `spawn_imported` and then overrides the defaults they set.
+    // `material` is the resolved `self.material`, without one the object gets a plain material of `color`.
+    pub fn spawn(
+        &self,
+        commands: &mut Commands,
+        meshes: &mut ResMut<Assets<Mesh>>,
+        materials: &mut ResMut<Assets<StandardMaterial>>,
+        material: Option<Handle<StandardMaterial>>,
+    ) -> Entity {
+        let transform = Transform::from(self.transform);
+        let imported = self.mesh.as_ref().and_then(|source| {
+            spawn_imported(commands, meshes, materials, source, transform.translation)
+                .map_err(|err| warn!("Using a placeholder for {}: {}", source.path.display(), err))
+                .ok()
+        });
+        let entity = imported.unwrap_or_else(|| match self.primitive {
+            Some(primitive) => spawn_primitive(commands, meshes, materials, primitive.into(), transform.translation),
+            None => spawn_shape(commands, meshes, materials, self.shape, transform.translation),
+        });
+        commands.entity(entity).insert((
+            transform,
+            RigidBody::from(self.body),
+            Damping {
+                linear_damping: self.linear_damping,
+                angular_damping: self.angular_damping,
+            },
+            Restitution {
+                coefficient: self.restitution,
+                combine_rule: self.restitution_combine.into(),
+            },
+            Friction {
+                coefficient: self.friction,
+                combine_rule: self.friction_combine.into(),
+            },
+            ColliderMassProperties::from(self.mass),
+            Sleeping::from(self.sleep),
+            material.unwrap_or_else(|| materials.add(Color::rgba(self.color[0], self.color[1], self.color[2], self.color[3]).into())),
+        ));
+        if let Some(material) = &self.physics_material {
+            commands.entity(entity).insert(PhysicsMaterialName(Some(material.clone())));
+        }
+        if let Some(name) = &self.name {
+            commands.entity(entity).insert(Name::new(name.clone()));
+        }
+        if self.hidden {
+            commands.entity(entity).insert(Visibility::Hidden);
+        }
+        if self.visual_only {
+            PhysicsComponents::default().apply_deferred(commands, entity);
+        }
+        if self.locked {
+            commands.entity(entity).insert(Locked(true));
+        }
+        entity
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct GroundData {
+    pub transform: TransformData,
+    pub size: f32,
+    pub color: [f32; 4],
+}
+
+impl Default for GroundData {
+    fn default() -> Self {
+        Self {
+            transform: (&Transform::from_xyz(0.0, -0.5, 0.0)).into(),
+            size: 10.0,
+            color: Color::rgb(0.3, 0.5, 0.3).as_rgba_f32(),
+        }
+    }
+}
+
+impl GroundData {
+    pub fn spawn(
+        &self,
+        commands: &mut Commands,
+        meshes: &mut ResMut<Assets<Mesh>>,
+        materials: &mut ResMut<Assets<StandardMaterial>>,
+    ) -> Entity {
+        commands.spawn((
+            PbrBundle {
+                mesh: meshes.add(shape::Plane::from_size(self.size).into()),
+                material: materials.add(Color::rgba(self.color[0], self.color[1], self.color[2], self.color[3]).into()),
+                transform: self.transform.into(),
+                ..default()
+            },
+            Ground,
+            Name::new("Ground"),
+            RigidBody::Fixed,
+            Collider::cuboid(self.size * 0.5, 0.1, self.size * 0.5),
+        )).id()
+    }
+}
+
+// A joint between two entries of `SceneFile::objects`, referenced by index
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct JointData {
+    pub body1: usize,
+    pub body2: usize,
+    pub kind: JointKind,
+    pub anchor1: [f32; 3],
+    pub anchor2: [f32; 3],
+    pub axis: [f32; 3],
+    pub limits_enabled: bool,
+    pub limits: [f32; 2],
+    pub rope_length: f32,
+}
+
+impl JointData {
+    fn new(body1: usize, body2: usize, spec: &JointSpec) -> Self {
+        Self {
+            body1,
+            body2,
+            kind: spec.kind,
+            anchor1: spec.anchor1.to_array(),
+            anchor2: spec.anchor2.to_array(),
+            axis: spec.axis.to_array(),
+            limits_enabled: spec.limits_enabled,
+            limits: spec.limits,
+            rope_length: spec.rope_length,
+        }
+    }
+
+    fn spec(&self) -> JointSpec {
+        JointSpec {
+            kind: self.kind,
+            anchor1: Vec3::from_array(self.anchor1),
+            anchor2: Vec3::from_array(self.anchor2),
+            axis: Vec3::from_array(self.axis),
+            limits_enabled: self.limits_enabled,
+            limits: self.limits,
+            rope_length: self.rope_length,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SceneFile {
+    pub version: u32,
+    pub ground: Option<GroundData>,
+    pub objects: Vec<SceneObject>,
+    // Added in version 2
+    #[serde(default)]
+    pub joints: Vec<JointData>,
+    // Added in version 8
+    #[serde(default)]
+    pub materials: Vec<MaterialData>,
+    // Added in version 9, older scenes get rapier's defaults
+    #[serde(default)]
+    pub world: Option<WorldSettings>,
+}
+
+// Only the version is read first so we know which layout the rest of the file uses
+#[derive(Deserialize)]
+struct SceneHeader {
+    // Versions start at 1, so 0 means the field was missing
+    #[serde(default)]
+    version: u32,
+}
+
+impl SceneFile {
+    pub fn to_ron(&self) -> Result<String, SceneError> {
+        Ok(ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?)
+    }
+
+    pub fn from_ron(text: &str) -> Result<Self, SceneError> {
+        let header: SceneHeader = ron::from_str(text)?;
+        if header.version == 0 {
+            return Err(SceneError::MissingVersion);
+        }
+        migrate(header.version, text)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), SceneError> {
+        fs::write(path, self.to_ron()?)?;
+        Ok(())
+    }
+
+    pub fn read(path: &Path) -> Result<Self, SceneError> {
+        Self::from_ron(&fs::read_to_string(path)?)
+    }
+
+    // A ground-less scene of the given editor objects and the joints between them, as used by
+    // the clipboard and history. Also returns the joint entities, in the order of `joints`.
+    pub fn capture_objects(world: &World, entities: &[Entity]) -> (Self, Vec<Entity>) {
+        let mut table = MaterialTable::default();
+        let (entities, objects): (Vec<Entity>, Vec<SceneObject>) = entities
+            .iter()
+            .filter(|entity| world.get::<Selectable>(**entity).is_some())
+            .filter_map(|entity| {
+                let material = world.get::<Handle<StandardMaterial>>(*entity).and_then(|handle| {
+                    table.index(handle, world.resource(), world.resource())
+                });
+                Some((*entity, SceneObject::capture(world, *entity)?.with_material(material)))
+            })
+            .unzip();
+
+        let index_of = |entity: Entity| entities.iter().position(|captured| *captured == entity);
+        let (joint_entities, joints) = world
+            .iter_entities()
+            .filter_map(|entity| {
+                let spec = entity.get::<JointSpec>()?;
+                let body1 = index_of(entity.get::<ImpulseJoint>()?.parent)?;
+                let body2 = index_of(entity.get::<Parent>()?.get())?;
+                Some((entity.id(), JointData::new(body1, body2, spec)))
+            })
+            .unzip();
+
+        let scene = Self {
+            version: SCENE_VERSION,
+            ground: None,
+            objects,
+            joints,
+            materials: table.materials,
+            world: None,
+        };
+        (scene, joint_entities)
+    }
+
+    // A handle for each of `materials`, in the same order
+    fn material_handles(
+        &self,
+        materials: &mut Assets<StandardMaterial>,
+        images: &mut Assets<Image>,
+        textures: &mut TextureLibrary,
+    ) -> Vec<Handle<StandardMaterial>> {
+        self.materials
+            .iter()
+            .map(|material| material.resolve(materials, images, textures))
+            .collect()
+    }
+
+    fn object_material(&self, handles: &[Handle<StandardMaterial>], object: &SceneObject) -> Option<Handle<StandardMaterial>> {
+        object.material.and_then(|index| handles.get(index)).cloned()
+    }
+
+    // Spawns `objects`, moved by `offset`, and their joints. Returns the new object and joint entities.
+    pub fn spawn_objects_in_world(&self, world: &mut World, offset: Vec3) -> (Vec<Entity>, Vec<Entity>) {
+        let mut state: SystemState<SpawnParams> = SystemState::new(world);
+        let (mut commands, mut meshes, mut materials, mut images, mut textures) = state.get_mut(world);
+        let handles = self.material_handles(&mut materials, &mut images, &mut textures);
+
+        let objects: Vec<Entity> = self
+            .objects
+            .iter()
+            .map(|object| {
+                let mut object = object.clone();
+                for (position, offset) in object.transform.translation.iter_mut().zip(offset.to_array()) {
+                    *position += offset;
+                }
+                let material = self.object_material(&handles, &object);
+                object.spawn(&mut commands, &mut meshes, &mut materials, material)
+            })
+            .collect();
+        let joints = self
+            .joints
+            .iter()
+            .filter_map(|joint| {
+                let (body1, body2) = (*objects.get(joint.body1)?, *objects.get(joint.body2)?);
+                Some(spawn_joint(&mut commands, body1, body2, joint.spec()))
+            })
+            .collect();
+
+        state.apply(world);
+        (objects, joints)
+    }
+}
+
+// Upgrades a file of any supported version to the current layout
+fn migrate(version: u32, text: &str) -> Result<SceneFile, SceneError> {
+    match version {
+        SCENE_VERSION => Ok(ron::from_str(text)?),
+        // Version 1 had no joints, version 2 no imported meshes, version 3 no names,
+        // visibility or locks, version 4 no visual-only objects, version 5 no shape dimensions and
+        // version 6 no combine rules or physics materials, version 7 no shared render materials and
+        // version 8 no world settings, version 9 no integrator, version 10 no broad phase choice and
+        // version 11 no sleep thresholds. Serde defaults fill all of them in.
+        1..=11 => {
+            let mut scene: SceneFile = ron::from_str(text)?;
+            scene.version = SCENE_VERSION;
+            Ok(scene)
+        }
+        _ => Err(SceneError::UnsupportedVersion(version)),
+    }
+}
+
+fn scene_menu_actions(
+    mut actions: EventReader<SceneAction>,
+    mut scene_state: ResMut<SceneState>,
+) {
+    for action in actions.read() {
+        let current = scene_state
+            .current_path
+            .as_ref()
+            .map(|path| path.display().to_string());
+        match action {
+            SceneAction::Save => match scene_state.current_path.clone() {
+                Some(path) => scene_state.pending_save = Some(path),
+                None => {
+                    scene_state.dialog = Some(FileDialog {
+                        kind: DialogKind::SaveAs,
+                        path: "scene.ron".to_string(),
+                    });
+                }
+            },
+            SceneAction::SaveAs => {
+                scene_state.dialog = Some(FileDialog {
+                    kind: DialogKind::SaveAs,
+                    path: current.unwrap_or_else(|| "scene.ron".to_string()),
+                });
+            }
+            SceneAction::Open => {
+                scene_state.dialog = Some(FileDialog {
+                    kind: DialogKind::Open,
+                    path: current.unwrap_or_default(),
+                });
+            }
+        }
+    }
+}
+
+fn scene_dialog_system(
+    mut contexts: EguiContexts,
+    mut scene_state: ResMut<SceneState>,
+) {
+    let scene_state = &mut *scene_state;
+    let mut confirmed = None;
+    let mut cancelled = false;
+
+    if let Some(dialog) = scene_state.dialog.as_mut() {
+        let title = match dialog.kind {
+            DialogKind::SaveAs => "Save Scene As",
+            DialogKind::Open => "Open Scene",
+        };
+        egui::Window::new(title)
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(contexts.ctx_mut(), |ui| {
+                ui.label("File path:");
+                let response = ui.text_edit_singleline(&mut dialog.path);
+                let submitted = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                ui.horizontal(|ui| {
+                    let label = match dialog.kind {
+                        DialogKind::SaveAs => "Save",
+                        DialogKind::Open => "Open",
+                    };
+                    if (ui.button(label).clicked() || submitted) && !dialog.path.trim().is_empty() {
+                        confirmed = Some((dialog.kind, PathBuf::from(dialog.path.trim())));
+                    }
+                    if ui.button("Cancel").clicked() {
+                        cancelled = true;
+                    }
+                });
+            });
+    }
+
+    if let Some((kind, path)) = confirmed {
+        match kind {
+            DialogKind::SaveAs => scene_state.pending_save = Some(path),
+            DialogKind::Open => scene_state.pending_load = Some(path),
+        }
+        scene_state.dialog = None;
+    } else if cancelled {
+        scene_state.dialog = None;
+    }
+
+    let mut dismissed = false;
+    if let Some(error) = &scene_state.error {
+        egui::Window::new("Scene Error")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(contexts.ctx_mut(), |ui| {
+                ui.label(error);
+                if ui.button("OK").clicked() {
+                    dismissed = true;
+                }
+            });
+    }
+    if dismissed {
+        scene_state.error = None;
+    }
+}
+
+type SavedObjectQuery<'w, 's> = Query<'w, 's, (
+    Entity,
+    &'static ShapeType,
+    &'static Transform,
+    PhysicsQuery,
+    &'static Handle<StandardMaterial>,
+    (Option<&'static ImportedMesh>, Option<&'static PrimitiveShape>),
+    (Option<&'static Name>, &'static Visibility, Option<&'static Locked>),
+    Option<&'static PhysicsMaterialName>,
+), With<Selectable>>;
+
+type SavedGroundQuery<'w, 's> = Query<'w, 's, (
+    &'static Transform,
+    &'static Handle<Mesh>,
+    &'static Handle<StandardMaterial>,
+), With<Ground>>;
+
+type SceneEntityQuery<'w, 's> = Query<'w, 's, Entity, Or<(With<Selectable>, With<Ground>)>>;
+
+#[allow(clippy::too_many_arguments)]
+fn save_scene(
+    mut scene_state: ResMut<SceneState>,
+    materials: Res<Assets<StandardMaterial>>,
+    textures: Res<TextureLibrary>,
+    world: Res<WorldSettings>,
+    meshes: Res<Assets<Mesh>>,
+    objects: SavedObjectQuery,
+    ground: SavedGroundQuery,
+    joints: Query<(&JointSpec, &ImpulseJoint, &Parent)>,
+) {
+    let Some(path) = scene_state.pending_save.take() else {
+        return;
+    };
+
+    let ground = ground.get_single().ok().map(|(transform, mesh, material)| GroundData {
+        transform: transform.into(),
+        size: meshes
+            .get(mesh)
+            .and_then(|mesh| mesh.compute_aabb())
+            .map(|aabb| aabb.half_extents.x * 2.0)
+            .unwrap_or(GroundData::default().size),
+        color: materials
+            .get(material)
+            .map(|material| material.base_color.as_rgba_f32())
+            .unwrap_or(GroundData::default().color),
+    });
+
+    let mut entities = Vec::new();
+    let mut table = MaterialTable::default();
+    let objects = objects
+        .iter()
+        .map(|(entity, shape, transform, physics, material, source, outliner, physics_material)| {
+            entities.push(entity);
+            SceneObject::from_components(
+                *shape,
+                transform,
+                &physics.into(),
+                materials.get(material),
+            )
+            .with_mesh(source.0)
+            .with_primitive(source.1)
+            .with_outliner_state(outliner.0, outliner.1, outliner.2)
+            .with_physics_material(physics_material)
+            .with_material(table.index(material, &materials, &textures))
+        })
+        .collect();
+
+    let index_of = |entity: Entity| entities.iter().position(|saved| *saved == entity);
+    let joints = joints
+        .iter()
+        .filter_map(|(spec, joint, parent)| {
+            Some(JointData::new(index_of(joint.parent)?, index_of(parent.get())?, spec))
+        })
+        .collect();
+
+    let scene = SceneFile {
+        version: SCENE_VERSION,
+        ground,
+        objects,
+        joints,
+        materials: table.materials,
+        world: Some(world.clone()),
+    };
+
+    match scene.write(&path) {
+        Ok(()) => {
+            info!("Saved scene to {}", path.display());
+            scene_state.current_path = Some(path);
+        }
+        Err(err) => {
+            error!("Failed to save scene to {}: {}", path.display(), err);
+            scene_state.error = Some(format!("Failed to save {}: {}", path.display(), err));
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn load_scene(
+    mut commands: Commands,
+    mut scene_state: ResMut<SceneState>,
+    mut ui_state: ResMut<UiState>,
+    mut history: ResMut<History>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut images: ResMut<Assets<Image>>,
+    mut textures: ResMut<TextureLibrary>,
+    mut world: ResMut<WorldSettings>,
+    existing: SceneEntityQuery,
+) {
+    let Some(path) = scene_state.pending_load.take() else {
+        return;
+    };
+
+    // Parse everything before touching the world so a bad file leaves the current scene intact
+    let scene = match SceneFile::read(&path) {
+        Ok(scene) => scene,
+        Err(err) => {
+            error!("Failed to load scene from {}: {}", path.display(), err);
+            scene_state.error = Some(format!("Failed to open {}: {}", path.display(), err));
+            return;
+        }
+    };
+
+    for entity in existing.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    ui_state.clear_selection();
+    // Recorded commands point at entities that no longer exist
+    history.clear();
+
+    *world = scene.world.clone().unwrap_or_default().clamped();
+    if let Some(ground) = &scene.ground {
+        ground.spawn(&mut commands, &mut meshes, &mut materials);
+    }
+    let handles = scene.material_handles(&mut materials, &mut images, &mut textures);
+    let entities: Vec<Entity> = scene.objects
+        .iter()
+        .map(|object| {
+            let material = scene.object_material(&handles, object);
+            object.spawn(&mut commands, &mut meshes, &mut materials, material)
+        })
+        .collect();
+    for joint in &scene.joints {
+        match (entities.get(joint.body1), entities.get(joint.body2)) {
+            (Some(&body1), Some(&body2)) if body1 != body2 => {
+                spawn_joint(&mut commands, body1, body2, joint.spec());
+            }
+            _ => warn!("Skipping joint with invalid bodies {} and {}", joint.body1, joint.body2),
+        }
+    }
+
+    info!("Loaded {} objects from {}", scene.objects.len(), path.display());
+    scene_state.current_path = Some(path);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world_settings::TimestepData;
+
+    fn object() -> SceneObject {
+        let physics = PhysicsComponents {
+            friction: Some(Friction { coefficient: 0.3, combine_rule: CoefficientCombineRule::Max }),
+            sleeping: Some(Sleeping { linear_threshold: 0.1, angular_threshold: 0.2, sleeping: true }),
+            ..PhysicsComponents::defaults(Collider::cuboid(0.5, 1.0, 0.5))
+        };
+        let transform = Transform::from_xyz(1.0, 2.0, 3.0).with_rotation(Quat::from_rotation_y(0.5));
+        SceneObject::from_components(ShapeType::Cube, &transform, &physics, None)
+            .with_primitive(Some(&PrimitiveShape {
+                primitive: Primitive::Cuboid { half_extents: [0.5, 1.0, 0.5] },
+                offset: Vec3::ZERO,
+                rotation: Quat::IDENTITY,
+                sensor: false,
+            }))
+            .with_outliner_state(Some(&Name::new("Pillar")), &Visibility::Hidden, Some(&Locked(true)))
+    }
+
+    #[test]
+    fn saved_scenes_load_back_unchanged() {
+        let scene = SceneFile {
+            version: SCENE_VERSION,
+            ground: Some(GroundData::default()),
+            objects: vec![object(), object()],
+            joints: vec![JointData::new(0, 1, &JointSpec::between(
+                JointKind::Revolute,
+                &GlobalTransform::from_xyz(0.0, 1.0, 0.0),
+                &GlobalTransform::from_xyz(2.0, 1.0, 0.0),
+            ))],
+            materials: Vec::new(),
+            world: Some(WorldSettings { gravity: [0.0, -3.7, 0.0], ..default() }),
+        };
+        let path = std::env::temp_dir().join(format!("scene-round-trip-{}.ron", std::process::id()));
+        scene.write(&path).unwrap();
+        let loaded = SceneFile::read(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unwrap(), scene);
+    }
+
+    #[test]
+    fn loaded_world_settings_are_clamped() {
+        let world = WorldSettings {
+            timestep: TimestepData::Interpolated { dt: 0.0, time_scale: 1.0, substeps: 0 },
+            velocity_iterations: 0,
+            stabilization_iterations: 1000,
+            ..default()
+        }
+        .clamped();
+        assert_eq!(world.timestep, TimestepData::Interpolated { dt: 0.001, time_scale: 1.0, substeps: 1 });
+        assert_eq!((world.velocity_iterations, world.stabilization_iterations), (1, 64));
+        assert_eq!(WorldSettings::default().clamped(), WorldSettings::default());
+    }
+
+    #[test]
+    fn version_1_scenes_get_the_defaults_of_newer_fields() {
+        let text = r#"(
+            version: 1,
+            ground: Some((
+                transform: (translation: (0.0, -0.5, 0.0), rotation: (0.0, 0.0, 0.0, 1.0), scale: (1.0, 1.0, 1.0)),
+                size: 10.0,
+                color: (0.3, 0.5, 0.3, 1.0),
+            )),
+            objects: [(
+                shape: Sphere,
+                transform: (translation: (0.0, 2.0, 0.0), rotation: (0.0, 0.0, 0.0, 1.0), scale: (1.0, 1.0, 1.0)),
+                body: Dynamic,
+                linear_damping: 0.5,
+                angular_damping: 0.5,
+                restitution: 0.7,
+                friction: 0.5,
+                mass: Mass(1.0),
+                color: (0.8, 0.2, 0.2, 1.0),
+            )],
+        )"#;
+        let scene = SceneFile::from_ron(text).unwrap();
+        assert_eq!(scene.version, SCENE_VERSION);
+        assert_eq!(scene.ground, Some(GroundData::default()));
+        assert!(scene.joints.is_empty() && scene.materials.is_empty() && scene.world.is_none());
+
+        let [object] = scene.objects.as_slice() else {
+            panic!("expected one object, got {}", scene.objects.len());
+        };
+        assert_eq!(object.shape, ShapeType::Sphere);
+        assert_eq!(object.mass, MassData::Mass(1.0));
+        assert_eq!(object.color, [0.8, 0.2, 0.2, 1.0]);
+        assert_eq!((object.mesh.as_ref(), object.name.as_ref(), object.primitive), (None, None, None));
+        assert!(!object.hidden && !object.locked && !object.visual_only);
+        assert_eq!((object.friction_combine, object.restitution_combine), (CombineRule::Average, CombineRule::Average));
+        assert_eq!((object.physics_material.as_ref(), object.material), (None, None));
+        assert_eq!(object.sleep, SleepData::default());
+    }
+
+    #[test]
+    fn unknown_versions_are_errors() {
+        assert!(matches!(SceneFile::from_ron("(objects: [])"), Err(SceneError::MissingVersion)));
+        let newer = format!("(version: {}, ground: None, objects: [])", SCENE_VERSION + 1);
+        assert!(matches!(SceneFile::from_ron(&newer), Err(SceneError::UnsupportedVersion(_))));
+    }
+}
@@ -0,0 +1,233 @@
+use bevy::prelude::*;
+use bevy_egui::EguiContexts;
+use bevy_rapier3d::prelude::*;
+
+use crate::ui::Selectable;
+use crate::world_settings::{TimestepData, WorldSettings};
+
+pub struct SimulationPlugin;
+
+impl Plugin for SimulationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_state::<EditorMode>()
+           .add_event::<SimulationAction>()
+           .init_resource::<SimulationControl>()
+           .init_resource::<EditSnapshot>()
+           .add_systems(Update, (
+               step_hotkey,
+               handle_simulation_actions.after(step_hotkey),
+               drive_physics_pipeline.after(handle_simulation_actions),
+           ));
+    }
+}
+
+// Edit: physics is frozen while objects are positioned.
+// Playing: rapier steps every frame. Paused: frozen, but single steps can be requested.
+#[derive(States, Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EditorMode {
+    #[default]
+    Edit,
+    Playing,
+    Paused,
+}
+
+// Sent by the simulation buttons in the toolbar
+#[derive(Event, Clone, Copy, PartialEq)]
+pub enum SimulationAction {
+    Play,
+    Pause,
+    Step,
+    // Exactly one physics step, whatever the timestep mode
+    StepOnce,
+    Stop,
+}
+
+// Range of the toolbar's time scale, from slow motion to fast forward
+pub const TIME_SCALE_RANGE: std::ops::RangeInclusive<f32> = 0.01..=4.0;
+
+#[derive(Resource)]
+pub struct SimulationControl {
+    // How many physics frames a single Step advances
+    pub step_frames: u32,
+    steps_remaining: u32,
+    // Replaces the time scale of the world's timestep, a fixed timestep gets its dt scaled
+    pub time_scale: f32,
+    single_step: bool,
+    // Whether physics steps this frame, and with which timestep
+    active: bool,
+    timestep: TimestepMode,
+    // Simulated seconds and physics steps since leaving Edit mode
+    sim_time: f32,
+    step_count: u64,
+    // Simulated seconds per real second, smoothed over the last frames while playing
+    real_time_factor: f32,
+}
+
+impl Default for SimulationControl {
+    fn default() -> Self {
+        Self {
+            step_frames: 1,
+            steps_remaining: 0,
+            time_scale: 1.0,
+            single_step: false,
+            active: false,
+            timestep: TimestepMode::Variable { max_dt: 1.0 / 60.0, time_scale: 1.0, substeps: 1 },
+            sim_time: 0.0,
+            step_count: 0,
+            real_time_factor: 0.0,
+        }
+    }
+}
+
+impl SimulationControl {
+    pub fn sim_time(&self) -> f32 {
+        self.sim_time
+    }
+
+    pub fn step_count(&self) -> u64 {
+        self.step_count
+    }
+
+    pub fn real_time_factor(&self) -> f32 {
+        self.real_time_factor
+    }
+
+    pub fn active_timestep(&self) -> Option<TimestepMode> {
+        self.active.then_some(self.timestep)
+    }
+
+    // Called by the physics backend after stepping `elapsed` simulated seconds in a `delta` long frame
+    pub fn record_steps(&mut self, steps: u64, elapsed: f32, delta: f32, playing: bool) {
+        self.step_count += steps;
+        self.sim_time += elapsed;
+        if playing && delta > 0.0 {
+            // Averaged over about half a second, interpolated stepping is bursty frame to frame
+            let blend = 1.0 - (-delta / 0.5).exp();
+            self.real_time_factor += (elapsed / delta - self.real_time_factor) * blend;
+        }
+    }
+
+    // The timestep rapier should run with, given the world's settings
+    fn timestep_mode(&self, timestep: TimestepData) -> TimestepMode {
+        if self.single_step {
+            // A fixed step always runs exactly once per frame
+            return TimestepMode::Fixed {
+                dt: timestep.step_length() * self.time_scale,
+                substeps: timestep.substeps(),
+            };
+        }
+        timestep.with_time_scale(self.time_scale).into()
+    }
+}
+
+// State of every selectable body at the moment the simulation left Edit mode
+#[derive(Resource, Default)]
+struct EditSnapshot {
+    bodies: Vec<(Entity, Transform, Option<Velocity>)>,
+}
+
+fn take_snapshot(
+    snapshot: &mut EditSnapshot,
+    bodies: &Query<(Entity, &mut Transform, Option<&mut Velocity>), With<Selectable>>,
+) {
+    snapshot.bodies = bodies
+        .iter()
+        .map(|(entity, transform, velocity)| (entity, *transform, velocity.copied()))
+        .collect();
+}
+
+fn handle_simulation_actions(
+    mut actions: EventReader<SimulationAction>,
+    mode: Res<State<EditorMode>>,
+    mut next_mode: ResMut<NextState<EditorMode>>,
+    mut control: ResMut<SimulationControl>,
+    mut snapshot: ResMut<EditSnapshot>,
+    mut bodies: Query<(Entity, &mut Transform, Option<&mut Velocity>), With<Selectable>>,
+) {
+    let mut current = *mode.get();
+    for action in actions.read() {
+        match (action, current) {
+            (SimulationAction::Play, EditorMode::Edit) => {
+                take_snapshot(&mut snapshot, &bodies);
+                current = EditorMode::Playing;
+            }
+            (SimulationAction::Play, EditorMode::Paused) => {
+                current = EditorMode::Playing;
+            }
+            (SimulationAction::Pause, EditorMode::Playing) => {
+                current = EditorMode::Paused;
+            }
+            (SimulationAction::Step, EditorMode::Edit) => {
+                take_snapshot(&mut snapshot, &bodies);
+                control.steps_remaining = control.step_frames;
+                current = EditorMode::Paused;
+            }
+            (SimulationAction::Step, EditorMode::Paused) => {
+                control.steps_remaining = control.step_frames;
+            }
+            (SimulationAction::StepOnce, EditorMode::Edit) => {
+                take_snapshot(&mut snapshot, &bodies);
+                control.single_step = true;
+                current = EditorMode::Paused;
+            }
+            (SimulationAction::StepOnce, EditorMode::Paused) => {
+                control.single_step = true;
+            }
+            (SimulationAction::Stop, EditorMode::Playing | EditorMode::Paused) => {
+                for (entity, transform, velocity) in snapshot.bodies.drain(..) {
+                    // Bodies deleted while playing cannot be restored
+                    if let Ok((_, mut current_transform, current_velocity)) = bodies.get_mut(entity) {
+                        *current_transform = transform;
+                        if let Some(mut current_velocity) = current_velocity {
+                            *current_velocity = velocity.unwrap_or_default();
+                        }
+                    }
+                }
+                control.steps_remaining = 0;
+                control.single_step = false;
+                control.sim_time = 0.0;
+                control.step_count = 0;
+                control.real_time_factor = 0.0;
+                current = EditorMode::Edit;
+            }
+            _ => {}
+        }
+    }
+
+    if current != *mode.get() {
+        next_mode.set(current);
+    }
+}
+
+fn step_hotkey(
+    keyboard: Res<Input<KeyCode>>,
+    mut contexts: EguiContexts,
+    mut actions: EventWriter<SimulationAction>,
+) {
+    if !contexts.ctx_mut().wants_keyboard_input() && keyboard.just_pressed(KeyCode::Period) {
+        actions.send(SimulationAction::StepOnce);
+    }
+}
+
+fn drive_physics_pipeline(
+    mode: Res<State<EditorMode>>,
+    settings: Res<WorldSettings>,
+    mut control: ResMut<SimulationControl>,
+) {
+    control.timestep = control.timestep_mode(settings.timestep);
+    control.active = match mode.get() {
+        EditorMode::Edit => false,
+        EditorMode::Playing => true,
+        EditorMode::Paused => {
+            if control.single_step {
+                control.single_step = false;
+                true
+            } else if control.steps_remaining > 0 {
+                control.steps_remaining -= 1;
+                true
+            } else {
+                false
+            }
+        }
+    };
+}
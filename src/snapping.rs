@@ -0,0 +1,210 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use bevy_rapier3d::prelude::*;
+
+use crate::history::{ComponentChange, History};
+use crate::outliner::{is_locked, Locked};
+use crate::ui::{Selectable, UiState};
+
+pub struct SnappingPlugin;
+
+impl Plugin for SnappingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<DropToSurface>()
+           .init_resource::<SnapSettings>()
+           .add_systems(Update, (
+               update_snap_toggle,
+               drop_shortcut,
+               drop_to_surface.after(drop_shortcut),
+           ));
+    }
+}
+
+// Drops the selected object onto whatever is beneath it
+#[derive(Event)]
+pub struct DropToSurface;
+
+#[derive(Resource)]
+pub struct SnapSettings {
+    pub enabled: bool,
+    pub translate_step: f32,
+    pub rotate_step_degrees: f32,
+    pub scale_step: f32,
+    // `enabled`, inverted while Ctrl is held
+    active: bool,
+}
+
+impl Default for SnapSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            translate_step: 0.25,
+            rotate_step_degrees: 15.0,
+            scale_step: 0.1,
+            active: false,
+        }
+    }
+}
+
+impl SnapSettings {
+    pub fn translation(&self, value: f32) -> f32 {
+        self.snap(value, self.translate_step)
+    }
+
+    pub fn angle(&self, radians: f32) -> f32 {
+        self.snap(radians, self.rotate_step_degrees.to_radians())
+    }
+
+    pub fn scale(&self, value: f32) -> f32 {
+        if self.active && self.scale_step > 0.0 {
+            // Never snap a scale down to zero
+            self.snap(value, self.scale_step).max(self.scale_step)
+        } else {
+            value
+        }
+    }
+
+    fn snap(&self, value: f32, step: f32) -> f32 {
+        if self.active && step > 0.0 {
+            (value / step).round() * step
+        } else {
+            value
+        }
+    }
+}
+
+fn update_snap_toggle(
+    keyboard: Res<Input<KeyCode>>,
+    mut settings: ResMut<SnapSettings>,
+) {
+    let ctrl = keyboard.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    let active = settings.enabled != ctrl;
+    if settings.active != active {
+        settings.active = active;
+    }
+}
+
+// "Snapping" group of the toolbar
+pub fn snapping_toolbar(
+    ui: &mut egui::Ui,
+    settings: &mut SnapSettings,
+    drop: &mut EventWriter<DropToSurface>,
+    has_selection: bool,
+) {
+    ui.group(|ui| {
+        ui.checkbox(&mut settings.enabled, "🧲 Snap")
+            .on_hover_text("Hold Ctrl to toggle snapping temporarily");
+        ui.add(egui::DragValue::new(&mut settings.translate_step)
+            .prefix("Grid: ")
+            .speed(0.01)
+            .clamp_range(0.01..=10.0));
+        ui.add(egui::DragValue::new(&mut settings.rotate_step_degrees)
+            .prefix("Angle: ")
+            .suffix("°")
+            .speed(0.5)
+            .clamp_range(1.0..=90.0));
+        ui.add(egui::DragValue::new(&mut settings.scale_step)
+            .prefix("Scale: ")
+            .speed(0.01)
+            .clamp_range(0.01..=10.0));
+        if ui.add_enabled(has_selection, egui::Button::new("⤓ Drop to Surface"))
+            .on_hover_text("Place the selected objects on the surface below them (End)")
+            .clicked()
+        {
+            drop.send(DropToSurface);
+        }
+    });
+}
+
+fn drop_shortcut(
+    keyboard: Res<Input<KeyCode>>,
+    mut drop: EventWriter<DropToSurface>,
+    mut contexts: EguiContexts,
+) {
+    if !contexts.ctx_mut().wants_keyboard_input() && keyboard.just_pressed(KeyCode::End) {
+        drop.send(DropToSurface);
+    }
+}
+
+fn drop_to_surface(
+    mut events: EventReader<DropToSurface>,
+    ui_state: Res<UiState>,
+    rapier_context: Res<RapierContext>,
+    mut history: ResMut<History>,
+    mut objects: Query<(&mut Transform, &Collider, Option<&Locked>), With<Selectable>>,
+) {
+    if events.read().count() == 0 {
+        return;
+    }
+
+    let mut changes = Vec::new();
+    for &entity in &ui_state.selection {
+        let Ok((mut transform, collider, locked)) = objects.get_mut(entity) else {
+            continue;
+        };
+        if is_locked(locked) {
+            continue;
+        }
+        if let Some(dropped) = dropped_transform(&rapier_context, entity, &transform, collider) {
+            changes.push((entity, *transform, dropped));
+            *transform = dropped;
+        }
+    }
+
+    if !changes.is_empty() {
+        history.push(ComponentChange::batch(changes));
+        history.seal();
+    }
+}
+
+// Where `entity` comes to rest on the surface below it, with its up axis along the surface normal
+fn dropped_transform(
+    rapier_context: &RapierContext,
+    entity: Entity,
+    transform: &Transform,
+    collider: &Collider,
+) -> Option<Transform> {
+    let filter = QueryFilter::default()
+        .exclude_rigid_body(entity)
+        .exclude_collider(entity)
+        .exclude_sensors();
+    const MAX_DROP: f32 = 1000.0;
+
+    // Find the surface straight below and align the object's up axis with its normal
+    let (_, hit) = rapier_context.cast_ray_and_get_normal(
+        transform.translation,
+        Vec3::NEG_Y,
+        MAX_DROP,
+        true,
+        filter,
+    )?;
+    let normal = hit.normal.try_normalize().unwrap_or(Vec3::Y);
+    rest_on_surface(rapier_context, transform, normal, collider, MAX_DROP, filter)
+}
+
+// Turns the object's up axis to `normal`, then sweeps its collider along `-normal` so it ends up
+// resting flush on the first surface it meets. Also used to place new objects.
+pub fn rest_on_surface(
+    rapier_context: &RapierContext,
+    transform: &Transform,
+    normal: Vec3,
+    collider: &Collider,
+    max_distance: f32,
+    filter: QueryFilter,
+) -> Option<Transform> {
+    let rotation = (Quat::from_rotation_arc(transform.rotation * Vec3::Y, normal) * transform.rotation).normalize();
+    let (_, toi) = rapier_context.cast_shape(
+        transform.translation,
+        rotation,
+        -normal,
+        collider,
+        max_distance,
+        true,
+        filter,
+    )?;
+
+    let mut rested = *transform;
+    rested.rotation = rotation;
+    rested.translation -= normal * toi.toi;
+    Some(rested)
+}
@@ -0,0 +1,487 @@
+use bevy::prelude::*;
+use bevy_egui::EguiContexts;
+
+use crate::history::{ComponentChange, History};
+use crate::outliner::{is_locked, Locked};
+use crate::placement::Placement;
+use crate::snapping::SnapSettings;
+use crate::ui::{Selectable, Tool, UiState};
+
+pub struct TransformGizmoPlugin;
+
+impl Plugin for TransformGizmoPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GizmoState>()
+           .add_systems(Update, (
+               gizmo_interaction,
+               draw_transform_gizmo.after(gizmo_interaction),
+           ));
+    }
+}
+
+#[derive(Default, PartialEq, Clone, Copy, Debug)]
+pub enum GizmoSpace {
+    #[default]
+    World,
+    Local,
+}
+
+// Part of the gizmo under the cursor or being dragged. Indices are axes: 0 = X, 1 = Y, 2 = Z.
+#[derive(PartialEq, Clone, Copy, Debug)]
+enum GizmoHandle {
+    // Translate or scale along one axis
+    Axis(usize),
+    // Translate within the plane whose normal is the given axis
+    Plane(usize),
+    // Rotate around one axis
+    Ring(usize),
+    // Uniform scale
+    Center,
+}
+
+// Where the gizmo is drawn and which directions its handles follow
+#[derive(Clone, Copy)]
+struct GizmoFrame {
+    origin: Vec3,
+    axes: [Vec3; 3],
+    size: f32,
+    // Whether the axes follow the object's rotation rather than the world
+    local: bool,
+}
+
+impl GizmoFrame {
+    fn new(transform: &Transform, tool: Tool, space: GizmoSpace, camera_position: Vec3) -> Self {
+        // Scale always acts on the object's own axes
+        let local = space == GizmoSpace::Local || tool == Tool::Scale;
+        let rotation = if local { transform.rotation } else { Quat::IDENTITY };
+        Self {
+            origin: transform.translation,
+            axes: [rotation * Vec3::X, rotation * Vec3::Y, rotation * Vec3::Z],
+            // Keep a constant size on screen
+            size: (camera_position - transform.translation).length() * 0.15,
+            local,
+        }
+    }
+
+    fn plane_corners(&self, normal: usize) -> [Vec3; 4] {
+        let a = self.axes[(normal + 1) % 3] * self.size;
+        let b = self.axes[(normal + 2) % 3] * self.size;
+        [
+            self.origin + a * 0.25 + b * 0.25,
+            self.origin + a * 0.45 + b * 0.25,
+            self.origin + a * 0.45 + b * 0.45,
+            self.origin + a * 0.25 + b * 0.45,
+        ]
+    }
+
+    fn ring_radius(&self) -> f32 {
+        self.size * 0.9
+    }
+}
+
+struct ActiveDrag {
+    handle: GizmoHandle,
+    frame: GizmoFrame,
+    // The shared pivot of the selection, which the handles act on
+    start: Transform,
+    // Every dragged object with its transform when the drag began
+    targets: Vec<(Entity, Transform)>,
+    // Hit point on the handle's plane, or the point along the handle's axis, when the drag began
+    start_point: Vec3,
+    start_param: f32,
+}
+
+#[derive(Resource, Default)]
+struct GizmoState {
+    hovered: Option<GizmoHandle>,
+    active: Option<ActiveDrag>,
+}
+
+fn axis_color(axis: usize) -> Color {
+    match axis {
+        0 => Color::RED,
+        1 => Color::GREEN,
+        _ => Color::BLUE,
+    }
+}
+
+// Parameter along the line (origin + t * axis) of the point closest to the ray
+fn closest_line_param(ray: Ray, origin: Vec3, axis: Vec3) -> Option<f32> {
+    let w = ray.origin - origin;
+    let b = ray.direction.dot(axis);
+    let denom = 1.0 - b * b;
+    // The ray runs along the axis, there is no unique closest point
+    if denom.abs() < 1e-4 {
+        return None;
+    }
+    let d = ray.direction.dot(w);
+    let e = axis.dot(w);
+    Some((e - b * d) / denom)
+}
+
+fn ray_plane(ray: Ray, origin: Vec3, normal: Vec3) -> Option<Vec3> {
+    let denom = ray.direction.dot(normal);
+    if denom.abs() < 1e-4 {
+        return None;
+    }
+    let t = (origin - ray.origin).dot(normal) / denom;
+    (t >= 0.0).then(|| ray.get_point(t))
+}
+
+fn distance_to_ray(ray: Ray, point: Vec3) -> (f32, f32) {
+    let t = (point - ray.origin).dot(ray.direction).max(0.0);
+    (ray.get_point(t).distance(point), t)
+}
+
+fn point_in_quad(point: Vec3, corners: &[Vec3; 4]) -> bool {
+    let origin = corners[0];
+    let u = corners[1] - origin;
+    let v = corners[3] - origin;
+    let local = point - origin;
+    let (s, t) = (local.dot(u) / u.length_squared(), local.dot(v) / v.length_squared());
+    (0.0..=1.0).contains(&s) && (0.0..=1.0).contains(&t)
+}
+
+fn handles_for(tool: Tool) -> Vec<GizmoHandle> {
+    match tool {
+        Tool::Move => (0..3)
+            .map(GizmoHandle::Axis)
+            .chain((0..3).map(GizmoHandle::Plane))
+            .collect(),
+        Tool::Rotate => (0..3).map(GizmoHandle::Ring).collect(),
+        Tool::Scale => (0..3)
+            .map(GizmoHandle::Axis)
+            .chain(std::iter::once(GizmoHandle::Center))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+// Returns the handle nearest the camera along the ray, if any is under the cursor
+fn pick_handle(ray: Ray, frame: &GizmoFrame, tool: Tool) -> Option<GizmoHandle> {
+    let tolerance = frame.size * 0.08;
+    let mut best: Option<(f32, GizmoHandle)> = None;
+
+    for handle in handles_for(tool) {
+        let hit = match handle {
+            GizmoHandle::Axis(axis) => closest_line_param(ray, frame.origin, frame.axes[axis])
+                .filter(|t| (0.0..=frame.size).contains(t))
+                .map(|t| distance_to_ray(ray, frame.origin + frame.axes[axis] * t))
+                .filter(|(distance, _)| *distance < tolerance)
+                .map(|(_, depth)| depth),
+            GizmoHandle::Plane(normal) => ray_plane(ray, frame.origin, frame.axes[normal])
+                .filter(|point| point_in_quad(*point, &frame.plane_corners(normal)))
+                .map(|point| (point - ray.origin).length()),
+            GizmoHandle::Ring(axis) => ray_plane(ray, frame.origin, frame.axes[axis])
+                .filter(|point| ((*point - frame.origin).length() - frame.ring_radius()).abs() < tolerance)
+                .map(|point| (point - ray.origin).length()),
+            GizmoHandle::Center => Some(distance_to_ray(ray, frame.origin))
+                .filter(|(distance, _)| *distance < tolerance * 1.5)
+                .map(|(_, depth)| depth),
+        };
+        if let Some(depth) = hit {
+            if best.is_none_or(|(best_depth, _)| depth < best_depth) {
+                best = Some((depth, handle));
+            }
+        }
+    }
+
+    best.map(|(_, handle)| handle)
+}
+
+// Plane used to track the cursor while dragging a handle
+fn drag_plane_normal(handle: GizmoHandle, frame: &GizmoFrame, camera_forward: Vec3) -> Vec3 {
+    match handle {
+        GizmoHandle::Plane(axis) | GizmoHandle::Ring(axis) => frame.axes[axis],
+        _ => camera_forward,
+    }
+}
+
+fn begin_drag(
+    handle: GizmoHandle,
+    targets: Vec<(Entity, Transform)>,
+    transform: &Transform,
+    frame: GizmoFrame,
+    ray: Ray,
+    camera_forward: Vec3,
+) -> Option<ActiveDrag> {
+    let start_param = match handle {
+        GizmoHandle::Axis(axis) => closest_line_param(ray, frame.origin, frame.axes[axis])?,
+        _ => 0.0,
+    };
+    let start_point = ray_plane(ray, frame.origin, drag_plane_normal(handle, &frame, camera_forward))
+        .unwrap_or(frame.origin);
+    Some(ActiveDrag {
+        handle,
+        frame,
+        start: *transform,
+        targets,
+        start_point,
+        start_param,
+    })
+}
+
+// The transform the pivot should have for the current cursor ray
+fn drag_transform(
+    drag: &ActiveDrag,
+    tool: Tool,
+    ray: Ray,
+    camera_forward: Vec3,
+    snap: &SnapSettings,
+) -> Option<Transform> {
+    let frame = &drag.frame;
+    let mut transform = drag.start;
+
+    match (tool, drag.handle) {
+        (Tool::Move, GizmoHandle::Axis(axis)) => {
+            let t = closest_line_param(ray, frame.origin, frame.axes[axis])?;
+            if frame.local {
+                let offset = snap.translation(t - drag.start_param);
+                transform.translation = drag.start.translation + frame.axes[axis] * offset;
+            } else {
+                // World axes snap to absolute grid positions
+                transform.translation[axis] = snap.translation(
+                    drag.start.translation[axis] + (t - drag.start_param),
+                );
+            }
+        }
+        (Tool::Move, GizmoHandle::Plane(normal)) => {
+            let point = ray_plane(ray, frame.origin, frame.axes[normal])?;
+            let offset = point - drag.start_point;
+            let in_plane = [(normal + 1) % 3, (normal + 2) % 3];
+            if frame.local {
+                transform.translation = drag.start.translation;
+                for axis in in_plane {
+                    transform.translation += frame.axes[axis] * snap.translation(offset.dot(frame.axes[axis]));
+                }
+            } else {
+                for axis in in_plane {
+                    transform.translation[axis] = snap.translation(drag.start.translation[axis] + offset[axis]);
+                }
+            }
+        }
+        (Tool::Rotate, GizmoHandle::Ring(axis)) => {
+            let axis = frame.axes[axis];
+            let point = ray_plane(ray, frame.origin, axis)?;
+            let from = drag.start_point - frame.origin;
+            let to = point - frame.origin;
+            let angle = snap.angle(axis.dot(from.cross(to)).atan2(from.dot(to)));
+            transform.rotation = (Quat::from_axis_angle(axis, angle) * drag.start.rotation).normalize();
+        }
+        (Tool::Scale, GizmoHandle::Axis(axis)) => {
+            if drag.start_param.abs() < 1e-4 {
+                return None;
+            }
+            let t = closest_line_param(ray, frame.origin, frame.axes[axis])?;
+            let ratio = t / drag.start_param;
+            transform.scale[axis] = snap.scale((drag.start.scale[axis] * ratio).max(0.01));
+        }
+        (Tool::Scale, GizmoHandle::Center) => {
+            let point = ray_plane(ray, frame.origin, drag_plane_normal(drag.handle, frame, camera_forward))?;
+            let start_distance = (drag.start_point - frame.origin).length();
+            if start_distance < 1e-4 {
+                return None;
+            }
+            // Snap the uniform ratio so the object keeps its proportions
+            let ratio = snap.scale((point - frame.origin).length() / start_distance);
+            transform.scale = (drag.start.scale * ratio).max(Vec3::splat(0.01));
+        }
+        _ => return None,
+    }
+
+    Some(transform)
+}
+
+type GizmoTargetQuery<'w, 's> = Query<'w, 's, (&'static mut Transform, Option<&'static Locked>), With<Selectable>>;
+
+// The selected objects the gizmo acts on, with their transforms. Locked objects are left out.
+fn gizmo_targets(ui_state: &UiState, transforms: &GizmoTargetQuery) -> Vec<(Entity, Transform)> {
+    ui_state
+        .selection
+        .iter()
+        .filter_map(|entity| {
+            let (transform, locked) = transforms.get(*entity).ok()?;
+            (!is_locked(locked)).then_some((*entity, *transform))
+        })
+        .collect()
+}
+
+// Shared pivot of the targets: their center, oriented and scaled like the last (primary) one
+fn selection_pivot(targets: &[(Entity, Transform)]) -> Option<Transform> {
+    let (_, primary) = targets.last()?;
+    let center = targets.iter().map(|(_, transform)| transform.translation).sum::<Vec3>() / targets.len() as f32;
+    Some(Transform {
+        translation: center,
+        ..*primary
+    })
+}
+
+// Where an object ends up when the pivot moves from `start_pivot` to `pivot`, keeping its offset to it
+fn follow_pivot(start_pivot: &Transform, pivot: &Transform, start: &Transform) -> Transform {
+    let rotation = pivot.rotation * start_pivot.rotation.inverse();
+    let ratio = pivot.scale / start_pivot.scale;
+    // Offsets scale along the pivot's own axes
+    let offset = start.translation - start_pivot.translation;
+    let offset = start_pivot.rotation * (ratio * (start_pivot.rotation.inverse() * offset));
+    Transform {
+        translation: pivot.translation + rotation * offset,
+        rotation: (rotation * start.rotation).normalize(),
+        scale: start.scale * ratio,
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn gizmo_interaction(
+    ui_state: Res<UiState>,
+    mut state: ResMut<GizmoState>,
+    mut history: ResMut<History>,
+    snap: Res<SnapSettings>,
+    mouse_button: Res<Input<MouseButton>>,
+    windows: Query<&Window>,
+    cameras: Query<(&Camera, &GlobalTransform)>,
+    mut transforms: GizmoTargetQuery,
+    placement: Res<Placement>,
+    mut contexts: EguiContexts,
+) {
+    let tool = ui_state.selected_tool;
+    let targets = gizmo_targets(&ui_state, &transforms);
+    let pivot = selection_pivot(&targets)
+        .filter(|_| matches!(tool, Tool::Move | Tool::Rotate | Tool::Scale));
+    let Some(pivot) = pivot else {
+        state.hovered = None;
+        state.active = None;
+        return;
+    };
+
+    if !mouse_button.pressed(MouseButton::Left) {
+        state.active = None;
+    }
+
+    let Ok((camera, camera_transform)) = cameras.get_single() else {
+        return;
+    };
+    let Some(ray) = windows
+        .get_single()
+        .ok()
+        .and_then(|window| window.cursor_position())
+        .and_then(|cursor| camera.viewport_to_world(camera_transform, cursor))
+    else {
+        return;
+    };
+    let camera_forward = camera_transform.forward();
+
+    // A drag only continues while the selection it started with is unchanged
+    let same_selection = |drag: &&ActiveDrag| {
+        drag.targets.len() == targets.len()
+            && drag.targets.iter().zip(&targets).all(|((dragged, _), (target, _))| dragged == target)
+    };
+    if let Some(drag) = state.active.as_ref().filter(same_selection) {
+        let Some(dragged) = drag_transform(drag, tool, ray, camera_forward, &snap) else {
+            return;
+        };
+        let primary = drag.targets.last().map(|(entity, _)| *entity);
+        let mut changes = Vec::new();
+        for (entity, start) in &drag.targets {
+            let Ok((mut transform, _)) = transforms.get_mut(*entity) else {
+                continue;
+            };
+            let mut after = follow_pivot(&drag.start, &dragged, start);
+            if Some(*entity) == primary {
+                // Avoid rounding drift on the object the pivot is taken from
+                after.rotation = dragged.rotation;
+                after.scale = dragged.scale;
+            }
+            if after != *transform {
+                changes.push((*entity, *transform, after));
+                *transform = after;
+            }
+        }
+        if !changes.is_empty() {
+            // Consecutive drag frames collapse into a single history entry
+            history.push(ComponentChange::batch(changes));
+        }
+        return;
+    }
+
+    // Don't start drags through the UI or while placing a new object
+    if placement.is_placing() || contexts.ctx_mut().is_pointer_over_area() {
+        state.hovered = None;
+        return;
+    }
+
+    let frame = GizmoFrame::new(&pivot, tool, ui_state.gizmo_space, camera_transform.translation());
+    state.hovered = pick_handle(ray, &frame, tool);
+
+    if mouse_button.just_pressed(MouseButton::Left) {
+        if let Some(handle) = state.hovered {
+            state.active = begin_drag(handle, targets, &pivot, frame, ray, camera_forward);
+        }
+    }
+}
+
+fn draw_transform_gizmo(
+    mut gizmos: Gizmos,
+    ui_state: Res<UiState>,
+    state: Res<GizmoState>,
+    transforms: GizmoTargetQuery,
+    cameras: Query<&GlobalTransform, With<Camera>>,
+) {
+    let tool = ui_state.selected_tool;
+    if !matches!(tool, Tool::Move | Tool::Rotate | Tool::Scale) {
+        return;
+    }
+    let (Some(pivot), Ok(camera_transform)) = (selection_pivot(&gizmo_targets(&ui_state, &transforms)), cameras.get_single()) else {
+        return;
+    };
+
+    let frame = match &state.active {
+        // Keep the axes the drag started with so they don't rotate under the cursor
+        Some(drag) => GizmoFrame { origin: pivot.translation, ..drag.frame },
+        None => GizmoFrame::new(&pivot, tool, ui_state.gizmo_space, camera_transform.translation()),
+    };
+    let highlighted = state.active.as_ref().map(|drag| drag.handle).or(state.hovered);
+    let color_for = |handle: GizmoHandle, base: Color| {
+        if highlighted == Some(handle) { Color::YELLOW } else { base }
+    };
+
+    for handle in handles_for(tool) {
+        match handle {
+            GizmoHandle::Axis(axis) => {
+                let color = color_for(handle, axis_color(axis));
+                let direction = frame.axes[axis];
+                let tip = frame.origin + direction * frame.size;
+                gizmos.line(frame.origin, tip, color);
+                if tool == Tool::Scale {
+                    let rotation = Quat::from_mat3(&Mat3::from_cols(frame.axes[0], frame.axes[1], frame.axes[2]));
+                    gizmos.cuboid(
+                        Transform::from_translation(tip)
+                            .with_rotation(rotation)
+                            .with_scale(Vec3::splat(frame.size * 0.08)),
+                        color,
+                    );
+                } else {
+                    // Arrow head
+                    let side = frame.axes[(axis + 1) % 3] * frame.size * 0.05;
+                    let up = frame.axes[(axis + 2) % 3] * frame.size * 0.05;
+                    let base = tip - direction * frame.size * 0.15;
+                    for offset in [side, -side, up, -up] {
+                        gizmos.line(tip, base + offset, color);
+                    }
+                }
+            }
+            GizmoHandle::Plane(normal) => {
+                let color = color_for(handle, axis_color(normal));
+                let corners = frame.plane_corners(normal);
+                gizmos.linestrip(corners.into_iter().chain(std::iter::once(corners[0])), color);
+            }
+            GizmoHandle::Ring(axis) => {
+                gizmos
+                    .circle(frame.origin, frame.axes[axis], frame.ring_radius(), color_for(handle, axis_color(axis)))
+                    .segments(48);
+            }
+            GizmoHandle::Center => {
+                gizmos.sphere(frame.origin, Quat::IDENTITY, frame.size * 0.08, color_for(handle, Color::WHITE));
+            }
+        }
+    }
+}
@@ -1,6 +1,28 @@
+use bevy::ecs::query::WorldQuery;
+use bevy::ecs::system::SystemParam;
 use bevy::prelude::*;
+use bevy::render::primitives::Aabb;
 use bevy_egui::{egui, EguiContexts, EguiPlugin};
 use bevy_rapier3d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::edit_actions::EditAction;
+use crate::history::{CommandGroup, ComponentChange, History, HistoryRequest};
+use crate::joints::{joints_inspector, spawn_joint, JointKind, JointLifetime, JointQuery, JointSpec};
+use crate::mass_properties::{mass_inspector, resulting_mass_properties};
+use crate::mesh_import::{mesh_collider_inspector, ImportedMesh, OpenMeshImport};
+use crate::outliner::{is_locked, Locked};
+use crate::physics_materials::{combine_rule_editor, physics_material_picker, CombineRule, MaterialLibrary, OpenMaterialLibrary, PhysicsMaterialName};
+use crate::placement::{AddObject, Placement};
+use crate::primitives::{collider_inspector, primitive_editor, Primitive, PrimitiveShape};
+use crate::render_materials::{material_inspector, MaterialEditor};
+use crate::physics::{AabbOverlay, PhysicsWorld, SleepingOverlay};
+use crate::physics_components::{fitted_collider, ColliderFit, PhysicsChange, PhysicsComponents};
+use crate::scene::SceneAction;
+use crate::simulation::{EditorMode, SimulationAction, SimulationControl, TIME_SCALE_RANGE};
+use crate::snapping::{snapping_toolbar, DropToSurface, SnapSettings};
+use crate::transform_gizmo::GizmoSpace;
+use crate::world_settings::OpenWorldSettings;
 
 pub struct UiPlugin;
 
@@ -12,257 +34,464 @@ impl Plugin for UiPlugin {
                ui_system,
                toolbar_system,
                handle_selection.after(ui_system),  // Run after UI to check if UI was clicked
-               handle_transform_tools,
+               draw_selection,
            ));
     }
 }
 
 #[derive(Resource, Default)]
-struct UiState {
-    selected_entity: Option<Entity>,
-    selected_tool: Tool,
-    selected_shape: ShapeType,
-    dragging: bool,
-    drag_start: Option<Vec2>,
+pub struct UiState {
+    // Selected objects in the order they were picked, the last one is the primary selection
+    pub selection: Vec<Entity>,
+    pub selected_tool: Tool,
+    pub gizmo_space: GizmoSpace,
     ui_received_click: bool,
+    joint_kind: JointKind,
+    // First body picked by the Joint tool, waiting for the second one
+    joint_first: Option<Entity>,
+    // Cursor position where a box selection started
+    marquee_start: Option<Vec2>,
+    // How Add Physics shapes new colliders
+    collider_fit: ColliderFit,
+    // Parameters picked in the Add Object menu, kept between spawns
+    new_primitives: Vec<Primitive>,
+}
+
+impl UiState {
+    // The object the inspector sections and gizmo orientation follow
+    pub fn primary_selection(&self) -> Option<Entity> {
+        self.selection.last().copied()
+    }
+
+    pub fn is_selected(&self, entity: Entity) -> bool {
+        self.selection.contains(&entity)
+    }
+
+    pub fn select(&mut self, entity: Entity, mode: SelectMode) {
+        let was_selected = self.is_selected(entity);
+        match mode {
+            SelectMode::Replace => self.selection.clear(),
+            SelectMode::Add => self.selection.retain(|selected| *selected != entity),
+            SelectMode::Toggle => {
+                self.selection.retain(|selected| *selected != entity);
+                if was_selected {
+                    return;
+                }
+            }
+        }
+        self.selection.push(entity);
+    }
+
+    pub fn clear_selection(&mut self) {
+        self.selection.clear();
+    }
+
+    fn new_primitive(&mut self, shape_type: ShapeType) -> Option<&mut Primitive> {
+        let index = match self.new_primitives.iter().position(|primitive| primitive.shape_type() == shape_type) {
+            Some(index) => index,
+            None => {
+                self.new_primitives.push(Primitive::unit(shape_type)?);
+                self.new_primitives.len() - 1
+            }
+        };
+        self.new_primitives.get_mut(index)
+    }
+}
+
+// How a click or box selection combines with the current selection
+#[derive(PartialEq, Clone, Copy)]
+pub enum SelectMode {
+    Replace,
+    // Shift
+    Add,
+    // Ctrl
+    Toggle,
+}
+
+impl SelectMode {
+    fn from_keyboard(keyboard: &Input<KeyCode>) -> Self {
+        if keyboard.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+            SelectMode::Add
+        } else if keyboard.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+            SelectMode::Toggle
+        } else {
+            SelectMode::Replace
+        }
+    }
 }
 
 #[derive(Default, PartialEq, Clone, Copy)]
-enum Tool {
+pub enum Tool {
     #[default]
     Select,
     Move,
     Rotate,
     Scale,
+    Joint,
 }
 
-#[derive(Default, PartialEq, Clone, Copy)]
-enum ShapeType {
+#[derive(Component, Default, PartialEq, Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum ShapeType {
     #[default]
     Cube,
     Sphere,
     Cylinder,
     Cone,
     Capsule,
+    // Geometry loaded from a file, described by an `ImportedMesh` component
+    Imported,
+    Torus,
+    Wedge,
+    Stairs,
+    Tube,
+    Plate,
+    RoundCuboid,
+}
+
+impl ShapeType {
+    // Shapes built from a `Primitive`, everything but imported meshes
+    pub const PRIMITIVES: [ShapeType; 11] = [
+        ShapeType::Cube,
+        ShapeType::Sphere,
+        ShapeType::Cylinder,
+        ShapeType::Cone,
+        ShapeType::Capsule,
+        ShapeType::Torus,
+        ShapeType::Wedge,
+        ShapeType::Stairs,
+        ShapeType::Tube,
+        ShapeType::Plate,
+        ShapeType::RoundCuboid,
+    ];
+
+    // Shapes whose parameters are set in the Add Object menu before spawning
+    pub const PARAMETRIC: [ShapeType; 6] = [
+        ShapeType::Torus,
+        ShapeType::Wedge,
+        ShapeType::Stairs,
+        ShapeType::Tube,
+        ShapeType::Plate,
+        ShapeType::RoundCuboid,
+    ];
+
+    // Default name of newly spawned objects
+    pub fn label(self) -> &'static str {
+        match self {
+            ShapeType::Cube => "Cube",
+            ShapeType::Sphere => "Sphere",
+            ShapeType::Cylinder => "Cylinder",
+            ShapeType::Cone => "Cone",
+            ShapeType::Capsule => "Capsule",
+            ShapeType::Imported => "Mesh",
+            ShapeType::Torus => "Torus",
+            ShapeType::Wedge => "Ramp",
+            ShapeType::Stairs => "Stairs",
+            ShapeType::Tube => "Tube",
+            ShapeType::Plate => "Plate",
+            ShapeType::RoundCuboid => "Rounded Cube",
+        }
+    }
 }
 
 // Component to mark selectable objects
 #[derive(Component)]
-struct Selectable;
+pub struct Selectable;
 
-fn spawn_shape(
+pub fn spawn_shape(
     commands: &mut Commands,
     meshes: &mut ResMut<Assets<Mesh>>,
     materials: &mut ResMut<Assets<StandardMaterial>>,
     shape_type: ShapeType,
     position: Vec3,
 ) -> Entity {
-    let (mesh, collider) = match shape_type {
-        ShapeType::Cube => (
-            Mesh::from(shape::Box::new(1.0, 1.0, 1.0)),
-            Collider::cuboid(0.5, 0.5, 0.5),
-        ),
-        ShapeType::Sphere => (
-            Mesh::from(shape::UVSphere {
-                radius: 0.5,
-                sectors: 32,
-                stacks: 16,
-            }),
-            Collider::ball(0.5),
-        ),
-        ShapeType::Cylinder => (
-            Mesh::from(shape::Cylinder {
-                radius: 0.5,
-                height: 1.0,
-                resolution: 32,
-                segments: 1,
-            }),
-            Collider::cylinder(0.5, 0.5),
-        ),
-        ShapeType::Cone => {
-            // Create a cone by scaling a cylinder
-            let mut cone_mesh = Mesh::from(shape::Cylinder {
-                radius: 0.5,
-                height: 1.0,
-                resolution: 32,
-                segments: 1,
-            });
-            
-            // Modify the vertices to create a cone shape
-            if let Some(positions) = cone_mesh.attribute_mut(Mesh::ATTRIBUTE_POSITION) {
-                if let bevy::render::mesh::VertexAttributeValues::Float32x3(positions) = positions {
-                    for position in positions.iter_mut() {
-                        if position[1] > 0.0 { // Top vertices
-                            position[0] = 0.0;
-                            position[2] = 0.0;
-                        }
-                    }
-                }
-            }
-            
-            (
-                cone_mesh,
-                // For cone we'll use a custom compound collider
-                Collider::compound(vec![
-                    // Cone base (cylinder with small height)
-                    (
-                        Vec3::new(0.0, -0.45, 0.0),
-                        Quat::IDENTITY,
-                        Collider::cylinder(0.5, 0.1),
-                    ),
-                    // Cone body (approximated with cylinder)
-                    (
-                        Vec3::ZERO,
-                        Quat::IDENTITY,
-                        Collider::cylinder(0.25, 0.8),
-                    ),
-                ]),
-            )
-        },
-        ShapeType::Capsule => (
-            Mesh::from(shape::Capsule {
-                radius: 0.5,
-                rings: 16,
-                depth: 1.0,
-                latitudes: 16,
-                longitudes: 32,
-                uv_profile: default(),
-            }),
-            Collider::capsule_y(0.5, 0.5), // height/2, radius
-        ),
-    };
+    match Primitive::unit(shape_type) {
+        Some(primitive) => spawn_primitive(commands, meshes, materials, PrimitiveShape::new(primitive), position),
+        // Imported meshes are built by `mesh_import`; a unit cube stands in when their file is unavailable
+        None => {
+            let mesh = meshes.add(Mesh::from(shape::Box::new(1.0, 1.0, 1.0)));
+            spawn_body(commands, mesh, materials, Collider::cuboid(0.5, 0.5, 0.5), shape_type, position)
+        }
+    }
+}
 
-    commands.spawn((
+// Spawns a built-in shape whose mesh and collider are both generated from `shape`
+pub fn spawn_primitive(
+    commands: &mut Commands,
+    meshes: &mut ResMut<Assets<Mesh>>,
+    materials: &mut ResMut<Assets<StandardMaterial>>,
+    shape: PrimitiveShape,
+    position: Vec3,
+) -> Entity {
+    let entity = spawn_body(
+        commands,
+        meshes.add(shape.mesh()),
+        materials,
+        shape.collider(),
+        shape.primitive.shape_type(),
+        position,
+    );
+    commands.entity(entity).insert(shape);
+    if shape.sensor {
+        commands.entity(entity).insert(Sensor);
+    }
+    entity
+}
+
+// Spawns a selectable dynamic body with the default physics setup shared by every editor object
+pub fn spawn_body(
+    commands: &mut Commands,
+    mesh: Handle<Mesh>,
+    materials: &mut ResMut<Assets<StandardMaterial>>,
+    collider: Collider,
+    shape_type: ShapeType,
+    position: Vec3,
+) -> Entity {
+    let entity = commands.spawn((
         PbrBundle {
-            mesh: meshes.add(mesh),
+            mesh,
             material: materials.add(Color::rgb(0.8, 0.7, 0.6).into()),
             transform: Transform::from_translation(position),
             ..default()
         },
         Selectable,
-        RigidBody::Dynamic,
-        collider,
-        ColliderMassProperties::Mass(1.0),
-        Restitution::coefficient(0.7),
-        Friction::coefficient(0.5),
-        Damping {
-            linear_damping: 0.5,
-            angular_damping: 0.5,
-        },
-    )).id()
+        Name::new(shape_type.label()),
+        shape_type,
+    )).id();
+    PhysicsComponents::defaults(collider).apply_deferred(commands, entity);
+    entity
 }
 
+type PickableQuery<'w, 's> = Query<'w, 's, (
+    Entity,
+    &'static GlobalTransform,
+    &'static Visibility,
+    Option<&'static Locked>,
+), With<Selectable>>;
+
+// Hidden and locked objects can't be clicked or box selected
+fn is_pickable(selectables: &PickableQuery, entity: Entity) -> bool {
+    selectables
+        .get(entity)
+        .is_ok_and(|(_, _, visibility, locked)| *visibility != Visibility::Hidden && !is_locked(locked))
+}
+
+// Box selections smaller than this, in pixels, count as a click on empty space
+const MARQUEE_MIN_SIZE: f32 = 4.0;
+
+#[allow(clippy::too_many_arguments)]
 fn handle_selection(
     mut ui_state: ResMut<UiState>,
+    mut history: ResMut<History>,
+    mut commands: Commands,
     mouse_button: Res<Input<MouseButton>>,
+    keyboard: Res<Input<KeyCode>>,
     windows: Query<&Window>,
     cameras: Query<(&Camera, &GlobalTransform)>,
-    rapier_context: Res<RapierContext>,
+    selectables: PickableQuery,
+    physics: Res<PhysicsWorld>,
+    placement: Res<Placement>,
     mut contexts: EguiContexts,
 ) {
+    if ui_state.selected_tool != Tool::Joint {
+        ui_state.joint_first = None;
+    }
+    if ui_state.selected_tool != Tool::Select {
+        ui_state.marquee_start = None;
+    }
+
+    let (Ok(window), Ok((camera, camera_transform))) = (windows.get_single(), cameras.get_single()) else {
+        return;
+    };
+    let cursor = window.cursor_position();
+    let mode = SelectMode::from_keyboard(&keyboard);
+
+    if let Some(start) = ui_state.marquee_start {
+        let end = cursor.unwrap_or(start);
+        let rect = Rect::from_corners(start, end);
+
+        if mouse_button.pressed(MouseButton::Left) {
+            let painter = contexts.ctx_mut().layer_painter(egui::LayerId::new(
+                egui::Order::Foreground,
+                egui::Id::new("marquee"),
+            ));
+            let rect = egui::Rect::from_min_max(egui::pos2(rect.min.x, rect.min.y), egui::pos2(rect.max.x, rect.max.y));
+            painter.rect(
+                rect,
+                0.0,
+                egui::Color32::from_rgba_unmultiplied(100, 150, 255, 30),
+                egui::Stroke::new(1.0, egui::Color32::from_rgb(100, 150, 255)),
+            );
+            return;
+        }
+
+        ui_state.marquee_start = None;
+        if rect.size().max_element() < MARQUEE_MIN_SIZE {
+            // Plain click on empty space
+            if mode == SelectMode::Replace {
+                ui_state.clear_selection();
+            }
+            return;
+        }
+
+        if mode == SelectMode::Replace {
+            ui_state.clear_selection();
+        }
+        let mode = if mode == SelectMode::Toggle { SelectMode::Toggle } else { SelectMode::Add };
+        for (entity, transform, _, _) in selectables.iter() {
+            if !is_pickable(&selectables, entity) {
+                continue;
+            }
+            let inside = camera
+                .world_to_viewport(camera_transform, transform.translation())
+                .is_some_and(|point| rect.contains(point));
+            if inside {
+                ui_state.select(entity, mode);
+            }
+        }
+        return;
+    }
+
     // Only handle selection if UI didn't receive the click and we're not hovering over UI
     if !ui_state.ui_received_click && 
+       !placement.is_placing() &&
        !contexts.ctx_mut().is_pointer_over_area() &&
-       ui_state.selected_tool == Tool::Select && 
+       matches!(ui_state.selected_tool, Tool::Select | Tool::Joint) && 
        mouse_button.just_pressed(MouseButton::Left) 
     {
-        if let Ok(window) = windows.get_single() {
-            if let Some(cursor_pos) = window.cursor_position() {
-                if let Ok((camera, camera_transform)) = cameras.get_single() {
-                    if let Some(ray) = camera.viewport_to_world(camera_transform, cursor_pos) {
-                        let ray_pos = ray.origin;
-                        let ray_dir = ray.direction;
-
-                        if let Some((entity, _toi)) = rapier_context.cast_ray(
-                            ray_pos,
-                            ray_dir,
-                            f32::MAX,
-                            true,
-                            QueryFilter::default(),
-                        ) {
-                            ui_state.selected_entity = Some(entity);
-                        } else {
-                            // Only deselect if we're not over any UI element
-                            ui_state.selected_entity = None;
-                        }
+        let Some(cursor_pos) = cursor else {
+            return;
+        };
+        let Some(ray) = camera.viewport_to_world(camera_transform, cursor_pos) else {
+            return;
+        };
+
+        // Clicks pass through hidden and locked objects to whatever is behind them
+        let pickable = |entity| is_pickable(&selectables, entity);
+        let hit = physics.cast_ray(ray, f32::MAX, &pickable);
+
+        if ui_state.selected_tool == Tool::Joint {
+            // Joints connect two selectable bodies, picked one after the other
+            let Some((entity, _toi)) = hit else {
+                return;
+            };
+            ui_state.select(entity, SelectMode::Replace);
+            match ui_state.joint_first.take() {
+                Some(first) if first != entity => {
+                    if let (Ok((_, transform1, ..)), Ok((_, transform2, ..))) = (selectables.get(first), selectables.get(entity)) {
+                        let spec = JointSpec::between(ui_state.joint_kind, transform1, transform2);
+                        let joint = spawn_joint(&mut commands, first, entity, spec);
+                        history.push(JointLifetime::created(joint, first, entity, spec));
+                        history.seal();
                     }
                 }
+                _ => ui_state.joint_first = Some(entity),
             }
+        } else if let Some((entity, _toi)) = hit {
+            ui_state.select(entity, mode);
+        } else {
+            // Dragging from empty space starts a box selection, releasing without a drag deselects
+            ui_state.marquee_start = Some(cursor_pos);
         }
     }
 }
 
-fn handle_transform_tools(
-    mut ui_state: ResMut<UiState>,
-    mouse_button: Res<Input<MouseButton>>,
-    windows: Query<&Window>,
-    mut transforms: Query<&mut Transform>,
-    cameras: Query<(&Camera, &GlobalTransform)>,
-    mut contexts: EguiContexts,
+// Outlines every selected object, the primary selection in a brighter colour
+fn draw_selection(
+    mut gizmos: Gizmos,
+    ui_state: Res<UiState>,
+    objects: Query<(&GlobalTransform, &Aabb), With<Selectable>>,
 ) {
-    // Don't handle transform tools if we're over UI
-    if contexts.ctx_mut().is_pointer_over_area() {
-        return;
+    let primary = ui_state.primary_selection();
+    for &entity in &ui_state.selection {
+        let Ok((transform, aabb)) = objects.get(entity) else {
+            continue;
+        };
+        let local = Transform::from_translation(aabb.center.into())
+            .with_scale(Vec3::from(aabb.half_extents) * 2.0);
+        let color = if Some(entity) == primary { Color::YELLOW } else { Color::ORANGE };
+        gizmos.cuboid(transform.mul_transform(local), color);
     }
+}
 
-    if ui_state.selected_tool != Tool::Select {
-        if let Some(selected_entity) = ui_state.selected_entity {
-            if let Ok(window) = windows.get_single() {
-                if let Some(cursor_pos) = window.cursor_position() {
-                    if mouse_button.just_pressed(MouseButton::Left) {
-                        ui_state.dragging = true;
-                        ui_state.drag_start = Some(cursor_pos);
-                    } else if mouse_button.just_released(MouseButton::Left) {
-                        ui_state.dragging = false;
-                        ui_state.drag_start = None;
-                    }
-
-                    if ui_state.dragging {
-                        if let Some(drag_start) = ui_state.drag_start {
-                            let delta = cursor_pos - drag_start;
-                            if let Ok(mut transform) = transforms.get_mut(selected_entity) {
-                                match ui_state.selected_tool {
-                                    Tool::Move => {
-                                        if let Ok((camera, camera_transform)) = cameras.get_single() {
-                                            let forward = -camera_transform.forward();
-                                            let right = camera_transform.right();
-                                            let movement = right * delta.x * 0.01 + forward * delta.y * 0.01;
-                                            transform.translation += movement;
-                                        }
-                                    }
-                                    Tool::Rotate => {
-                                        let rotation = Quat::from_euler(
-                                            EulerRot::XYZ,
-                                            delta.y * 0.01,
-                                            delta.x * 0.01,
-                                            0.0,
-                                        );
-                                        transform.rotate(rotation);
-                                    }
-                                    Tool::Scale => {
-                                        let scale_factor = 1.0 + delta.x * 0.01;
-                                        transform.scale *= scale_factor;
-                                    }
-                                    _ => {}
-                                }
-                            }
-                            ui_state.drag_start = Some(cursor_pos);
-                        }
-                    }
-                }
-            }
-        }
-    }
+// Events the toolbar can send to other plugins
+#[derive(SystemParam)]
+struct ToolbarActions<'w> {
+    scene: EventWriter<'w, SceneAction>,
+    simulation: EventWriter<'w, SimulationAction>,
+    mesh_import: EventWriter<'w, OpenMeshImport>,
+    drop_to_surface: EventWriter<'w, DropToSurface>,
+    edit: EventWriter<'w, EditAction>,
+    add_object: EventWriter<'w, AddObject>,
+    material_library: EventWriter<'w, OpenMaterialLibrary>,
+    world_settings: EventWriter<'w, OpenWorldSettings>,
 }
 
+#[allow(clippy::too_many_arguments)]
 fn toolbar_system(
     mut contexts: EguiContexts,
     mut ui_state: ResMut<UiState>,
-    mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut history: ResMut<History>,
+    mode: Res<State<EditorMode>>,
+    mut simulation: ResMut<SimulationControl>,
+    physics: Res<PhysicsWorld>,
+    mut aabb_overlay: ResMut<AabbOverlay>,
+    mut sleeping_overlay: ResMut<SleepingOverlay>,
+    mut snap: ResMut<SnapSettings>,
+    mut placement: ResMut<Placement>,
+    mut actions: ToolbarActions,
 ) {
+    let mut spawn = None;
+    let mode = *mode.get();
+
     egui::TopBottomPanel::top("toolbar").show(contexts.ctx_mut(), |ui| {
+        egui::menu::bar(ui, |ui| {
+            ui.menu_button("File", |ui| {
+                if ui.button("📂 Open...").clicked() {
+                    actions.scene.send(SceneAction::Open);
+                    ui.close_menu();
+                }
+                if ui.button("💾 Save").clicked() {
+                    actions.scene.send(SceneAction::Save);
+                    ui.close_menu();
+                }
+                if ui.button("💾 Save As...").clicked() {
+                    actions.scene.send(SceneAction::SaveAs);
+                    ui.close_menu();
+                }
+            });
+            ui.menu_button("Edit", |ui| {
+                let has_selection = !ui_state.selection.is_empty();
+                for (action, label, enabled) in [
+                    (EditAction::Copy, "📋 Copy (Ctrl+C)", has_selection),
+                    (EditAction::Paste, "📥 Paste (Ctrl+V)", true),
+                    (EditAction::Duplicate, "⧉ Duplicate (Ctrl+D)", has_selection),
+                    (EditAction::Delete, "🗑 Delete (Del)", has_selection),
+                ] {
+                    if ui.add_enabled(enabled, egui::Button::new(label)).clicked() {
+                        actions.edit.send(action);
+                        ui.close_menu();
+                    }
+                }
+                ui.separator();
+                if ui.button("🧱 Physics Materials...").clicked() {
+                    actions.material_library.send(OpenMaterialLibrary);
+                    ui.close_menu();
+                }
+                if ui.button("🌍 World Settings...").clicked() {
+                    actions.world_settings.send(OpenWorldSettings);
+                    ui.close_menu();
+                }
+            });
+            ui.menu_button("View", |ui| {
+                ui.checkbox(&mut aabb_overlay.show, "Broad phase AABBs")
+                    .on_hover_text("Orange boxes overlap another one");
+                ui.add_enabled(aabb_overlay.show, egui::Checkbox::new(&mut aabb_overlay.tree_nodes, "Tree nodes"))
+                    .on_hover_text("Inner nodes of the dynamic tree, native backend only");
+                ui.checkbox(&mut sleeping_overlay.show, "Sleeping bodies")
+                    .on_hover_text("Tints the bodies the solver has put to sleep");
+            });
+        });
+
         ui.horizontal(|ui| {
             // Tools section
             ui.group(|ui| {
@@ -271,54 +500,353 @@ fn toolbar_system(
                 ui.selectable_value(&mut ui_state.selected_tool, Tool::Move, "↔️ Move");
                 ui.selectable_value(&mut ui_state.selected_tool, Tool::Rotate, "🔄 Rotate");
                 ui.selectable_value(&mut ui_state.selected_tool, Tool::Scale, "⇲ Scale");
+                ui.selectable_value(&mut ui_state.selected_tool, Tool::Joint, "🔗 Joint");
+
+                if matches!(ui_state.selected_tool, Tool::Move | Tool::Rotate) {
+                    ui.separator();
+                    ui.selectable_value(&mut ui_state.gizmo_space, GizmoSpace::World, "🌐 World");
+                    ui.selectable_value(&mut ui_state.gizmo_space, GizmoSpace::Local, "📦 Local");
+                }
+
+                if ui_state.selected_tool == Tool::Joint {
+                    egui::ComboBox::from_id_source("joint_kind")
+                        .selected_text(ui_state.joint_kind.label())
+                        .show_ui(ui, |ui| {
+                            for kind in JointKind::ALL {
+                                ui.selectable_value(&mut ui_state.joint_kind, kind, kind.label());
+                            }
+                        });
+                    ui.label(if ui_state.joint_first.is_some() {
+                        "Pick the second body"
+                    } else {
+                        "Pick the first body"
+                    });
+                }
             });
             
+            ui.separator();
+
+            // Simulation section
+            ui.group(|ui| {
+                ui.label(match mode {
+                    EditorMode::Edit => "Edit",
+                    EditorMode::Playing => "Playing",
+                    EditorMode::Paused => "Paused",
+                });
+                if ui.add_enabled(mode != EditorMode::Playing, egui::Button::new("▶ Play")).clicked() {
+                    actions.simulation.send(SimulationAction::Play);
+                }
+                if ui.add_enabled(mode == EditorMode::Playing, egui::Button::new("⏸ Pause")).clicked() {
+                    actions.simulation.send(SimulationAction::Pause);
+                }
+                if ui.add_enabled(mode != EditorMode::Playing, egui::Button::new("⏭ Step")).clicked() {
+                    actions.simulation.send(SimulationAction::Step);
+                }
+                ui.add(egui::DragValue::new(&mut simulation.step_frames).clamp_range(1..=600).suffix(" frames"));
+                if ui.add_enabled(mode != EditorMode::Playing, egui::Button::new("⏯ 1 Step"))
+                    .on_hover_text("Advance exactly one physics step (.)")
+                    .clicked()
+                {
+                    actions.simulation.send(SimulationAction::StepOnce);
+                }
+                if ui.add_enabled(mode != EditorMode::Edit, egui::Button::new("⏹ Stop")).clicked() {
+                    actions.simulation.send(SimulationAction::Stop);
+                }
+                ui.add(egui::Slider::new(&mut simulation.time_scale, TIME_SCALE_RANGE)
+                    .logarithmic(true)
+                    .suffix("x")
+                    .text("Time scale"));
+                if ui.button("1x").clicked() {
+                    simulation.time_scale = 1.0;
+                }
+            });
+
+            // Simulation HUD
+            ui.group(|ui| {
+                ui.label(physics.kind().label())
+                    .on_hover_text("Physics backend, chosen at startup with --physics rapier|native");
+                ui.label(format!("t = {:.3} s", simulation.sim_time()));
+                ui.label(format!("{} steps", simulation.step_count()));
+                ui.label(if mode == EditorMode::Playing {
+                    format!("{:.2}x real time", simulation.real_time_factor())
+                } else {
+                    "-- real time".to_string()
+                });
+            });
+
+            ui.separator();
+
+            // History section
+            ui.group(|ui| {
+                let undo = ui.add_enabled(history.can_undo(), egui::Button::new("↩ Undo"));
+                let undo = match history.undo_label() {
+                    Some(label) => undo.on_hover_text(format!("Undo {} (Ctrl+Z)", label)),
+                    None => undo,
+                };
+                if undo.clicked() {
+                    history.request(HistoryRequest::Undo);
+                }
+
+                let redo = ui.add_enabled(history.can_redo(), egui::Button::new("↪ Redo"));
+                let redo = match history.redo_label() {
+                    Some(label) => redo.on_hover_text(format!("Redo {} (Ctrl+Shift+Z)", label)),
+                    None => redo,
+                };
+                if redo.clicked() {
+                    history.request(HistoryRequest::Redo);
+                }
+            });
+
+            ui.separator();
+
+            snapping_toolbar(ui, &mut snap, &mut actions.drop_to_surface, !ui_state.selection.is_empty());
+
             ui.separator();
             
             // Add objects dropdown
             ui.group(|ui| {
                 ui.label("Add Object:");
-                egui::ComboBox::from_id_source("add_object")
-                    .selected_text("➕ Add Object")
-                    .show_ui(ui, |ui| {
-                        if ui.selectable_label(false, "📦 Cube").clicked() {
-                            spawn_shape(&mut commands, &mut meshes, &mut materials, ShapeType::Cube, Vec3::new(0.0, 2.0, 0.0));
-                        }
-                        if ui.selectable_label(false, "⚪ Sphere").clicked() {
-                            spawn_shape(&mut commands, &mut meshes, &mut materials, ShapeType::Sphere, Vec3::new(0.0, 2.0, 0.0));
-                        }
-                        if ui.selectable_label(false, "🛢️ Cylinder").clicked() {
-                            spawn_shape(&mut commands, &mut meshes, &mut materials, ShapeType::Cylinder, Vec3::new(0.0, 2.0, 0.0));
+                ui.menu_button("➕ Add Object", |ui| {
+                    for (shape_type, icon) in [
+                        (ShapeType::Cube, "📦"),
+                        (ShapeType::Sphere, "⚪"),
+                        (ShapeType::Cylinder, "🛢️"),
+                        (ShapeType::Cone, "🔺"),
+                        (ShapeType::Capsule, "💊"),
+                    ] {
+                        if ui.button(format!("{} {}", icon, shape_type.label())).clicked() {
+                            spawn = Primitive::unit(shape_type);
+                            ui.close_menu();
                         }
-                        if ui.selectable_label(false, "🔺 Cone").clicked() {
-                            spawn_shape(&mut commands, &mut meshes, &mut materials, ShapeType::Cone, Vec3::new(0.0, 2.0, 0.0));
-                        }
-                        if ui.selectable_label(false, "💊 Capsule").clicked() {
-                            spawn_shape(&mut commands, &mut meshes, &mut materials, ShapeType::Capsule, Vec3::new(0.0, 2.0, 0.0));
-                        }
-                    });
+                    }
+                    ui.separator();
+                    for shape_type in ShapeType::PARAMETRIC {
+                        ui.menu_button(shape_type.label(), |ui| {
+                            let Some(primitive) = ui_state.new_primitive(shape_type) else {
+                                return;
+                            };
+                            primitive_editor(ui, primitive);
+                            if ui.button("➕ Add").clicked() {
+                                spawn = Some(*primitive);
+                                ui.close_menu();
+                            }
+                        });
+                    }
+                    ui.separator();
+                    if ui.button("📁 Import Mesh...").clicked() {
+                        actions.mesh_import.send(OpenMeshImport);
+                        ui.close_menu();
+                    }
+                });
+                ui.checkbox(&mut placement.follow_cursor, "Place with cursor")
+                    .on_hover_text("Click in the viewport to place new objects (Shift keeps placing, Esc cancels)");
             });
         });
     });
+
+    if let Some(primitive) = spawn {
+        actions.add_object.send(AddObject(primitive));
+    }
+}
+
+// Everything the Inspector shows about a selected object. Only the transform is required, the
+// physics components come and go with Add / Remove Physics.
+#[derive(WorldQuery)]
+#[world_query(mutable)]
+struct InspectedObject {
+    entity: Entity,
+    transform: &'static mut Transform,
+    rigid_body: Option<&'static mut RigidBody>,
+    collider: Option<&'static Collider>,
+    mass: Option<&'static mut ColliderMassProperties>,
+    damping: Option<&'static mut Damping>,
+    restitution: Option<&'static mut Restitution>,
+    friction: Option<&'static mut Friction>,
+    velocity: Option<&'static Velocity>,
+    sensor: Option<&'static Sensor>,
+    sleeping: Option<&'static mut Sleeping>,
+    mesh: Option<&'static Handle<Mesh>>,
+    material: Option<&'static Handle<StandardMaterial>>,
+    shape: Option<&'static mut PrimitiveShape>,
+    physics_material: Option<&'static PhysicsMaterialName>,
+}
+
+impl InspectedObjectReadOnlyItem<'_> {
+    fn physics(&self) -> PhysicsComponents {
+        PhysicsComponents {
+            rigid_body: self.rigid_body.copied(),
+            collider: self.collider.cloned(),
+            mass: self.mass.copied(),
+            damping: self.damping.copied(),
+            restitution: self.restitution.copied(),
+            friction: self.friction.copied(),
+            velocity: self.velocity.copied(),
+            sensor: self.sensor.copied(),
+            sleeping: self.sleeping.copied(),
+        }
+    }
+
+    // Names of the components the object carries, for the Components section
+    fn component_names(&self) -> Vec<&'static str> {
+        [
+            ("Transform", true),
+            ("Mesh", self.mesh.is_some()),
+            ("Material", self.material.is_some()),
+            ("Rigid Body", self.rigid_body.is_some()),
+            ("Collider", self.collider.is_some()),
+            ("Mass", self.mass.is_some()),
+            ("Damping", self.damping.is_some()),
+            ("Restitution", self.restitution.is_some()),
+            ("Friction", self.friction.is_some()),
+            ("Physics Material", self.physics_material.is_some_and(|name| name.0.is_some())),
+            ("Velocity", self.velocity.is_some()),
+            ("Sensor", self.sensor.is_some()),
+        ]
+        .into_iter()
+        .filter(|(_, present)| *present)
+        .map(|(name, _)| name)
+        .collect()
+    }
+}
+
+// The Inspector's editable values of one selected object, None where a component is missing
+#[derive(Clone, Copy, PartialEq)]
+struct InspectorValues {
+    transform: Transform,
+    rigid_body: Option<RigidBody>,
+    damping: Option<Damping>,
+    restitution: Option<Restitution>,
+    friction: Option<Friction>,
+    sleeping: Option<Sleeping>,
+}
+
+impl InspectorValues {
+    fn euler(&self) -> [f32; 3] {
+        let (x, y, z) = self.transform.rotation.to_euler(EulerRot::XYZ);
+        [x, y, z]
+    }
+
+    // Copies the fields edited on the primary selection (`before` -> `edited`) onto this object.
+    // Components this object doesn't have are left out.
+    fn with_edits(mut self, before: &Self, edited: &Self) -> Self {
+        let pick = |value: f32, before: f32, edited: f32| if edited != before { edited } else { value };
+
+        for axis in 0..3 {
+            self.transform.translation[axis] = pick(
+                self.transform.translation[axis],
+                before.transform.translation[axis],
+                edited.transform.translation[axis],
+            );
+            self.transform.scale[axis] = pick(
+                self.transform.scale[axis],
+                before.transform.scale[axis],
+                edited.transform.scale[axis],
+            );
+        }
+        if edited.transform.rotation != before.transform.rotation {
+            let (current, before, edited) = (self.euler(), before.euler(), edited.euler());
+            let [x, y, z] = [0, 1, 2].map(|axis| pick(current[axis], before[axis], edited[axis]));
+            self.transform.rotation = Quat::from_euler(EulerRot::XYZ, x, y, z);
+        }
+
+        if let (Some(damping), Some(before), Some(edited)) = (&mut self.damping, before.damping, edited.damping) {
+            damping.linear_damping = pick(damping.linear_damping, before.linear_damping, edited.linear_damping);
+            damping.angular_damping = pick(damping.angular_damping, before.angular_damping, edited.angular_damping);
+        }
+        if let (Some(restitution), Some(before), Some(edited)) = (&mut self.restitution, before.restitution, edited.restitution) {
+            restitution.coefficient = pick(restitution.coefficient, before.coefficient, edited.coefficient);
+            if edited.combine_rule != before.combine_rule {
+                restitution.combine_rule = edited.combine_rule;
+            }
+        }
+        if let (Some(friction), Some(before), Some(edited)) = (&mut self.friction, before.friction, edited.friction) {
+            friction.coefficient = pick(friction.coefficient, before.coefficient, edited.coefficient);
+            if edited.combine_rule != before.combine_rule {
+                friction.combine_rule = edited.combine_rule;
+            }
+        }
+        if let (Some(sleeping), Some(before), Some(edited)) = (&mut self.sleeping, before.sleeping, edited.sleeping) {
+            sleeping.linear_threshold = pick(sleeping.linear_threshold, before.linear_threshold, edited.linear_threshold);
+            sleeping.angular_threshold = pick(sleeping.angular_threshold, before.angular_threshold, edited.angular_threshold);
+        }
+        self
+    }
+}
+
+// Shows a dash instead of the number while the selected objects disagree on the value
+fn mixed_drag_value(ui: &mut egui::Ui, value: &mut f32, prefix: &str, speed: f64, mixed: bool) -> bool {
+    let mut drag = egui::DragValue::new(value).prefix(prefix).speed(speed);
+    if mixed {
+        drag = drag.custom_formatter(|_, _| "—".to_string());
+    }
+    ui.add(drag).changed()
 }
 
+fn mixed_slider(ui: &mut egui::Ui, value: &mut f32, text: &str, mixed: bool) -> bool {
+    let mut slider = egui::Slider::new(value, 0.0..=1.0).text(text);
+    if mixed {
+        slider = slider.custom_formatter(|_, _| "—".to_string());
+    }
+    ui.add(slider).changed()
+}
+
+const AXIS_PREFIXES: [&str; 3] = ["X: ", "Y: ", "Z: "];
+
+// Writes an edited value into the component when it changed, and records the change
+fn write_change<C: Component + Copy + PartialEq>(
+    entity: Entity,
+    component: Option<Mut<C>>,
+    before: Option<C>,
+    after: Option<C>,
+    changes: &mut Vec<(Entity, C, C)>,
+) {
+    if let (Some(mut component), Some(before), Some(after)) = (component, before, after) {
+        if after != before {
+            *component = after;
+            changes.push((entity, before, after));
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 fn ui_system(
     mut contexts: EguiContexts,
     mut ui_state: ResMut<UiState>,
-    mut query: Query<(
-        Entity,
-        &mut Transform,
-        &mut RigidBody,
-        Option<&Collider>,
-        Option<&ColliderMassProperties>,
-        &mut Damping,
-        &mut Restitution,
-        &mut Friction,
-    )>,
+    mut history: ResMut<History>,
+    snap: Res<SnapSettings>,
+    mut commands: Commands,
+    mut query: Query<InspectedObject, With<Selectable>>,
+    mut joints: JointQuery,
+    mut imported: Query<&mut ImportedMesh>,
+    labels: Query<(Option<&Name>, Option<&Locked>)>,
+    mut edit_actions: EventWriter<EditAction>,
+    meshes: Res<Assets<Mesh>>,
+    library: Res<MaterialLibrary>,
+    mut material_editor: MaterialEditor,
+    physics: Res<PhysicsWorld>,
+    simulation: Res<SimulationControl>,
 ) {
     // Reset UI click state at the start of each frame
     ui_state.ui_received_click = contexts.ctx_mut().is_pointer_over_area();
 
+    // Primary selection last, like `UiState::selection`
+    let targets: Vec<(Entity, InspectorValues)> = ui_state
+        .selection
+        .iter()
+        .filter(|entity| !labels.get(**entity).is_ok_and(|(_, locked)| is_locked(locked)))
+        .filter_map(|entity| query.get(*entity).ok())
+        .map(|object| {
+            (object.entity, InspectorValues {
+                transform: *object.transform,
+                rigid_body: object.rigid_body.copied(),
+                damping: object.damping.copied(),
+                restitution: object.restitution.copied(),
+                friction: object.friction.copied(),
+                sleeping: object.sleeping.copied(),
+            })
+        })
+        .collect();
+
     egui::Window::new("Inspector")
         .default_width(280.0)
         .default_height(600.0)
@@ -329,126 +857,391 @@ fn ui_system(
                 ui_state.ui_received_click = true;
             }
 
-            match ui_state.selected_entity {
-                Some(selected_entity) => {
-                    if let Ok((entity, mut transform, mut rigid_body, _collider, _mass, mut damping, mut restitution, mut friction)) 
-                        = query.get_mut(selected_entity) 
-                    {
-                        ui.label(format!("Entity {:?}", entity));
-
-                        // Transform section
-                        ui.collapsing("Transform", |ui| {
-                            let mut position = transform.translation.to_array();
-                            let mut rotation = transform.rotation.to_euler(EulerRot::XYZ);
-                            let mut scale = transform.scale.to_array();
-
-                            ui.group(|ui| {
-                                ui.label("Position");
-                                if ui.add(egui::DragValue::new(&mut position[0]).prefix("X: ").speed(0.1)).changed() {
-                                    transform.translation.x = position[0];
-                                }
-                                if ui.add(egui::DragValue::new(&mut position[1]).prefix("Y: ").speed(0.1)).changed() {
-                                    transform.translation.y = position[1];
-                                }
-                                if ui.add(egui::DragValue::new(&mut position[2]).prefix("Z: ").speed(0.1)).changed() {
-                                    transform.translation.z = position[2];
-                                }
-                            });
-
-                            ui.group(|ui| {
-                                ui.label("Rotation (radians)");
-                                if ui.add(egui::DragValue::new(&mut rotation.0).prefix("X: ").speed(0.01)).changed() {
-                                    transform.rotation = Quat::from_euler(EulerRot::XYZ, rotation.0, rotation.1, rotation.2);
-                                }
-                                if ui.add(egui::DragValue::new(&mut rotation.1).prefix("Y: ").speed(0.01)).changed() {
-                                    transform.rotation = Quat::from_euler(EulerRot::XYZ, rotation.0, rotation.1, rotation.2);
-                                }
-                                if ui.add(egui::DragValue::new(&mut rotation.2).prefix("Z: ").speed(0.01)).changed() {
-                                    transform.rotation = Quat::from_euler(EulerRot::XYZ, rotation.0, rotation.1, rotation.2);
-                                }
-                            });
-
-                            ui.group(|ui| {
-                                ui.label("Scale");
-                                if ui.add(egui::DragValue::new(&mut scale[0]).prefix("X: ").speed(0.1)).changed() {
-                                    transform.scale.x = scale[0];
-                                }
-                                if ui.add(egui::DragValue::new(&mut scale[1]).prefix("Y: ").speed(0.1)).changed() {
-                                    transform.scale.y = scale[1];
-                                }
-                                if ui.add(egui::DragValue::new(&mut scale[2]).prefix("Z: ").speed(0.1)).changed() {
-                                    transform.scale.z = scale[2];
-                                }
-
-                                if ui.button("Make Uniform").clicked() {
-                                    transform.scale = Vec3::splat(transform.scale.x);
-                                }
-                            });
+            let Some(&(primary, before)) = targets.last() else {
+                if ui_state.selection.is_empty() {
+                    ui.label("No entity selected");
+                    ui.label("Click an object to select it");
+                    ui.label("Shift-click to add, Ctrl-click to toggle, drag to box select");
+                } else if ui_state.selection.iter().any(|entity| labels.get(*entity).is_ok_and(|(_, locked)| is_locked(locked))) {
+                    ui.label("🔒 Selection is locked");
+                    ui.label("Unlock it in the Outliner to edit it");
+                } else {
+                    ui.label("Selected entity no longer exists");
+                }
+                return;
+            };
+
+            // Objects without the component don't count as disagreeing
+            let mixed = |field: &dyn Fn(&InspectorValues) -> Option<f32>| {
+                targets.iter().any(|(_, values)| field(values).is_some() && field(values) != field(&before))
+            };
+            let mut edited = before;
+            let mut make_uniform = false;
+            let mut set_body = None;
+            let mut assign_material = None;
+
+            if targets.len() > 1 {
+                ui.label(format!("{} objects selected", targets.len()));
+                ui.label("Edits apply to every selected object");
+            } else {
+                match labels.get(primary) {
+                    Ok((Some(name), _)) => ui.label(format!("{} ({:?})", name, primary)),
+                    _ => ui.label(format!("Entity {:?}", primary)),
+                };
+            }
+
+            if let Ok(object) = query.get(primary) {
+                ui.collapsing("Components", |ui| {
+                    ui.label(object.component_names().join(", "));
+                });
+            }
+
+            // Transform section
+            ui.collapsing("Transform", |ui| {
+                ui.group(|ui| {
+                    ui.label("Position");
+                    for (axis, prefix) in AXIS_PREFIXES.into_iter().enumerate() {
+                        let mut value = edited.transform.translation[axis];
+                        let mixed = mixed(&|values| Some(values.transform.translation[axis]));
+                        if mixed_drag_value(ui, &mut value, prefix, 0.1, mixed) {
+                            edited.transform.translation[axis] = snap.translation(value);
+                        }
+                    }
+                });
+
+                ui.group(|ui| {
+                    ui.label("Rotation (radians)");
+                    let mut rotation = edited.euler();
+                    for (axis, prefix) in AXIS_PREFIXES.into_iter().enumerate() {
+                        let mixed = mixed(&|values| Some(values.euler()[axis]));
+                        if mixed_drag_value(ui, &mut rotation[axis], prefix, 0.01, mixed) {
+                            rotation[axis] = snap.angle(rotation[axis]);
+                            edited.transform.rotation = Quat::from_euler(EulerRot::XYZ, rotation[0], rotation[1], rotation[2]);
+                        }
+                    }
+                });
+
+                ui.group(|ui| {
+                    ui.label("Scale");
+                    for (axis, prefix) in AXIS_PREFIXES.into_iter().enumerate() {
+                        let mut value = edited.transform.scale[axis];
+                        let mixed = mixed(&|values| Some(values.transform.scale[axis]));
+                        if mixed_drag_value(ui, &mut value, prefix, 0.1, mixed) {
+                            edited.transform.scale[axis] = snap.scale(value);
+                        }
+                    }
+
+                    if ui.button("Make Uniform").clicked() {
+                        make_uniform = true;
+                    }
+                });
+            });
+
+            // Physics section, one group per component the primary selection has
+            ui.collapsing("Physics", |ui| {
+                let Some(rigid_body) = before.rigid_body else {
+                    ui.label("No rigid body, the object is only visual");
+                    ui.label("Use Add Physics to simulate it");
+                    return;
+                };
+
+                ui.group(|ui| {
+                    let body_mixed = targets.iter().any(|(_, values)| {
+                        values.rigid_body.is_some_and(|body| body != rigid_body)
+                    });
+                    ui.label(if body_mixed { "Body Type (mixed)" } else { "Body Type" });
+                    ui.horizontal(|ui| {
+                        for (body, label) in [
+                            (RigidBody::Dynamic, "Dynamic"),
+                            (RigidBody::Fixed, "Fixed"),
+                            (RigidBody::KinematicPositionBased, "Kinematic"),
+                        ] {
+                            let current = !body_mixed && rigid_body == body;
+                            if ui.selectable_label(current, label).clicked() {
+                                set_body = Some(body);
+                            }
+                        }
+                    });
+                    // Any edit wakes the body up again
+                    if rigid_body == RigidBody::Dynamic && physics.is_sleeping(primary) {
+                        ui.label("💤 Sleeping");
+                    }
+                    // Speeds below which a resting body falls asleep
+                    if let Some(sleeping) = &mut edited.sleeping {
+                        ui.horizontal(|ui| {
+                            ui.label("Sleep below");
+                            let linear_mixed = mixed(&|values| values.sleeping.map(|sleeping| sleeping.linear_threshold));
+                            mixed_drag_value(ui, &mut sleeping.linear_threshold, "m/s: ", 0.01, linear_mixed);
+                            let angular_mixed = mixed(&|values| values.sleeping.map(|sleeping| sleeping.angular_threshold));
+                            mixed_drag_value(ui, &mut sleeping.angular_threshold, "rad/s: ", 0.01, angular_mixed);
                         });
+                        sleeping.linear_threshold = sleeping.linear_threshold.max(0.0);
+                        sleeping.angular_threshold = sleeping.angular_threshold.max(0.0);
+                    }
+                });
+
+                if let Ok(object) = query.get(primary) {
+                    let shape = match object.collider {
+                        Some(collider) => format!("{:?}", collider.raw.shape_type()),
+                        None => "None".to_string(),
+                    };
+                    ui.label(format!("Collider: {}", shape));
+
+                    let current = library.describe(object.physics_material, object.friction, object.restitution, object.mass);
+                    assign_material = physics_material_picker(ui, &library, current);
+                }
 
-                        // Physics section
-                        ui.collapsing("Physics", |ui| {
-                            ui.group(|ui| {
-                                ui.label("Body Type");
-                                ui.horizontal(|ui| {
-                                    if ui.selectable_label(*rigid_body == RigidBody::Dynamic, "Dynamic").clicked() {
-                                        *rigid_body = RigidBody::Dynamic;
-                                    }
-                                    if ui.selectable_label(*rigid_body == RigidBody::Fixed, "Fixed").clicked() {
-                                        *rigid_body = RigidBody::Fixed;
-                                    }
-                                    if ui.selectable_label(*rigid_body == RigidBody::KinematicPositionBased, "Kinematic").clicked() {
-                                        *rigid_body = RigidBody::KinematicPositionBased;
-                                    }
-                                });
-                            });
-
-                            ui.group(|ui| {
-                                ui.label("Damping");
-                                let mut linear = damping.linear_damping;
-                                let mut angular = damping.angular_damping;
-                                if ui.add(egui::Slider::new(&mut linear, 0.0..=1.0).text("Linear")).changed() {
-                                    damping.linear_damping = linear;
-                                }
-                                if ui.add(egui::Slider::new(&mut angular, 0.0..=1.0).text("Angular")).changed() {
-                                    damping.angular_damping = angular;
-                                }
-                            });
-
-                            ui.group(|ui| {
-                                ui.label("Restitution (Bounciness)");
-                                let mut value = restitution.coefficient;
-                                if ui.add(egui::Slider::new(&mut value, 0.0..=1.0)).changed() {
-                                    restitution.coefficient = value;
-                                }
-                            });
-
-                            ui.group(|ui| {
-                                ui.label("Friction");
-                                let mut value = friction.coefficient;
-                                if ui.add(egui::Slider::new(&mut value, 0.0..=1.0)).changed() {
-                                    friction.coefficient = value;
-                                }
-                            });
+                if let Some(damping) = &mut edited.damping {
+                    ui.group(|ui| {
+                        ui.label("Damping");
+                        let linear_mixed = mixed(&|values| values.damping.map(|damping| damping.linear_damping));
+                        mixed_slider(ui, &mut damping.linear_damping, "Linear", linear_mixed);
+                        let angular_mixed = mixed(&|values| values.damping.map(|damping| damping.angular_damping));
+                        mixed_slider(ui, &mut damping.angular_damping, "Angular", angular_mixed);
+                    });
+                }
+
+                if let Some(restitution) = &mut edited.restitution {
+                    ui.group(|ui| {
+                        ui.label("Restitution (Bounciness)");
+                        let restitution_mixed = mixed(&|values| values.restitution.map(|restitution| restitution.coefficient));
+                        ui.horizontal(|ui| {
+                            mixed_slider(ui, &mut restitution.coefficient, "", restitution_mixed);
+                            let mut rule = CombineRule::from(restitution.combine_rule);
+                            combine_rule_editor(ui, "restitution_combine", &mut rule);
+                            restitution.combine_rule = rule.into();
                         });
+                    });
+                }
 
-                        ui.separator();
+                if let Some(friction) = &mut edited.friction {
+                    ui.group(|ui| {
+                        ui.label("Friction");
+                        let friction_mixed = mixed(&|values| values.friction.map(|friction| friction.coefficient));
                         ui.horizontal(|ui| {
-                            if ui.button("Add Physics").clicked() {
-                                // TODO: Add physics components
-                            }
-                            if ui.button("Remove Physics").clicked() {
-                                // TODO: Remove physics components
-                            }
+                            mixed_slider(ui, &mut friction.coefficient, "", friction_mixed);
+                            let mut rule = CombineRule::from(friction.combine_rule);
+                            combine_rule_editor(ui, "friction_combine", &mut rule);
+                            friction.combine_rule = rule.into();
                         });
-                    } else {
-                        ui.label("Selected entity no longer exists");
+                    });
+                }
+            });
+
+            let others: Vec<Entity> = targets.iter().map(|(entity, _)| *entity).filter(|entity| *entity != primary).collect();
+            material_inspector(ui, primary, &others, &mut material_editor, &mut commands, &mut history);
+
+            // Shapes, mass, mesh colliders and joints belong to a single object
+            let mut shape_edit = None;
+            let mut mass_edit = None;
+            if targets.len() == 1 {
+                if let Some(&before) = query.get(primary).ok().and_then(|object| object.shape) {
+                    let mut edited = before;
+                    collider_inspector(ui, &mut edited);
+                    if edited != before {
+                        shape_edit = Some((before, edited));
                     }
                 }
-                None => {
-                    ui.label("No entity selected");
-                    ui.label("Click an object to select it");
+                if let Some(rigid_body) = set_body.or(before.rigid_body) {
+                    if let Ok(object) = query.get(primary) {
+                        if let Some(&before) = object.mass {
+                            let resulting = resulting_mass_properties(
+                                physics.mass_properties(primary),
+                                object.collider,
+                                Some(&before),
+                                simulation.active_timestep().is_some(),
+                            );
+                            let mut edited = before;
+                            mass_inspector(ui, &mut edited, resulting);
+                            if edited != before {
+                                mass_edit = Some((before, edited));
+                            }
+                        }
+                    }
+                    mesh_collider_inspector(ui, primary, &mut imported, rigid_body, &mut history);
+                    joints_inspector(ui, primary, &mut joints, &mut commands, &mut history);
+                }
+            }
+
+            ui.separator();
+            ui.horizontal(|ui| {
+                if ui.button("⧉ Duplicate").clicked() {
+                    edit_actions.send(EditAction::Duplicate);
+                }
+                if ui.button("🗑 Delete").clicked() {
+                    edit_actions.send(EditAction::Delete);
+                }
+            });
+
+            let can_add = targets.iter().any(|(_, values)| values.rigid_body.is_none());
+            let can_remove = targets
+                .iter()
+                .any(|(entity, _)| query.get(*entity).is_ok_and(|object| !object.physics().is_empty()));
+            let mut add_physics = false;
+            let mut remove_physics = false;
+            ui.horizontal(|ui| {
+                add_physics = ui.add_enabled(can_add, egui::Button::new("Add Physics"))
+                    .on_hover_text("Dynamic body with a collider fitted to the mesh")
+                    .clicked();
+                egui::ComboBox::from_id_source("collider_fit")
+                    .selected_text(ui_state.collider_fit.label())
+                    .show_ui(ui, |ui| {
+                        for fit in ColliderFit::ALL {
+                            ui.selectable_value(&mut ui_state.collider_fit, fit, fit.label());
+                        }
+                    });
+            });
+            ui.horizontal(|ui| {
+                remove_physics = ui.add_enabled(can_remove, egui::Button::new("Remove Physics"))
+                    .on_hover_text("Strips the body, collider and joints but keeps the mesh and material")
+                    .clicked();
+            });
+
+            if add_physics {
+                let mut group = CommandGroup::new("Add Physics");
+                for &(entity, values) in &targets {
+                    if values.rigid_body.is_some() {
+                        continue;
+                    }
+                    let Ok(object) = query.get(entity) else {
+                        continue;
+                    };
+                    let before = object.physics();
+                    // A collider the object already has is kept, built-in shapes get their exact collider
+                    // and anything else gets one wrapped around its mesh
+                    let collider = before.collider.clone().or_else(|| match object.shape {
+                        Some(shape) => Some(shape.collider()),
+                        None => fitted_collider(meshes.get(object.mesh?)?, ui_state.collider_fit),
+                    });
+                    let Some(collider) = collider else {
+                        warn!("Can't fit a collider to {:?}, it has no mesh geometry", entity);
+                        continue;
+                    };
+                    let mut after = before.clone().or(PhysicsComponents::defaults(collider));
+                    if object.shape.is_some_and(|shape| shape.sensor) {
+                        after.sensor = Some(Sensor);
+                    }
+                    after.clone().apply_deferred(&mut commands, entity);
+                    group.add(PhysicsChange::new(entity, before, after));
+                }
+                if !group.is_empty() {
+                    history.push(group);
+                    history.seal();
+                }
+                return;
+            }
+
+            if remove_physics {
+                let mut group = CommandGroup::new("Remove Physics");
+                let removed: Vec<Entity> = targets.iter().map(|(entity, _)| *entity).collect();
+                // Joints need a body on both ends. They are removed first so undo restores them last.
+                for (joint_entity, spec, joint, parent) in joints.iter() {
+                    let (body1, body2) = (joint.parent, parent.get());
+                    if removed.contains(&body1) || removed.contains(&body2) {
+                        commands.entity(joint_entity).despawn_recursive();
+                        group.add(JointLifetime::removed(joint_entity, body1, body2, *spec));
+                    }
                 }
+                for entity in removed {
+                    let Ok(object) = query.get(entity) else {
+                        continue;
+                    };
+                    let before = object.physics();
+                    if before.is_empty() {
+                        continue;
+                    }
+                    PhysicsComponents::default().apply_deferred(&mut commands, entity);
+                    group.add(PhysicsChange::new(entity, before, PhysicsComponents::default()));
+                }
+                if !group.is_empty() {
+                    history.push(group);
+                    history.seal();
+                }
+                return;
+            }
+
+            if let Some(material) = assign_material {
+                let mut group = CommandGroup::new(format!("Assign {}", material.name));
+                for &(entity, values) in &targets {
+                    if values.rigid_body.is_none() {
+                        continue;
+                    }
+                    let Ok(object) = query.get(entity) else {
+                        continue;
+                    };
+                    let before = (object.friction.copied(), object.restitution.copied(), object.mass.copied());
+                    material.assign(&mut commands, &mut group, entity, before, object.physics_material);
+                }
+                if !group.is_empty() {
+                    history.push(group);
+                    history.seal();
+                }
+                return;
+            }
+
+            if let Some((before, after)) = shape_edit {
+                if let Some(mut shape) = query.get_mut(primary).ok().and_then(|object| object.shape) {
+                    *shape = after;
+                    history.push(ComponentChange::new(primary, before, after));
+                }
+            }
+            if let Some((before, after)) = mass_edit {
+                if let Some(mut mass) = query.get_mut(primary).ok().and_then(|object| object.mass) {
+                    *mass = after;
+                    history.push(ComponentChange::new(primary, before, after));
+                }
+            }
+
+            if edited == before && !make_uniform && set_body.is_none() {
+                return;
+            }
+
+            // Apply the edit to the whole selection and record whatever changed this frame
+            let mut transforms = Vec::new();
+            let mut bodies = Vec::new();
+            let mut dampings = Vec::new();
+            let mut restitutions = Vec::new();
+            let mut frictions = Vec::new();
+            let mut sleepings = Vec::new();
+            for &(entity, values) in &targets {
+                let mut after = values.with_edits(&before, &edited);
+                if make_uniform {
+                    after.transform.scale = Vec3::splat(after.transform.scale.x);
+                }
+                if let Some(body) = set_body {
+                    after.rigid_body = after.rigid_body.map(|_| body);
+                }
+
+                let Ok(mut object) = query.get_mut(entity) else {
+                    continue;
+                };
+                if after.transform != values.transform {
+                    *object.transform = after.transform;
+                    transforms.push((entity, values.transform, after.transform));
+                }
+                write_change(entity, object.rigid_body, values.rigid_body, after.rigid_body, &mut bodies);
+                write_change(entity, object.damping, values.damping, after.damping, &mut dampings);
+                write_change(entity, object.restitution, values.restitution, after.restitution, &mut restitutions);
+                write_change(entity, object.friction, values.friction, after.friction, &mut frictions);
+                write_change(entity, object.sleeping, values.sleeping, after.sleeping, &mut sleepings);
+            }
+            if !transforms.is_empty() {
+                history.push(ComponentChange::batch(transforms));
+            }
+            if !bodies.is_empty() {
+                history.push(ComponentChange::batch(bodies));
+            }
+            if !dampings.is_empty() {
+                history.push(ComponentChange::batch(dampings));
+            }
+            if !restitutions.is_empty() {
+                history.push(ComponentChange::batch(restitutions));
+            }
+            if !frictions.is_empty() {
+                history.push(ComponentChange::batch(frictions));
+            }
+            if !sleepings.is_empty() {
+                history.push(ComponentChange::batch(sleepings));
             }
         });
 }
@@ -0,0 +1,347 @@
+use std::ops::RangeInclusive;
+
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use bevy_rapier3d::prelude::*;
+use bevy_rapier3d::rapier::dynamics::IntegrationParameters;
+use serde::{Deserialize, Serialize};
+
+use crate::joints::vec3_editor;
+use crate::physics::{BroadPhaseKind, Integrator};
+use crate::ui::Selectable;
+
+pub struct WorldSettingsPlugin;
+
+impl Plugin for WorldSettingsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<OpenWorldSettings>()
+           .init_resource::<WorldSettings>()
+           .init_resource::<WorldSettingsWindow>()
+           .add_systems(Update, (
+               open_world_settings,
+               world_settings_window.after(open_world_settings),
+               apply_world_settings.after(world_settings_window),
+               sync_body_settings.after(world_settings_window),
+           ));
+    }
+}
+
+// Sent by the Edit menu in the toolbar
+#[derive(Event)]
+pub struct OpenWorldSettings;
+
+// What the window allows, loaded scenes are clamped to the same ranges
+const ITERATIONS: RangeInclusive<usize> = 1..=64;
+const STEP_LENGTH: RangeInclusive<f32> = 0.001..=0.1;
+const SUBSTEPS: RangeInclusive<usize> = 1..=32;
+
+fn clamp_step(value: f32) -> f32 {
+    // `max` first so NaN ends up at the shortest step
+    value.max(*STEP_LENGTH.start()).min(*STEP_LENGTH.end())
+}
+
+// Serializable mirror of rapier's `TimestepMode`
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy, Debug)]
+pub enum TimestepData {
+    // Advances by the frame time, capped at `max_dt`
+    Variable { max_dt: f32, time_scale: f32, substeps: usize },
+    // Advances by `dt` every frame, however long the frame took
+    Fixed { dt: f32, substeps: usize },
+    // Steps by `dt` only when real time has caught up, bodies are interpolated in between
+    Interpolated { dt: f32, time_scale: f32, substeps: usize },
+}
+
+impl From<TimestepMode> for TimestepData {
+    fn from(mode: TimestepMode) -> Self {
+        match mode {
+            TimestepMode::Variable { max_dt, time_scale, substeps } => TimestepData::Variable { max_dt, time_scale, substeps },
+            TimestepMode::Fixed { dt, substeps } => TimestepData::Fixed { dt, substeps },
+            TimestepMode::Interpolated { dt, time_scale, substeps } => TimestepData::Interpolated { dt, time_scale, substeps },
+        }
+    }
+}
+
+impl From<TimestepData> for TimestepMode {
+    fn from(mode: TimestepData) -> Self {
+        match mode {
+            TimestepData::Variable { max_dt, time_scale, substeps } => TimestepMode::Variable { max_dt, time_scale, substeps },
+            TimestepData::Fixed { dt, substeps } => TimestepMode::Fixed { dt, substeps },
+            TimestepData::Interpolated { dt, time_scale, substeps } => TimestepMode::Interpolated { dt, time_scale, substeps },
+        }
+    }
+}
+
+impl TimestepData {
+    fn label(self) -> &'static str {
+        match self {
+            TimestepData::Variable { .. } => "Variable",
+            TimestepData::Fixed { .. } => "Fixed",
+            TimestepData::Interpolated { .. } => "Interpolated",
+        }
+    }
+
+    // Length of one step, or the longest one for a variable timestep
+    pub fn step_length(self) -> f32 {
+        match self {
+            TimestepData::Variable { max_dt, .. } => max_dt,
+            TimestepData::Fixed { dt, .. } | TimestepData::Interpolated { dt, .. } => dt,
+        }
+    }
+
+    pub fn substeps(self) -> usize {
+        match self {
+            TimestepData::Variable { substeps, .. }
+            | TimestepData::Fixed { substeps, .. }
+            | TimestepData::Interpolated { substeps, .. } => substeps,
+        }
+    }
+
+    // Fixed steps have no time scale, so their dt is scaled instead
+    pub fn with_time_scale(self, scale: f32) -> Self {
+        match self {
+            TimestepData::Variable { max_dt, substeps, .. } => TimestepData::Variable { max_dt, time_scale: scale, substeps },
+            TimestepData::Fixed { dt, substeps } => TimestepData::Fixed { dt: dt * scale, substeps },
+            TimestepData::Interpolated { dt, substeps, .. } => TimestepData::Interpolated { dt, time_scale: scale, substeps },
+        }
+    }
+
+    fn clamped(self) -> Self {
+        let substeps = |substeps: usize| substeps.clamp(*SUBSTEPS.start(), *SUBSTEPS.end());
+        match self {
+            TimestepData::Variable { max_dt, time_scale, substeps: count } => TimestepData::Variable { max_dt: clamp_step(max_dt), time_scale, substeps: substeps(count) },
+            TimestepData::Fixed { dt, substeps: count } => TimestepData::Fixed { dt: clamp_step(dt), substeps: substeps(count) },
+            TimestepData::Interpolated { dt, time_scale, substeps: count } => TimestepData::Interpolated { dt: clamp_step(dt), time_scale, substeps: substeps(count) },
+        }
+    }
+
+    // The same step length and substeps in another mode
+    fn with_kind(self, kind: &str) -> Self {
+        let (dt, time_scale, substeps) = match self {
+            TimestepData::Variable { max_dt, time_scale, substeps } => (max_dt, time_scale, substeps),
+            TimestepData::Fixed { dt, substeps } => (dt, 1.0, substeps),
+            TimestepData::Interpolated { dt, time_scale, substeps } => (dt, time_scale, substeps),
+        };
+        match kind {
+            "Fixed" => TimestepData::Fixed { dt, substeps },
+            "Interpolated" => TimestepData::Interpolated { dt, time_scale, substeps },
+            _ => TimestepData::Variable { max_dt: dt, time_scale, substeps },
+        }
+    }
+}
+
+// Gravity, stepping and solver settings of the whole simulation, saved with the scene
+#[derive(Resource, Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct WorldSettings {
+    pub gravity: [f32; 3],
+    pub timestep: TimestepData,
+    pub velocity_iterations: usize,
+    pub friction_iterations: usize,
+    pub stabilization_iterations: usize,
+    // Continuous collision detection on every dynamic body, so fast ones don't tunnel through thin colliders
+    pub ccd: bool,
+    pub max_ccd_substeps: usize,
+    // Added in scene version 10
+    #[serde(default)]
+    pub integrator: Integrator,
+    // Added in scene version 11
+    #[serde(default)]
+    pub broad_phase: BroadPhaseKind,
+}
+
+// Whatever rapier starts with
+impl Default for WorldSettings {
+    fn default() -> Self {
+        let config = RapierConfiguration::default();
+        let params = IntegrationParameters::default();
+        Self {
+            gravity: config.gravity.to_array(),
+            timestep: config.timestep_mode.into(),
+            velocity_iterations: params.max_velocity_iterations,
+            friction_iterations: params.max_velocity_friction_iterations,
+            stabilization_iterations: params.max_stabilization_iterations,
+            ccd: false,
+            max_ccd_substeps: params.max_ccd_substeps,
+            integrator: Integrator::default(),
+            broad_phase: BroadPhaseKind::default(),
+        }
+    }
+}
+
+impl WorldSettings {
+    // Hand-edited scene files may hold values the window wouldn't allow, a zero step would hang stepping
+    pub fn clamped(self) -> Self {
+        let iterations = |iterations: usize| iterations.clamp(*ITERATIONS.start(), *ITERATIONS.end());
+        Self {
+            timestep: self.timestep.clamped(),
+            velocity_iterations: iterations(self.velocity_iterations),
+            friction_iterations: iterations(self.friction_iterations),
+            stabilization_iterations: iterations(self.stabilization_iterations),
+            max_ccd_substeps: iterations(self.max_ccd_substeps),
+            ..self
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+struct WorldSettingsWindow {
+    open: bool,
+}
+
+fn open_world_settings(
+    mut events: EventReader<OpenWorldSettings>,
+    mut window: ResMut<WorldSettingsWindow>,
+) {
+    for _ in events.read() {
+        window.open = true;
+    }
+}
+
+fn iterations_editor(ui: &mut egui::Ui, value: &mut usize, label: &str) {
+    ui.add(egui::DragValue::new(value).prefix(label).clamp_range(ITERATIONS));
+}
+
+fn step_editor(ui: &mut egui::Ui, value: &mut f32, label: &str) {
+    ui.add(egui::DragValue::new(value)
+        .prefix(label)
+        .suffix(" s")
+        .speed(0.0005)
+        .max_decimals(4)
+        .clamp_range(STEP_LENGTH));
+}
+
+fn substeps_editor(ui: &mut egui::Ui, value: &mut usize) {
+    ui.add(egui::DragValue::new(value).prefix("Substeps: ").clamp_range(SUBSTEPS));
+}
+
+fn world_settings_window(
+    mut contexts: EguiContexts,
+    mut window: ResMut<WorldSettingsWindow>,
+    mut settings: ResMut<WorldSettings>,
+) {
+    if !window.open {
+        return;
+    }
+
+    // Edited on a copy so the settings are only marked changed when something was edited
+    let mut edited = settings.clone();
+    egui::Window::new("World Settings")
+        .open(&mut window.open)
+        .default_width(300.0)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.collapsing("Gravity", |ui| {
+                let mut gravity = Vec3::from_array(edited.gravity);
+                vec3_editor(ui, "m/s²", &mut gravity);
+                edited.gravity = gravity.to_array();
+                ui.horizontal(|ui| {
+                    for (label, gravity) in [("Earth", -9.81), ("Moon", -1.62), ("Mars", -3.71), ("Zero-G", 0.0)] {
+                        if ui.button(label).clicked() {
+                            edited.gravity = [0.0, gravity, 0.0];
+                        }
+                    }
+                });
+            });
+
+            ui.collapsing("Timestep", |ui| {
+                ui.horizontal(|ui| {
+                    for kind in ["Variable", "Fixed", "Interpolated"] {
+                        if ui.selectable_label(edited.timestep.label() == kind, kind).clicked() {
+                            edited.timestep = edited.timestep.with_kind(kind);
+                        }
+                    }
+                });
+                match &mut edited.timestep {
+                    TimestepData::Variable { max_dt, substeps, .. } => {
+                        step_editor(ui, max_dt, "Max dt: ");
+                        substeps_editor(ui, substeps);
+                    }
+                    TimestepData::Fixed { dt, substeps } => {
+                        step_editor(ui, dt, "dt: ");
+                        substeps_editor(ui, substeps);
+                        ui.label("Runs slower or faster than real time when the frame rate differs from 1 / dt");
+                    }
+                    TimestepData::Interpolated { dt, substeps, .. } => {
+                        step_editor(ui, dt, "dt: ");
+                        substeps_editor(ui, substeps);
+                    }
+                }
+                ui.label("Slow motion and fast forward are set with the time scale in the toolbar");
+            });
+
+            ui.collapsing("Solver", |ui| {
+                iterations_editor(ui, &mut edited.velocity_iterations, "Velocity iterations: ");
+                iterations_editor(ui, &mut edited.friction_iterations, "Friction iterations: ");
+                iterations_editor(ui, &mut edited.stabilization_iterations, "Stabilization iterations: ");
+                egui::ComboBox::from_label("Integrator")
+                    .selected_text(edited.integrator.label())
+                    .show_ui(ui, |ui| {
+                        for integrator in Integrator::ALL {
+                            ui.selectable_value(&mut edited.integrator, integrator, integrator.label());
+                        }
+                    })
+                    .response
+                    .on_hover_text("Used by the native backend, rapier always integrates semi-implicitly");
+                egui::ComboBox::from_label("Broad phase")
+                    .selected_text(edited.broad_phase.label())
+                    .show_ui(ui, |ui| {
+                        for kind in BroadPhaseKind::ALL {
+                            ui.selectable_value(&mut edited.broad_phase, kind, kind.label());
+                        }
+                    })
+                    .response
+                    .on_hover_text("Used by the native backend, rapier has its own sweep and prune");
+            });
+
+            ui.collapsing("Continuous Collision Detection", |ui| {
+                ui.checkbox(&mut edited.ccd, "CCD on dynamic bodies")
+                    .on_hover_text("Used by rapier, the native backend has no continuous collision detection");
+                iterations_editor(ui, &mut edited.max_ccd_substeps, "Max CCD substeps: ");
+            });
+
+            ui.separator();
+            if ui.button("Reset to Defaults").clicked() {
+                edited = WorldSettings::default();
+            }
+        });
+
+    if edited != *settings {
+        *settings = edited;
+    }
+}
+
+fn apply_world_settings(
+    settings: Res<WorldSettings>,
+    mut rapier_config: ResMut<RapierConfiguration>,
+    mut rapier_context: ResMut<RapierContext>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+    // The timestep is applied by the simulation, together with its time scale
+    rapier_config.gravity = Vec3::from_array(settings.gravity);
+
+    let params = &mut rapier_context.integration_parameters;
+    params.max_velocity_iterations = settings.velocity_iterations;
+    params.max_velocity_friction_iterations = settings.friction_iterations;
+    params.max_stabilization_iterations = settings.stabilization_iterations;
+    params.max_ccd_substeps = settings.max_ccd_substeps;
+}
+
+type BodySettingsQuery<'w, 's> = Query<'w, 's, (
+    Entity,
+    &'static RigidBody,
+    Option<&'static Ccd>,
+), With<Selectable>>;
+
+// Keeps CCD in line with the settings, also on bodies added later
+fn sync_body_settings(
+    mut commands: Commands,
+    settings: Res<WorldSettings>,
+    bodies: BodySettingsQuery,
+) {
+    for (entity, rigid_body, ccd) in bodies.iter() {
+        let wants_ccd = *rigid_body == RigidBody::Dynamic && settings.ccd;
+        if ccd.is_some_and(|ccd| ccd.enabled) != wants_ccd {
+            commands.entity(entity).insert(Ccd { enabled: wants_ccd });
+        }
+    }
+}